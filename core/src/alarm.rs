@@ -0,0 +1,356 @@
+use crate::clock::{Weekday, SECONDS_PER_DAY};
+use heapless::{consts::*, String};
+use ufmt::{uDisplay, uWrite, Formatter};
+
+pub const ALARM_COUNT: usize = 4;
+pub const SNOOZE_SECONDS: u32 = 9 * 60;
+
+// How late the alarm interrupt may be handled and still count as a hit
+const FIRE_TOLERANCE: u32 = 60;
+
+/// Set of weekdays an alarm repeats on, bit 0 is Monday and bit 6 Sunday.
+/// An empty set means the alarm fires once and then disables itself.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    pub const ONCE: Self = Self(0);
+    pub const WORKDAYS: Self = Self(0b0001_1111);
+    pub const WEEKEND: Self = Self(0b0110_0000);
+    pub const EVERY_DAY: Self = Self(0b0111_1111);
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self(bits & Self::EVERY_DAY.0)
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_once(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(self, day: Weekday) -> bool {
+        self.0 & (1 << day.index()) != 0
+    }
+
+    pub fn toggle(&mut self, day: Weekday) {
+        self.0 ^= 1 << day.index();
+    }
+}
+
+impl uDisplay for Weekdays {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let mut text: String<U7> = String::new();
+        for (bit, letter) in "MTWTFSS".chars().enumerate() {
+            let _ = text.push(if self.0 & (1 << bit) != 0 {
+                letter
+            } else {
+                '-'
+            });
+        }
+        f.write_str(&text)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Alarm {
+    pub enabled: bool,
+    pub hours: u8,
+    pub minutes: u8,
+    pub repeat: Weekdays,
+}
+
+impl Alarm {
+    pub const fn new(hours: u8, minutes: u8, repeat: Weekdays) -> Self {
+        Self {
+            enabled: true,
+            hours,
+            minutes,
+            repeat,
+        }
+    }
+
+    pub const fn disabled() -> Self {
+        Self {
+            enabled: false,
+            hours: 0,
+            minutes: 0,
+            repeat: Weekdays::ONCE,
+        }
+    }
+
+    /// First RTC timestamp strictly after `now` at which the alarm goes off
+    pub fn next_fire(&self, now: u32) -> Option<u32> {
+        if !self.enabled {
+            return None;
+        }
+
+        let today = now / SECONDS_PER_DAY;
+        let offset = self.hours as u32 * 3600 + self.minutes as u32 * 60;
+        // A week and a day covers every weekday even when today's slot has passed
+        (today..today + 8)
+            .filter(|&day| self.repeat.is_once() || self.repeat.contains(Weekday::from_days(day)))
+            .map(|day| day * SECONDS_PER_DAY + offset)
+            .find(|&at| at > now)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AlarmState {
+    Idle,
+    Ringing(usize),
+    Snoozed { alarm: usize, until: u32 },
+}
+
+pub struct Alarms {
+    alarms: [Alarm; ALARM_COUNT],
+    state: AlarmState,
+    /// Minute the last alarm went off, so a second interrupt within the
+    /// tolerance does not ring it again after a dismiss
+    fired: Option<u32>,
+}
+
+impl Default for Alarms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Alarms {
+    pub fn new() -> Self {
+        let mut alarms = [Alarm::disabled(); ALARM_COUNT];
+        alarms[0] = Alarm {
+            enabled: false,
+            ..Alarm::new(7, 0, Weekdays::WORKDAYS)
+        };
+        Self {
+            alarms,
+            state: AlarmState::Idle,
+            fired: None,
+        }
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Alarm> {
+        self.alarms.get(idx)
+    }
+
+    pub fn set(&mut self, idx: usize, alarm: Alarm) {
+        if let Some(slot) = self.alarms.get_mut(idx) {
+            *slot = alarm;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Alarm> {
+        self.alarms.iter()
+    }
+
+    pub fn state(&self) -> AlarmState {
+        self.state
+    }
+
+    pub fn is_ringing(&self) -> bool {
+        matches!(self.state, AlarmState::Ringing(_))
+    }
+
+    /// Earliest timestamp after `now` at which an alarm or a pending snooze fires
+    pub fn next_fire(&self, now: u32) -> Option<u32> {
+        let snooze = match self.state {
+            AlarmState::Snoozed { until, .. } => Some(until),
+            _ => None,
+        };
+        self.alarms
+            .iter()
+            .filter_map(|alarm| alarm.next_fire(now))
+            .chain(snooze)
+            .min()
+    }
+
+    /// Handles the RTC alarm interrupt, returns true when an alarm started ringing
+    pub fn trigger(&mut self, now: u32) -> bool {
+        if let AlarmState::Snoozed { alarm, until } = self.state {
+            if until <= now {
+                self.state = AlarmState::Ringing(alarm);
+                return true;
+            }
+        }
+
+        let since = now.saturating_sub(FIRE_TOLERANCE);
+        let fired = self.fired;
+        let due = self.alarms.iter().enumerate().find_map(|(idx, alarm)| {
+            let at = alarm.next_fire(since)?;
+            if at <= now && Some(at) != fired {
+                Some((idx, at))
+            } else {
+                None
+            }
+        });
+        match due {
+            Some((idx, at)) => {
+                self.state = AlarmState::Ringing(idx);
+                self.fired = Some(at);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn snooze(&mut self, now: u32) {
+        if let AlarmState::Ringing(alarm) = self.state {
+            self.state = AlarmState::Snoozed {
+                alarm,
+                until: now + SNOOZE_SECONDS,
+            };
+        }
+    }
+
+    /// Stops a ringing or snoozed alarm, one-shot alarms get disabled
+    pub fn dismiss(&mut self) {
+        match self.state {
+            AlarmState::Ringing(alarm) | AlarmState::Snoozed { alarm, .. } => {
+                if self.alarms[alarm].repeat.is_once() {
+                    self.alarms[alarm].enabled = false;
+                }
+            }
+            AlarmState::Idle => {}
+        }
+        self.state = AlarmState::Idle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{DateTime, Time};
+    use ufmt::uwrite;
+
+    // 2021-01-04 is a Monday
+    fn at(day: u8, hours: u8, minutes: u8) -> u32 {
+        let time = Time {
+            hours,
+            minutes,
+            seconds: 0,
+        };
+        u32::from(&DateTime::new(2021, 1, day, time).unwrap())
+    }
+
+    #[test]
+    fn weekday_masks() {
+        let monday = Weekday::from_days(at(4, 0, 0) / SECONDS_PER_DAY);
+        let saturday = Weekday::from_days(at(9, 0, 0) / SECONDS_PER_DAY);
+        assert_eq!(monday, Weekday::Monday);
+        assert_eq!(saturday, Weekday::Saturday);
+        assert!(Weekdays::WORKDAYS.contains(monday));
+        assert!(!Weekdays::WORKDAYS.contains(saturday));
+        assert!(Weekdays::WEEKEND.contains(saturday));
+        assert!(Weekdays::ONCE.is_once());
+        assert_eq!(Weekdays::from_bits(0xff), Weekdays::EVERY_DAY);
+
+        let mut days = Weekdays::WORKDAYS;
+        days.toggle(Weekday::Wednesday);
+        days.toggle(Weekday::Sunday);
+        assert_eq!(days.bits(), 0b0101_1011);
+        let mut text: String<U8> = String::new();
+        uwrite!(text, "{}", days).unwrap();
+        assert_eq!(text.as_str(), "MT-TF-S");
+    }
+
+    #[test]
+    fn next_fire_is_strictly_later() {
+        let once = Alarm::new(7, 30, Weekdays::ONCE);
+        assert_eq!(once.next_fire(at(4, 6, 0)), Some(at(4, 7, 30)));
+        assert_eq!(once.next_fire(at(4, 7, 30)), Some(at(5, 7, 30)));
+        assert_eq!(once.next_fire(at(4, 23, 59)), Some(at(5, 7, 30)));
+        assert_eq!(Alarm::disabled().next_fire(at(4, 6, 0)), None);
+    }
+
+    #[test]
+    fn next_fire_skips_to_the_repeat_days() {
+        let workdays = Alarm::new(7, 0, Weekdays::WORKDAYS);
+        // Friday after the alarm, then the weekend
+        assert_eq!(workdays.next_fire(at(8, 8, 0)), Some(at(11, 7, 0)));
+        assert_eq!(workdays.next_fire(at(9, 6, 0)), Some(at(11, 7, 0)));
+
+        let weekend = Alarm::new(9, 15, Weekdays::WEEKEND);
+        assert_eq!(weekend.next_fire(at(4, 10, 0)), Some(at(9, 9, 15)));
+
+        // Only Monday, checked on Monday after the alarm: a week later
+        let monday = Alarm::new(6, 0, Weekdays::from_bits(1));
+        assert_eq!(monday.next_fire(at(4, 6, 1)), Some(at(11, 6, 0)));
+    }
+
+    #[test]
+    fn trigger_accepts_a_late_interrupt() {
+        let mut alarms = Alarms::new();
+        alarms.set(1, Alarm::new(7, 0, Weekdays::EVERY_DAY));
+        assert!(!alarms.trigger(at(4, 6, 59)));
+        assert!(alarms.trigger(at(4, 7, 0) + 30));
+        assert_eq!(alarms.state(), AlarmState::Ringing(1));
+
+        alarms.dismiss();
+        assert!(!alarms.trigger(at(4, 7, 2)));
+        assert_eq!(alarms.state(), AlarmState::Idle);
+    }
+
+    #[test]
+    fn dismissed_alarm_stays_quiet() {
+        let mut alarms = Alarms::new();
+        alarms.set(0, Alarm::new(7, 0, Weekdays::EVERY_DAY));
+        alarms.set(1, Alarm::new(7, 0, Weekdays::WORKDAYS));
+        let ring = at(4, 7, 0);
+        assert!(alarms.trigger(ring));
+        alarms.dismiss();
+
+        // Woken from STOP again within the tolerance, neither alarm of that
+        // minute rings a second time
+        assert!(!alarms.trigger(ring + 10));
+        assert!(!alarms.trigger(ring + FIRE_TOLERANCE));
+        assert_eq!(alarms.state(), AlarmState::Idle);
+
+        // A second interrupt while ringing keeps the alarm ringing
+        let next = at(5, 7, 0);
+        assert!(alarms.trigger(next));
+        assert!(!alarms.trigger(next + 10));
+        assert_eq!(alarms.state(), AlarmState::Ringing(0));
+    }
+
+    #[test]
+    fn snooze_rings_again() {
+        let mut alarms = Alarms::new();
+        alarms.set(0, Alarm::new(7, 0, Weekdays::ONCE));
+        let ring = at(4, 7, 0);
+        assert!(alarms.trigger(ring));
+
+        alarms.snooze(ring + 5);
+        let until = ring + 5 + SNOOZE_SECONDS;
+        assert_eq!(alarms.state(), AlarmState::Snoozed { alarm: 0, until });
+        assert_eq!(alarms.next_fire(ring + 5), Some(until));
+        assert!(!alarms.trigger(until - 1));
+        assert!(alarms.trigger(until));
+        assert_eq!(alarms.state(), AlarmState::Ringing(0));
+
+        // Snoozing only applies to a ringing alarm
+        alarms.dismiss();
+        alarms.snooze(until);
+        assert_eq!(alarms.state(), AlarmState::Idle);
+    }
+
+    #[test]
+    fn dismiss_disables_one_shot_alarms() {
+        let mut alarms = Alarms::new();
+        alarms.set(0, Alarm::new(7, 0, Weekdays::ONCE));
+        alarms.set(1, Alarm::new(7, 0, Weekdays::EVERY_DAY));
+        assert!(alarms.trigger(at(4, 7, 0)));
+        alarms.snooze(at(4, 7, 0));
+        alarms.dismiss();
+        assert!(!alarms.get(0).unwrap().enabled);
+        assert_eq!(alarms.next_fire(at(4, 7, 0)), Some(at(5, 7, 0)));
+
+        alarms.set(0, Alarm::new(6, 0, Weekdays::ONCE));
+        alarms.trigger(at(5, 7, 0));
+        assert_eq!(alarms.state(), AlarmState::Ringing(1));
+        alarms.dismiss();
+        assert!(alarms.get(1).unwrap().enabled);
+    }
+}
@@ -0,0 +1,251 @@
+// Backlight of the display, dimmed through the duty cycle of a PWM channel.
+// Brightness is set in levels, changes fade over `FADE_MS` stepped from the
+// timer tick like the tone sequencer.
+
+use embedded_hal::Pwm;
+
+/// Brightness levels above off, the highest is full brightness
+pub const LEVELS: u8 = 10;
+/// Duration of a fade across the whole range
+const FADE_MS: u32 = 500;
+const FULL: u32 = 1000;
+
+/// Brightness in per mille of full for a level. The eye is more sensitive to
+/// changes in the dark, so the lower levels are closer together.
+pub fn level_permille(level: u8) -> u32 {
+    let level = level.min(LEVELS) as u32;
+    level * level * FULL / (LEVELS as u32 * LEVELS as u32)
+}
+
+/// Steps the brightness towards a target, independent of the PWM hardware
+pub struct Fader {
+    current: u32,
+    target: u32,
+}
+
+impl Default for Fader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fader {
+    /// Starts off
+    pub const fn new() -> Self {
+        Self {
+            current: 0,
+            target: 0,
+        }
+    }
+
+    /// Fades to `permille` of full brightness from wherever it is now
+    pub fn set(&mut self, permille: u32) {
+        self.target = permille.min(FULL);
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.current != self.target
+    }
+
+    /// Advances the fade by `elapsed_ms`, returns the new brightness if it changed
+    pub fn tick(&mut self, elapsed_ms: u32) -> Option<u32> {
+        if !self.is_fading() {
+            return None;
+        }
+        let step = (elapsed_ms * FULL / FADE_MS).max(1);
+        self.current = if self.current < self.target {
+            (self.current + step).min(self.target)
+        } else {
+            self.current.saturating_sub(step).max(self.target)
+        };
+        Some(self.current)
+    }
+}
+
+pub struct Backlight<P: Pwm> {
+    pwm: P,
+    channel: P::Channel,
+    fader: Fader,
+    level: u8,
+}
+
+impl<P> Backlight<P>
+where
+    P: Pwm<Duty = u16>,
+    P::Channel: Copy,
+{
+    /// Starts off, `set_level` fades it in
+    pub fn new(mut pwm: P, channel: P::Channel) -> Self {
+        pwm.set_duty(channel, 0);
+        pwm.enable(channel);
+        Self {
+            pwm,
+            channel,
+            fader: Fader::new(),
+            level: 0,
+        }
+    }
+
+    /// Fades to the level, 0 is off
+    pub fn set_level(&mut self, level: u8) {
+        let level = level.min(LEVELS);
+        if level != self.level {
+            self.level = level;
+            self.fader.set(level_permille(level));
+        }
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fader.is_fading()
+    }
+
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        if let Some(permille) = self.fader.tick(elapsed_ms) {
+            let duty = self.pwm.get_max_duty() as u32 * permille / FULL;
+            self.pwm.set_duty(self.channel, duty as u16);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    const MAX_DUTY: u16 = 4800;
+
+    /// Keeps the duty of its single channel
+    struct FakePwm {
+        duty: u16,
+        enabled: bool,
+    }
+
+    impl Pwm for FakePwm {
+        type Channel = ();
+        type Time = u32;
+        type Duty = u16;
+
+        fn disable(&mut self, _: ()) {
+            self.enabled = false;
+        }
+
+        fn enable(&mut self, _: ()) {
+            self.enabled = true;
+        }
+
+        fn get_period(&self) -> u32 {
+            1000
+        }
+
+        fn get_duty(&self, _: ()) -> u16 {
+            self.duty
+        }
+
+        fn get_max_duty(&self) -> u16 {
+            MAX_DUTY
+        }
+
+        fn set_duty(&mut self, _: (), duty: u16) {
+            assert!(duty <= MAX_DUTY);
+            self.duty = duty;
+        }
+
+        fn set_period<T: Into<u32>>(&mut self, _: T) {}
+    }
+
+    /// Ticks every 10 ms until the fade ends, returns the ticks taken
+    fn fade(fader: &mut Fader) -> u32 {
+        let mut ticks = 0;
+        while fader.tick(10).is_some() {
+            ticks += 1;
+        }
+        ticks
+    }
+
+    #[test]
+    fn levels_follow_a_square_curve() {
+        assert_eq!(level_permille(0), 0);
+        assert_eq!(level_permille(1), 10);
+        assert_eq!(level_permille(5), 250);
+        assert_eq!(level_permille(LEVELS), FULL);
+        assert_eq!(level_permille(LEVELS + 5), FULL);
+    }
+
+    #[test]
+    fn fades_in_steps() {
+        let mut fader = Fader::new();
+        assert_eq!(fader.tick(10), None);
+        fader.set(FULL);
+        assert!(fader.is_fading());
+        assert_eq!(fader.tick(10), Some(20));
+        assert_eq!(fader.tick(10), Some(40));
+        // The whole range takes FADE_MS
+        assert_eq!(fade(&mut fader), FADE_MS / 10 - 2);
+        assert!(!fader.is_fading());
+
+        // Stops at the target instead of stepping past it
+        fader.set(250);
+        assert_eq!(fader.tick(400), Some(250));
+        assert_eq!(fader.tick(400), None);
+        // A tick shorter than one step still moves
+        fader.set(0);
+        assert_eq!(fader.tick(0), Some(249));
+        fader.set(500);
+        assert_eq!(fader.tick(100), Some(449));
+    }
+
+    #[test]
+    fn duty_is_clamped_to_full() {
+        let mut fader = Fader::new();
+        fader.set(FULL + 1);
+        assert_eq!(fader.tick(FADE_MS * 2), Some(FULL));
+
+        let pwm = FakePwm {
+            duty: 100,
+            enabled: false,
+        };
+        let mut backlight = Backlight::new(pwm, ());
+        assert_eq!((backlight.pwm.duty, backlight.pwm.enabled), (0, true));
+        backlight.set_level(LEVELS + 3);
+        backlight.tick(FADE_MS);
+        assert_eq!(backlight.pwm.duty, MAX_DUTY);
+        assert!(!backlight.is_fading());
+
+        backlight.set_level(5);
+        backlight.tick(FADE_MS);
+        assert_eq!(backlight.pwm.duty, MAX_DUTY / 4);
+    }
+
+    #[test]
+    fn night_schedule_crosses_midnight() {
+        let settings = Settings {
+            brightness: 8,
+            night_brightness: 1,
+            night_start: 22,
+            night_end: 7,
+            ..Settings::default()
+        };
+        let mut levels = [0; 24];
+        for (hour, level) in levels.iter_mut().enumerate() {
+            *level = settings.brightness_at(hour as u8);
+        }
+        assert_eq!(levels[..7], [1; 7]);
+        assert_eq!(levels[7..22], [8; 15]);
+        assert_eq!(levels[22..], [1; 2]);
+
+        // Within a day, and no night at all when it starts as it ends
+        let early = Settings {
+            night_start: 1,
+            night_end: 5,
+            ..settings
+        };
+        assert!(!early.is_night(0) && early.is_night(1) && early.is_night(4));
+        assert!(!early.is_night(5) && !early.is_night(23));
+        let never = Settings {
+            night_start: 6,
+            night_end: 6,
+            ..settings
+        };
+        assert!((0..24).all(|hour| !never.is_night(hour)));
+    }
+}
@@ -0,0 +1,371 @@
+use heapless::{consts::*, String};
+use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+pub const SECONDS_PER_DAY: u32 = 86400;
+// The RTC counter is treated as a Unix timestamp, so day 0 is 1970-01-01
+const EPOCH_YEAR: u16 = 1970;
+// Last full year the 32 bit counter reaches, it wraps in February 2106
+const MAX_YEAR: u16 = 2105;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Time {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl Time {
+    /// `None` unless it is a time of day
+    pub fn new(hours: u8, minutes: u8, seconds: u8) -> Option<Self> {
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return None;
+        }
+        Some(Self {
+            hours,
+            minutes,
+            seconds,
+        })
+    }
+}
+
+impl uDisplay for Time {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let _ = f.write_str(&zero_pad(self.hours));
+        let _ = f.write_str(":");
+        let _ = f.write_str(&zero_pad(self.minutes));
+        let _ = f.write_str(":");
+        f.write_str(&zero_pad(self.seconds))
+    }
+}
+
+fn zero_pad(num: u8) -> String<U2> {
+    let mut num_str = String::new();
+    if num < 10 {
+        num_str.clear();
+        uwrite!(num_str, "0{}", num).unwrap();
+    } else {
+        uwrite!(num_str, "{}", num).unwrap();
+    }
+
+    num_str
+}
+
+impl core::convert::From<u32> for Time {
+    fn from(val: u32) -> Self {
+        let val = val % SECONDS_PER_DAY;
+        let hours = (val / 3600) as u8;
+        let minutes = ((val % 3600) / 60) as u8;
+        let seconds = ((val % 3600) % 60) as u8;
+
+        Self {
+            hours,
+            minutes,
+            seconds,
+        }
+    }
+}
+
+impl core::convert::From<&Time> for u32 {
+    fn from(val: &Time) -> Self {
+        val.hours as u32 * 3600 + val.minutes as u32 * 60 + val.seconds as u32
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Day of the week for the given number of days since 1970-01-01 (a Thursday)
+    pub fn from_days(days: u32) -> Self {
+        match (days + 3) % 7 {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+
+    /// 0 for Monday up to 6 for Sunday
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Weekday::Monday => "Mon",
+            Weekday::Tuesday => "Tue",
+            Weekday::Wednesday => "Wed",
+            Weekday::Thursday => "Thu",
+            Weekday::Friday => "Fri",
+            Weekday::Saturday => "Sat",
+            Weekday::Sunday => "Sun",
+        }
+    }
+}
+
+impl uDisplay for Weekday {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        f.write_str(self.as_str())
+    }
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn days_in_year(year: u16) -> u32 {
+    if is_leap_year(year) {
+        366
+    } else {
+        365
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub weekday: Weekday,
+    pub time: Time,
+}
+
+impl DateTime {
+    /// Builds a date, the weekday is derived from the calendar date.
+    /// `None` for dates that do not exist or the RTC can not hold.
+    pub fn new(year: u16, month: u8, day: u8, time: Time) -> Option<Self> {
+        if !(EPOCH_YEAR..=MAX_YEAR).contains(&year)
+            || !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+            || Time::new(time.hours, time.minutes, time.seconds).is_none()
+        {
+            return None;
+        }
+        let mut date = Self {
+            year,
+            month,
+            day,
+            weekday: Weekday::Thursday,
+            time,
+        };
+        date.weekday = Weekday::from_days(date.days_since_epoch());
+        Some(date)
+    }
+
+    /// Date formatted as "Mon 2020-12-31"
+    pub fn date_str(&self) -> String<U16> {
+        let mut text = String::new();
+        let _ = uwrite!(text, "{} {}-", self.weekday, self.year);
+        let _ = text.push_str(&zero_pad(self.month));
+        let _ = text.push('-');
+        let _ = text.push_str(&zero_pad(self.day));
+        text
+    }
+
+    fn days_since_epoch(&self) -> u32 {
+        let mut days = 0;
+        for year in EPOCH_YEAR..self.year {
+            days += days_in_year(year);
+        }
+        for month in 1..self.month {
+            days += days_in_month(self.year, month) as u32;
+        }
+        days + self.day as u32 - 1
+    }
+}
+
+impl core::convert::From<u32> for DateTime {
+    fn from(val: u32) -> Self {
+        let mut days = val / SECONDS_PER_DAY;
+        let weekday = Weekday::from_days(days);
+
+        let mut year = EPOCH_YEAR;
+        while days >= days_in_year(year) {
+            days -= days_in_year(year);
+            year += 1;
+        }
+        let mut month = 1;
+        while days >= days_in_month(year, month) as u32 {
+            days -= days_in_month(year, month) as u32;
+            month += 1;
+        }
+
+        Self {
+            year,
+            month,
+            day: days as u8 + 1,
+            weekday,
+            time: val.into(),
+        }
+    }
+}
+
+impl core::convert::From<&DateTime> for u32 {
+    fn from(val: &DateTime) -> Self {
+        val.days_since_epoch() * SECONDS_PER_DAY + u32::from(&val.time)
+    }
+}
+
+impl uDisplay for DateTime {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        uwrite!(f, "{} {}", self.date_str().as_str(), self.time)
+    }
+}
+
+/// Wall clock the screens read and set, the RTC on the device
+pub trait Clock {
+    /// Seconds since 1970-01-01
+    fn timestamp(&self) -> u32;
+    fn set_timestamp(&mut self, timestamp: u32);
+
+    fn get_time(&self) -> Time {
+        self.timestamp().into()
+    }
+
+    /// Sets the time of day, keeping the current date
+    fn set_time(&mut self, time: &Time) {
+        let midnight = self.timestamp() / SECONDS_PER_DAY * SECONDS_PER_DAY;
+        self.set_timestamp(midnight + u32::from(time))
+    }
+
+    fn get_datetime(&self) -> DateTime {
+        self.timestamp().into()
+    }
+
+    fn set_datetime(&mut self, datetime: &DateTime) {
+        self.set_timestamp(datetime.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIDNIGHT: Time = Time {
+        hours: 0,
+        minutes: 0,
+        seconds: 0,
+    };
+
+    fn date(year: u16, month: u8, day: u8) -> DateTime {
+        DateTime::new(year, month, day, MIDNIGHT).unwrap()
+    }
+
+    #[test]
+    fn leap_years() {
+        assert!(!is_leap_year(1970));
+        assert!(is_leap_year(1972));
+        assert!(is_leap_year(2000));
+        assert!(!is_leap_year(2023));
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(2100));
+    }
+
+    #[test]
+    fn month_lengths() {
+        let lengths = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+        for (month, length) in (1..=12).zip(lengths.iter()) {
+            assert_eq!(days_in_month(2023, month), *length);
+        }
+        assert_eq!(days_in_month(2000, 2), 29);
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+    }
+
+    #[test]
+    fn timestamps_of_known_dates() {
+        // Timestamps and weekdays as given by `date -u`
+        let dates = [
+            (date(1970, 1, 1), 0, Weekday::Thursday),
+            (date(2000, 2, 29), 951_782_400, Weekday::Tuesday),
+            (date(2000, 12, 31), 978_220_800, Weekday::Sunday),
+            (date(2024, 2, 29), 1_709_164_800, Weekday::Thursday),
+            (date(2100, 2, 28), 4_107_456_000, Weekday::Sunday),
+            (date(2100, 3, 1), 4_107_542_400, Weekday::Monday),
+            (date(2105, 12, 31), 4_291_660_800, Weekday::Thursday),
+        ];
+        for (date, timestamp, weekday) in dates.iter() {
+            assert_eq!(u32::from(date), *timestamp);
+            assert_eq!(DateTime::from(*timestamp), *date);
+            assert_eq!(date.weekday, *weekday);
+        }
+    }
+
+    #[test]
+    fn calendar_round_trips() {
+        let mut previous = date(1970, 1, 1);
+        // Every day up to the end of 2105, each at a different time of day
+        for days in 1..=u32::from(&date(2105, 12, 31)) / SECONDS_PER_DAY {
+            let timestamp = days * SECONDS_PER_DAY + days % SECONDS_PER_DAY;
+            let datetime = DateTime::from(timestamp);
+            assert_eq!(u32::from(&datetime), timestamp);
+            let rebuilt = DateTime::new(datetime.year, datetime.month, datetime.day, datetime.time);
+            assert_eq!(rebuilt, Some(datetime));
+
+            // One day after the previous one, across month and year ends
+            if datetime.month == previous.month {
+                assert_eq!(datetime.day, previous.day + 1);
+            } else {
+                assert_eq!(previous.day, days_in_month(previous.year, previous.month));
+                assert_eq!(datetime.day, 1);
+                assert_eq!(datetime.month, previous.month % 12 + 1);
+                if datetime.month == 1 {
+                    assert_eq!(datetime.year, previous.year + 1);
+                }
+            }
+            assert_eq!(datetime.weekday.index(), (previous.weekday.index() + 1) % 7);
+            previous = datetime;
+        }
+    }
+
+    #[test]
+    fn time_of_day() {
+        let time = Time::from(978_307_199);
+        assert_eq!(Time::new(23, 59, 59), Some(time));
+        assert_eq!(u32::from(&time), SECONDS_PER_DAY - 1);
+        assert_eq!(Time::new(24, 0, 0), None);
+        assert_eq!(Time::new(0, 60, 0), None);
+        assert_eq!(Time::new(0, 0, 60), None);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        assert_eq!(DateTime::new(2023, 0, 1, MIDNIGHT), None);
+        assert_eq!(DateTime::new(2023, 13, 1, MIDNIGHT), None);
+        assert_eq!(DateTime::new(2023, 1, 0, MIDNIGHT), None);
+        assert_eq!(DateTime::new(2023, 4, 31, MIDNIGHT), None);
+        assert_eq!(DateTime::new(2023, 2, 29, MIDNIGHT), None);
+        assert_eq!(DateTime::new(2100, 2, 29, MIDNIGHT), None);
+        assert!(DateTime::new(2000, 2, 29, MIDNIGHT).is_some());
+        // Outside of what the RTC counter holds
+        assert_eq!(DateTime::new(1969, 12, 31, MIDNIGHT), None);
+        assert_eq!(DateTime::new(2106, 1, 1, MIDNIGHT), None);
+        let time = Time {
+            hours: 24,
+            minutes: 0,
+            seconds: 0,
+        };
+        assert_eq!(DateTime::new(2023, 1, 1, time), None);
+    }
+}
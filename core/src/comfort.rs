@@ -0,0 +1,198 @@
+// Metrics derived from temperature and relative humidity
+
+// Magnus formula coefficients (Sonntag 1990) for the saturation vapour pressure
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+const KELVIN: f32 = 273.15;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ComfortLevel {
+    Cold,
+    Hot,
+    Dry,
+    Humid,
+    Comfortable,
+}
+
+impl ComfortLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ComfortLevel::Cold => "Too cold",
+            ComfortLevel::Hot => "Too hot",
+            ComfortLevel::Dry => "Too dry",
+            ComfortLevel::Humid => "Too humid",
+            ComfortLevel::Comfortable => "Comfortable",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Comfort {
+    /// Degree Celsius
+    pub dew_point: f32,
+    /// Apparent temperature in degree Celsius (NOAA)
+    pub heat_index: f32,
+    /// Canadian humidex, dimensionless but read as degree Celsius
+    pub humidex: f32,
+    /// Grams of water vapour per cubic metre
+    pub absolute_humidity: f32,
+    pub level: ComfortLevel,
+}
+
+impl Comfort {
+    /// Temperature in degree Celsius and relative humidity in percent
+    pub fn new(temperature: f32, humidity: f32) -> Self {
+        let humidity = humidity.clamp(1.0, 100.0);
+        let dew_point = dew_point(temperature, humidity);
+
+        Self {
+            dew_point,
+            heat_index: heat_index(temperature, humidity),
+            humidex: humidex(temperature, dew_point),
+            absolute_humidity: absolute_humidity(temperature, humidity),
+            level: level(temperature, humidity, dew_point),
+        }
+    }
+}
+
+/// Saturation vapour pressure over water in hPa
+fn saturation_pressure(temperature: f32) -> f32 {
+    6.112 * libm::expf(MAGNUS_A * temperature / (MAGNUS_B + temperature))
+}
+
+pub fn dew_point(temperature: f32, humidity: f32) -> f32 {
+    let gamma = libm::logf(humidity / 100.0) + MAGNUS_A * temperature / (MAGNUS_B + temperature);
+    MAGNUS_B * gamma / (MAGNUS_A - gamma)
+}
+
+pub fn absolute_humidity(temperature: f32, humidity: f32) -> f32 {
+    // Vapour pressure in Pa over the specific gas constant of water vapour
+    let vapour_pressure = saturation_pressure(temperature) * humidity;
+    vapour_pressure / (461.5 * (KELVIN + temperature)) * 1000.0
+}
+
+pub fn humidex(temperature: f32, dew_point: f32) -> f32 {
+    let vapour_pressure = 6.11 * libm::expf(5417.753 * (1.0 / 273.16 - 1.0 / (KELVIN + dew_point)));
+    temperature + 0.5555 * (vapour_pressure - 10.0)
+}
+
+/// NOAA heat index, Steadman's approximation below 80 F and the Rothfusz
+/// regression with its adjustments above
+pub fn heat_index(temperature: f32, humidity: f32) -> f32 {
+    let t = temperature * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let fahrenheit = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut hi = -42.379 + 2.049_015 * t + 10.143_33 * rh
+            - 0.224_755 * t * rh
+            - 0.006_837_83 * t * t
+            - 0.054_817_17 * rh * rh
+            + 0.001_228_74 * t * t * rh
+            + 0.000_852_82 * t * rh * rh
+            - 0.000_001_99 * t * t * rh * rh;
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            let delta = libm::fabsf(t - 95.0);
+            hi -= (13.0 - rh) / 4.0 * libm::sqrtf((17.0 - delta) / 17.0);
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            hi += (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0);
+        }
+        hi
+    };
+
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+fn level(temperature: f32, humidity: f32, dew_point: f32) -> ComfortLevel {
+    if temperature < 18.0 {
+        ComfortLevel::Cold
+    } else if temperature > 26.0 {
+        ComfortLevel::Hot
+    } else if humidity < 30.0 {
+        ComfortLevel::Dry
+    } else if humidity > 60.0 || dew_point > 16.0 {
+        ComfortLevel::Humid
+    } else {
+        ComfortLevel::Comfortable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(value: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} is not {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn dew_point_reference_values() {
+        assert_near(dew_point(20.0, 50.0), 9.3, 0.1);
+        // Saturated air is at its dew point
+        assert_near(dew_point(20.0, 100.0), 20.0, 0.01);
+        assert_near(dew_point(-10.0, 100.0), -10.0, 0.01);
+    }
+
+    #[test]
+    fn absolute_humidity_reference_values() {
+        // Water vapour density of saturated air
+        assert_near(absolute_humidity(20.0, 100.0), 17.3, 0.1);
+        assert_near(absolute_humidity(0.0, 100.0), 4.85, 0.05);
+    }
+
+    #[test]
+    fn humidex_reference_values() {
+        // Environment Canada table: 30 degrees at a dew point of 15
+        assert_near(humidex(30.0, 15.0), 34.0, 0.5);
+    }
+
+    #[test]
+    fn heat_index_below_the_regression() {
+        // Steadman's formula stays within a degree of the air temperature
+        assert_near(heat_index(20.0, 0.0), 18.1, 0.1);
+        assert_near(heat_index(20.0, 50.0), 19.4, 0.1);
+        assert_near(heat_index(20.0, 100.0), 20.7, 0.1);
+        assert_near(heat_index(26.0, 40.0), 25.7, 0.1);
+    }
+
+    #[test]
+    fn heat_index_above_the_regression() {
+        // NOAA heat index chart: 90 F at 70 % feels like 106 F
+        assert_near(heat_index(32.2, 70.0), 41.1, 0.5);
+        // The dry and the humid adjustments
+        assert_near(heat_index(35.0, 10.0), 31.9, 0.1);
+        assert_near(heat_index(28.0, 90.0), 34.0, 0.1);
+    }
+
+    #[test]
+    fn humidity_limits() {
+        // Bone dry air is taken as 1 % instead of a dew point of minus infinity
+        let dry = Comfort::new(20.0, 0.0);
+        assert_near(dry.dew_point, -38.0, 0.1);
+        assert_near(dry.absolute_humidity, 0.17, 0.01);
+        assert_eq!(dry.level, ComfortLevel::Dry);
+
+        let saturated = Comfort::new(20.0, 100.0);
+        assert_near(saturated.dew_point, 20.0, 0.01);
+        assert_near(saturated.humidex, 27.6, 0.1);
+        assert_eq!(saturated.level, ComfortLevel::Humid);
+        // Sensors read slightly above 100 % in condensing air
+        assert_eq!(Comfort::new(20.0, 102.0), saturated);
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!(Comfort::new(17.0, 45.0).level, ComfortLevel::Cold);
+        assert_eq!(Comfort::new(27.0, 45.0).level, ComfortLevel::Hot);
+        assert_eq!(Comfort::new(22.0, 45.0).level, ComfortLevel::Comfortable);
+        assert_eq!(Comfort::new(22.0, 20.0).level, ComfortLevel::Dry);
+        assert_eq!(Comfort::new(22.0, 70.0).level, ComfortLevel::Humid);
+    }
+}
@@ -0,0 +1,20 @@
+use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+/// Formats a float with a single decimal place, ufmt has no float support
+#[derive(Copy, Clone)]
+pub struct Decimal(pub f32);
+
+impl uDisplay for Decimal {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let tenths = if self.0 < 0.0 {
+            (self.0 * 10.0 - 0.5) as i32
+        } else {
+            (self.0 * 10.0 + 0.5) as i32
+        };
+        if tenths < 0 {
+            f.write_str("-")?;
+        }
+        let tenths = tenths.abs();
+        uwrite!(f, "{}.{}", tenths / 10, tenths % 10)
+    }
+}
@@ -0,0 +1,811 @@
+use crate::alarm::AlarmState;
+use crate::input::{Button, Event, Gesture};
+use crate::menu::{Context, Response, Screen};
+use crate::rotation::Rotation;
+use crate::theme::Theme;
+use core::fmt::Debug;
+use embedded_graphics::{
+    fonts::{Font12x16, Font8x16, Text},
+    pixelcolor::Rgb565,
+    prelude::*,
+    primitives::{Line, Rectangle},
+    style::{MonoTextStyleBuilder, PrimitiveStyle},
+};
+use embedded_hal::blocking::delay::DelayMs;
+use heapless::{consts::*, String, Vec};
+
+pub type ScreenId = usize;
+
+struct Entry<D: 'static> {
+    screen: &'static mut dyn Screen<D>,
+    parent: Option<ScreenId>,
+}
+
+/// Navigates between the registered screens. The arrows move between the
+/// screens sharing a parent, the stack keeps track of the opened submenus.
+pub struct Gui<D: 'static> {
+    display: Display<D>,
+    screens: Vec<Entry<D>, U16>,
+    stack: Vec<ScreenId, U4>,
+}
+
+impl<D> Gui<D>
+where
+    D: DrawTarget<Color = Rgb565> + 'static,
+    D::Error: Debug,
+{
+    pub fn new(display: Display<D>) -> Self {
+        Self {
+            display,
+            screens: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Adds a top level screen, the first one is shown at start
+    pub fn register(&mut self, screen: &'static mut dyn Screen<D>) -> ScreenId {
+        self.add(screen, None)
+    }
+
+    /// Adds a screen to the submenu of `parent`
+    pub fn register_child(
+        &mut self,
+        parent: ScreenId,
+        screen: &'static mut dyn Screen<D>,
+    ) -> ScreenId {
+        self.add(screen, Some(parent))
+    }
+
+    fn add(&mut self, screen: &'static mut dyn Screen<D>, parent: Option<ScreenId>) -> ScreenId {
+        let id = self.screens.len();
+        if self.screens.push(Entry { screen, parent }).is_err() {
+            panic!("Too many screens");
+        }
+        if self.stack.is_empty() {
+            let _ = self.stack.push(id);
+        }
+        id
+    }
+
+    pub fn release(self) -> D {
+        self.display.release()
+    }
+
+    fn current(&self) -> Option<ScreenId> {
+        self.stack.last().copied()
+    }
+
+    fn children(&self, parent: Option<ScreenId>) -> impl Iterator<Item = ScreenId> + '_ {
+        (0..self.screens.len()).filter(move |id| self.screens[*id].parent == parent)
+    }
+
+    /// Moves to the next or previous screen sharing the parent of the current
+    /// one. Top level screens wrap around, submenus are left past either end.
+    fn step(&mut self, forward: bool) {
+        let current = match self.current() {
+            Some(current) => current,
+            None => return,
+        };
+        let parent = self.screens[current].parent;
+        let mut siblings: Vec<ScreenId, U16> = Vec::new();
+        for id in self.children(parent) {
+            let _ = siblings.push(id);
+        }
+        let pos = siblings.iter().position(|id| *id == current).unwrap_or(0);
+
+        let next = match (forward, parent) {
+            (true, _) if pos + 1 < siblings.len() => Some(siblings[pos + 1]),
+            (false, _) if pos > 0 => Some(siblings[pos - 1]),
+            (true, None) => Some(siblings[0]),
+            (false, None) => Some(siblings[siblings.len() - 1]),
+            (_, Some(_)) => None,
+        };
+        match next {
+            Some(next) => {
+                self.stack.pop();
+                let _ = self.stack.push(next);
+            }
+            None => self.back(),
+        }
+    }
+
+    fn open(&mut self, child: usize) {
+        if let Some(current) = self.current() {
+            let child = self.children(Some(current)).nth(child);
+            if let Some(id) = child {
+                // The stack is only as deep as the menus are nested
+                let _ = self.stack.push(id);
+            }
+        }
+    }
+
+    fn back(&mut self) {
+        if self.stack.len() > 1 {
+            self.stack.pop();
+        }
+    }
+
+    /// Passes the event to the current screen, navigates if it is not used
+    pub fn handle(&mut self, event: Event, ctx: &mut Context) {
+        let current = match self.current() {
+            Some(current) => current,
+            None => return,
+        };
+        match self.screens[current].screen.handle(event, ctx) {
+            Response::Handled => {}
+            Response::Open(child) => self.open(child),
+            Response::Ignored => match (event.button, event.gesture) {
+                (Button::Left, Gesture::Press) | (Button::Left, Gesture::Repeat) => {
+                    self.step(false)
+                }
+                (Button::Right, Gesture::Press) | (Button::Right, Gesture::Repeat) => {
+                    self.step(true)
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// Starts drawing the current view, only changed widgets reach the screen
+    pub fn begin_frame(&mut self) {
+        self.display.begin_frame();
+    }
+
+    /// Blanks whatever the current view did not draw
+    pub fn end_frame(&mut self) {
+        self.display.end_frame();
+    }
+
+    /// Draws everything again in the theme if it is not the current one
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.display.set_theme(theme);
+    }
+
+    pub fn render(&mut self, ctx: &mut Context) {
+        let current = match self.current() {
+            Some(current) => current,
+            None => return,
+        };
+        let screen = &mut self.screens[current].screen;
+        let theme = self.display.theme;
+        let (title, color) = match ctx.alarms.state() {
+            AlarmState::Ringing(_) => ("Alarm!", theme.warning),
+            _ if ctx.health.is_offline() => ("Sensor offline", theme.error),
+            _ => (screen.title(), theme.foreground),
+        };
+        self.display.render_tab_header(title, color);
+        screen.render(&mut self.display, ctx);
+    }
+}
+
+impl<D> Gui<D>
+where
+    D: DrawTarget<Color = Rgb565> + Sleep + 'static,
+    D::Error: Debug,
+{
+    pub fn sleep<T: DelayMs<u8>>(&mut self, delay: &mut T) {
+        self.display.display.sleep(delay);
+    }
+
+    /// Switches the panel back on and draws everything again
+    pub fn wake<T: DelayMs<u8>>(&mut self, delay: &mut T) {
+        self.display.display.wake(delay);
+        self.display.invalidate();
+    }
+}
+
+impl<D> Gui<D>
+where
+    D: DrawTarget<Color = Rgb565> + Rotate + 'static,
+    D::Error: Debug,
+{
+    /// Turns the screen, everything is laid out and drawn again
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        if rotation != self.display.display.rotation() {
+            self.display.display.set_rotation(rotation);
+            self.display.invalidate();
+        }
+    }
+}
+
+/// A panel that can be switched off to save power
+pub trait Sleep {
+    fn sleep<T: DelayMs<u8>>(&mut self, delay: &mut T);
+    /// Switches the panel on again, what it showed may be lost
+    fn wake<T: DelayMs<u8>>(&mut self, delay: &mut T);
+}
+
+/// A panel whose content can be turned, its size follows the rotation
+pub trait Rotate {
+    fn rotation(&self) -> Rotation;
+    fn set_rotation(&mut self, rotation: Rotation);
+}
+
+/// A line of text across the screen, enough for the small font on a
+/// 256 pixel wide one
+pub type TextLine = String<U32>;
+
+/// The first `chars` characters of the text
+fn first_chars(text: &str, chars: usize) -> &str {
+    match text.char_indices().nth(chars) {
+        Some((idx, _)) => &text[..idx],
+        None => text,
+    }
+}
+
+/// Pads the text with spaces to `width` characters, cut to fit the line
+pub fn pad_to(text: &str, width: usize) -> TextLine {
+    let mut line = TextLine::new();
+    for c in text.chars().chain(core::iter::repeat(' ')).take(width) {
+        if line.push(c).is_err() {
+            break;
+        }
+    }
+    line
+}
+
+/// Splits the text into two lines at the last space fitting into `width`
+/// characters
+pub fn wrap(text: &str, width: usize) -> (&str, &str) {
+    let line = first_chars(text, width);
+    if line.len() == text.len() {
+        return (text, "");
+    }
+    // A space right after the line breaks it as well
+    let space = if text[line.len()..].starts_with(' ') {
+        Some(line.len())
+    } else {
+        line.rfind(' ')
+    };
+    match space {
+        Some(idx) => (&text[..idx], &text[idx + 1..]),
+        None => text.split_at(line.len()),
+    }
+}
+
+/// Font heights in pixels, both fonts are 16 pixels high
+const FONT_HEIGHT: u32 = 16;
+const FONT_SM_WIDTH: u32 = 8;
+const FONT_LG_WIDTH: u32 = 12;
+/// Height of the frame around the title, the view is drawn below it
+pub const HEADER_HEIGHT: u32 = 20;
+// Room between the frame and the title on either side
+const HEADER_INSET: u32 = 8;
+// Top of the first line of text below the header
+const LINES_TOP: u32 = HEADER_HEIGHT + 4;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FontSize {
+    Small,
+    Large,
+}
+
+impl FontSize {
+    pub fn width(self) -> u32 {
+        match self {
+            FontSize::Small => FONT_SM_WIDTH,
+            FontSize::Large => FONT_LG_WIDTH,
+        }
+    }
+
+    pub fn height(self) -> u32 {
+        FONT_HEIGHT
+    }
+}
+
+/// Something drawn on the screen in an earlier frame. `tag` identifies the
+/// content, a widget drawn again with the same tag is left alone.
+#[derive(Copy, Clone)]
+struct Widget {
+    area: Rectangle,
+    tag: u32,
+    used: bool,
+}
+
+/// Tag of a widget that only changes with its colour
+pub(crate) fn color_tag(color: Rgb565) -> u32 {
+    ((color.r() as u32) << 11) | ((color.g() as u32) << 5) | color.b() as u32
+}
+
+// FNV-1a, only has to tell different texts at the same spot apart
+fn text_tag(text: &str, char_width: u32, color: Rgb565) -> u32 {
+    let seed = 0x811c_9dc5 ^ char_width ^ (color_tag(color) << 8);
+    text.bytes().fold(seed, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn intersection(a: &Rectangle, b: &Rectangle) -> Option<Rectangle> {
+    let left = a.top_left.x.max(b.top_left.x);
+    let top = a.top_left.y.max(b.top_left.y);
+    let right = (a.top_left.x + a.size.width as i32).min(b.top_left.x + b.size.width as i32);
+    let bottom = (a.top_left.y + a.size.height as i32).min(b.top_left.y + b.size.height as i32);
+    if left >= right || top >= bottom {
+        return None;
+    }
+    Some(Rectangle::new(
+        Point::new(left, top),
+        Size::new((right - left) as u32, (bottom - top) as u32),
+    ))
+}
+
+/// The parts of `old` not covered by `new`, as up to four strips
+fn outside(old: &Rectangle, new: &Rectangle) -> [Option<Rectangle>; 4] {
+    let inner = match intersection(old, new) {
+        Some(inner) => inner,
+        None => return [Some(*old), None, None, None],
+    };
+    let strip = |x: i32, y: i32, width: i32, height: i32| {
+        if width > 0 && height > 0 {
+            Some(Rectangle::new(
+                Point::new(x, y),
+                Size::new(width as u32, height as u32),
+            ))
+        } else {
+            None
+        }
+    };
+    let (left, top) = (old.top_left.x, old.top_left.y);
+    let (right, bottom) = (left + old.size.width as i32, top + old.size.height as i32);
+    let (inner_right, inner_bottom) = (
+        inner.top_left.x + inner.size.width as i32,
+        inner.top_left.y + inner.size.height as i32,
+    );
+    [
+        strip(left, top, right - left, inner.top_left.y - top),
+        strip(left, inner_bottom, right - left, bottom - inner_bottom),
+        strip(
+            left,
+            inner.top_left.y,
+            inner.top_left.x - left,
+            inner.size.height as i32,
+        ),
+        strip(
+            inner_right,
+            inner.top_left.y,
+            right - inner_right,
+            inner.size.height as i32,
+        ),
+    ]
+}
+
+/// Drawing primitives of the GUI on top of any embedded-graphics target,
+/// the ST7735 on the device or a `FrameBuffer` on the host.
+///
+/// Drawing is retained: every frame the GUI draws the whole view again, but
+/// only widgets whose content changed reach the display and whatever was
+/// drawn in the previous frame and not this one is blanked at the end.
+pub struct Display<D> {
+    display: D,
+    widgets: Vec<Widget, U32>,
+    theme: Theme,
+}
+
+impl<D> Display<D>
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    pub fn new(display: D) -> Self {
+        Self {
+            display,
+            widgets: Vec::new(),
+            theme: Theme::DAY,
+        }
+    }
+
+    pub fn release(self) -> D {
+        self.display
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Switches to the theme, everything is drawn again in its colours
+    pub fn set_theme(&mut self, theme: Theme) {
+        if theme != self.theme {
+            self.theme = theme;
+            self.invalidate();
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        for widget in self.widgets.iter_mut() {
+            widget.used = false;
+        }
+    }
+
+    /// Blanks the widgets that were not drawn in this frame
+    pub fn end_frame(&mut self) {
+        let mut idx = 0;
+        while idx < self.widgets.len() {
+            if self.widgets[idx].used {
+                idx += 1;
+            } else {
+                let widget = self.widgets.swap_remove(idx);
+                self.fill_background(&widget.area);
+            }
+        }
+    }
+
+    /// Forgets everything drawn so far and clears the whole screen
+    pub fn invalidate(&mut self) {
+        self.widgets = Vec::new();
+        self.clear();
+    }
+
+    /// Registers `area` as drawn this frame, returns whether it has to be
+    /// drawn because its content changed since the last frame. Leftovers of
+    /// older widgets overlapping the area are blanked.
+    pub fn claim(&mut self, area: Rectangle, tag: u32) -> bool {
+        let mut changed = true;
+        let mut idx = 0;
+        while idx < self.widgets.len() {
+            let widget = self.widgets[idx];
+            if widget.area == area {
+                changed = widget.tag != tag;
+                self.widgets.swap_remove(idx);
+            } else if !widget.used && intersection(&widget.area, &area).is_some() {
+                for strip in outside(&widget.area, &area).iter().flatten() {
+                    self.fill_background(strip);
+                }
+                self.widgets.swap_remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+
+        let widget = Widget {
+            area,
+            tag,
+            used: true,
+        };
+        if self.widgets.push(widget).is_err() {
+            // Too much on the screen to track, start over from a blank one
+            self.invalidate();
+            let _ = self.widgets.push(widget);
+            return true;
+        }
+        changed
+    }
+
+    fn claim_text(&mut self, text: &str, char_width: u32, color: Rgb565, x: i32, y: i32) -> bool {
+        let area = Rectangle::new(
+            Point::new(x, y),
+            Size::new(text.len() as u32 * char_width, FONT_HEIGHT),
+        );
+        self.claim(area, text_tag(text, char_width, color))
+    }
+
+    pub fn print_text(&mut self, text: &str, size: FontSize, x: i32, y: i32) {
+        self.print_text_colored(text, size, self.theme.foreground, x, y);
+    }
+
+    pub fn print_text_sm(&mut self, text: &str, x: i32, y: i32) {
+        self.print_text(text, FontSize::Small, x, y);
+    }
+
+    pub fn print_text_lg(&mut self, text: &str, x: i32, y: i32) {
+        self.print_text(text, FontSize::Large, x, y);
+    }
+
+    /// Text in a colour other than the foreground of the theme
+    pub fn print_text_colored(
+        &mut self,
+        text: &str,
+        size: FontSize,
+        color: Rgb565,
+        x: i32,
+        y: i32,
+    ) {
+        if !self.claim_text(text, size.width(), color, x, y) {
+            return;
+        }
+        let (position, background) = (Point::new(x, y), self.theme.background);
+        // Each font is a type of its own, so is the style built on it
+        match size {
+            FontSize::Small => {
+                let style = MonoTextStyleBuilder::new(Font8x16)
+                    .text_color(color)
+                    .background_color(background)
+                    .build();
+                Text::new(text, position)
+                    .into_styled(style)
+                    .draw(&mut self.display)
+                    .unwrap();
+            }
+            FontSize::Large => {
+                let style = MonoTextStyleBuilder::new(Font12x16)
+                    .text_color(color)
+                    .background_color(background)
+                    .build();
+                Text::new(text, position)
+                    .into_styled(style)
+                    .draw(&mut self.display)
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Frames the header and centers the title in `color`
+    pub fn render_tab_header(&mut self, text: &str, color: Rgb565) {
+        let frame = Rectangle::new(Point::zero(), Size::new(self.size().width, HEADER_HEIGHT));
+        if self.claim(frame, 0) {
+            let thick_stroke = PrimitiveStyle::with_stroke(self.theme.accent, 3);
+
+            frame
+                .into_styled(thick_stroke)
+                .draw(&mut self.display)
+                .unwrap();
+        }
+
+        // Centered by padding so every title covers the same area
+        let chars = (frame.size.width.saturating_sub(2 * HEADER_INSET) / FONT_SM_WIDTH) as usize;
+        let text = first_chars(text, chars);
+        let mut title: String<U32> = String::new();
+        for _ in 0..(chars - text.chars().count()) / 2 {
+            let _ = title.push(' ');
+        }
+        let _ = title.push_str(text);
+        title = pad_to(&title, chars);
+        let x = HEADER_INSET as i32;
+        self.print_text_colored(&title, FontSize::Small, color, x, 3);
+    }
+
+    /// Characters of the font fitting across the screen
+    pub fn line_chars(&self, font: FontSize) -> usize {
+        (self.size().width / font.width()) as usize
+    }
+
+    /// Pads the text with spaces to a full line of the small font so it
+    /// overwrites older text
+    pub fn pad(&self, text: &str) -> TextLine {
+        pad_to(text, self.line_chars(FontSize::Small))
+    }
+
+    /// Like `pad` for the large font
+    pub fn pad_lg(&self, text: &str) -> TextLine {
+        pad_to(text, self.line_chars(FontSize::Large))
+    }
+
+    /// Splits the text into two lines of the small font
+    pub fn wrap<'a>(&self, text: &'a str) -> (&'a str, &'a str) {
+        wrap(text, self.line_chars(FontSize::Small))
+    }
+
+    /// Lines of text fitting below the header
+    pub fn line_count(&self) -> usize {
+        (self.size().height.saturating_sub(LINES_TOP) / FONT_HEIGHT) as usize
+    }
+
+    /// Top of line `idx` with `count` lines spread over the space below the
+    /// header, on a screen too small for them they run off the bottom
+    pub fn line_y(&self, idx: usize, count: usize) -> i32 {
+        let spacing = self.size().height.saturating_sub(LINES_TOP) / count.max(1) as u32;
+        (LINES_TOP + idx as u32 * spacing.max(FONT_HEIGHT)) as i32
+    }
+
+    /// Top of the `idx`-th line when as many lines as fit are spread out
+    pub fn row_y(&self, idx: usize) -> i32 {
+        self.line_y(idx, self.line_count())
+    }
+
+    /// Left edge of `chars` characters of the font centered on the screen
+    pub fn centered_x(&self, chars: usize, font: FontSize) -> i32 {
+        (self
+            .size()
+            .width
+            .saturating_sub(chars as u32 * font.width())
+            / 2) as i32
+    }
+
+    pub fn print_pointer(&mut self, start: Point, end: Point) {
+        let area = Rectangle::new(
+            start,
+            Size::new((end.x - start.x) as u32 + 1, (end.y - start.y) as u32 + 1),
+        );
+        if self.claim(area, 0) {
+            self.print_line(start, end, self.theme.foreground);
+        }
+    }
+
+    /// Draws untracked, only for use within an area that was claimed
+    pub fn print_line(&mut self, start: Point, end: Point, color: Rgb565) {
+        self.print_stroke(start, end, color, 1);
+    }
+
+    /// Like `print_line` with a stroke `width` pixels wide
+    pub fn print_stroke(&mut self, start: Point, end: Point, color: Rgb565, width: u32) {
+        Line::new(start, end)
+            .into_styled(PrimitiveStyle::with_stroke(color, width))
+            .draw(&mut self.display)
+            .unwrap();
+    }
+
+    pub fn fill_background(&mut self, area: &Rectangle) {
+        self.fill(area, self.theme.background);
+    }
+
+    /// Fills untracked, only for use within an area that was claimed
+    pub fn fill(&mut self, area: &Rectangle, color: Rgb565) {
+        // A single windowed write on the ST7735 instead of pixel by pixel
+        self.display.fill_solid(area, color).unwrap();
+    }
+
+    pub fn size(&self) -> Size {
+        self.display.bounding_box().size
+    }
+
+    pub fn clear(&mut self) {
+        self.display.clear(self.theme.background).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::menu::Menu;
+    use crate::snapshots::State;
+    use pomia_framebuffer::FrameBuffer;
+
+    extern crate std;
+    use std::boxed::Box;
+
+    struct Blank;
+
+    impl Screen<FrameBuffer> for Blank {
+        fn title(&self) -> &'static str {
+            "Blank"
+        }
+
+        fn render(&mut self, _display: &mut Display<FrameBuffer>, _ctx: &mut Context) {}
+    }
+
+    fn press(button: Button) -> Event {
+        Event {
+            button,
+            gesture: Gesture::Press,
+        }
+    }
+
+    fn enter(gesture: Gesture) -> Event {
+        Event {
+            button: Button::Enter,
+            gesture,
+        }
+    }
+
+    fn blank() -> &'static mut Blank {
+        Box::leak(Box::new(Blank))
+    }
+
+    /// Two screens around a menu with two children
+    fn gui() -> Gui<FrameBuffer> {
+        let mut gui = Gui::new(Display::new(FrameBuffer::new(128, 160)));
+        assert_eq!(gui.register(blank()), 0);
+        let menu = Box::leak(Box::new(Menu::new("Menu", &["First", "Second"])));
+        assert_eq!(gui.register(menu), 1);
+        assert_eq!(gui.register_child(1, blank()), 2);
+        assert_eq!(gui.register_child(1, blank()), 3);
+        assert_eq!(gui.register(blank()), 4);
+        gui
+    }
+
+    fn visit(gui: &mut Gui<FrameBuffer>, state: &mut State, events: &[Event]) -> Vec<usize, U8> {
+        let mut ctx = state.context();
+        let mut visited = Vec::new();
+        for event in events {
+            gui.handle(*event, &mut ctx);
+            let _ = visited.push(gui.current().unwrap());
+        }
+        visited
+    }
+
+    #[test]
+    fn top_level_screens_wrap_around() {
+        let (mut gui, mut state) = (gui(), State::new());
+        assert_eq!(gui.current(), Some(0));
+        let right = [press(Button::Right); 4];
+        assert_eq!(visit(&mut gui, &mut state, &right), [1, 4, 0, 1]);
+        let left = [press(Button::Left); 3];
+        assert_eq!(visit(&mut gui, &mut state, &left), [0, 4, 1]);
+        // The children are not part of the top level
+        assert_eq!(gui.stack.len(), 1);
+    }
+
+    #[test]
+    fn submenus_are_pushed_and_left_past_either_end() {
+        let (mut gui, mut state) = (gui(), State::new());
+        let events = [
+            press(Button::Right),
+            enter(Gesture::LongPress),
+            press(Button::Right),
+            press(Button::Right),
+        ];
+        assert_eq!(visit(&mut gui, &mut state, &events), [1, 2, 3, 1]);
+        assert_eq!(gui.stack.len(), 1);
+
+        let events = [enter(Gesture::LongPress), press(Button::Left)];
+        assert_eq!(visit(&mut gui, &mut state, &events), [2, 1]);
+        assert_eq!(gui.stack.len(), 1);
+    }
+
+    #[test]
+    fn menu_selection_wraps_around() {
+        let (mut gui, mut state) = (gui(), State::new());
+        let events = [
+            press(Button::Right),
+            enter(Gesture::Click),
+            enter(Gesture::LongPress),
+        ];
+        assert_eq!(visit(&mut gui, &mut state, &events), [1, 1, 3]);
+        gui.back();
+
+        // Past the last entry the first one is selected again
+        let events = [enter(Gesture::DoubleClick), enter(Gesture::LongPress)];
+        assert_eq!(visit(&mut gui, &mut state, &events), [1, 2]);
+        assert_eq!(gui.stack[..], [1, 2]);
+    }
+
+    #[test]
+    fn back_keeps_the_root() {
+        let (mut gui, mut state) = (gui(), State::new());
+        gui.back();
+        assert_eq!(gui.stack[..], [0]);
+
+        visit(
+            &mut gui,
+            &mut state,
+            &[press(Button::Right), enter(Gesture::LongPress)],
+        );
+        gui.back();
+        gui.back();
+        assert_eq!(gui.stack[..], [1]);
+
+        // Neither a screen without children nor a missing entry opens anything
+        gui.open(5);
+        gui.step(false);
+        gui.open(0);
+        assert_eq!(gui.stack[..], [0]);
+    }
+
+    #[test]
+    fn pads_and_cuts_whole_characters() {
+        assert_eq!(pad_to("T: 21.5", 10).as_str(), "T: 21.5   ");
+        assert_eq!(pad_to("Humidity", 3).as_str(), "Hum");
+        assert_eq!(pad_to("21.5\u{b0}C", 6).as_str(), "21.5\u{b0}C");
+        assert_eq!(pad_to("21.5\u{b0}C", 5).as_str(), "21.5\u{b0}");
+        assert_eq!(pad_to("\u{b0}\u{b0}", 4).as_str(), "\u{b0}\u{b0}  ");
+        // Stops where the line is full
+        assert_eq!(pad_to("", 40).len(), 32);
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        assert_eq!(wrap("Fine weather", 16), ("Fine weather", ""));
+        assert_eq!(
+            wrap("Rain at times, worse later", 16),
+            ("Rain at times,", "worse later")
+        );
+        assert_eq!(wrap("Showery early", 7), ("Showery", "early"));
+        assert_eq!(wrap("Unsettled", 5), ("Unset", "tled"));
+    }
+
+    #[test]
+    fn wraps_whole_characters() {
+        assert_eq!(
+            wrap("M\u{fc}nchen f\u{f6}hnig", 7),
+            ("M\u{fc}nchen", "f\u{f6}hnig")
+        );
+        assert_eq!(
+            wrap("\u{b0}\u{b0}\u{b0}\u{b0}", 3),
+            ("\u{b0}\u{b0}\u{b0}", "\u{b0}")
+        );
+        assert_eq!(
+            wrap("ab \u{b0}\u{b0}\u{b0}", 4),
+            ("ab", "\u{b0}\u{b0}\u{b0}")
+        );
+        assert_eq!(first_chars("\u{e9}t\u{e9}", 2), "\u{e9}t");
+    }
+}
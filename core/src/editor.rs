@@ -0,0 +1,381 @@
+// Multi-field editor for times, dates, alarms and settings. The arrows change
+// the selected field, enter moves to the next one, a long press confirms and
+// pressing both arrows together cancels.
+
+use crate::display::{Display, FontSize};
+use crate::input::{Button, Event, Gesture};
+use core::fmt::Debug;
+use core::ops::Range;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use heapless::{consts::*, String, Vec};
+use ufmt::uwrite;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Kind {
+    /// Zero padded to the number of digits
+    Number(u8),
+    /// The value is the index of the label
+    Choice(&'static [&'static str]),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Field {
+    value: i32,
+    min: i32,
+    max: i32,
+    step: i32,
+    wrap: bool,
+    kind: Kind,
+    /// Printed in front of the field, e.g. the `:` between hours and minutes
+    prefix: &'static str,
+}
+
+impl Field {
+    /// Number between `min` and `max` inclusive, wrapping around by default
+    pub fn number(value: i32, min: i32, max: i32, digits: u8) -> Self {
+        Self {
+            value: value.max(min).min(max),
+            min,
+            max,
+            step: 1,
+            wrap: true,
+            kind: Kind::Number(digits),
+            prefix: "",
+        }
+    }
+
+    /// One of the labels, `value` is the index of the selected one
+    pub fn choice(value: usize, labels: &'static [&'static str]) -> Self {
+        let max = labels.len().max(1) as i32 - 1;
+        Self {
+            value: (value as i32).min(max),
+            min: 0,
+            max,
+            step: 1,
+            wrap: true,
+            kind: Kind::Choice(labels),
+            prefix: "",
+        }
+    }
+
+    pub fn step(mut self, step: i32) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
+    /// Stops at `min` and `max` instead of wrapping around
+    pub fn clamped(mut self) -> Self {
+        self.wrap = false;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &'static str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Moves to the next multiple of the step above `min`, a value that is
+    /// off that grid snaps onto it
+    pub fn increment(&mut self) {
+        let next = self.min + ((self.value - self.min) / self.step + 1) * self.step;
+        self.value = if next <= self.max {
+            next
+        } else if self.wrap {
+            self.min
+        } else {
+            self.value.max(self.last())
+        };
+    }
+
+    pub fn decrement(&mut self) {
+        let above = self.value - self.min + self.step - 1;
+        let next = self.min + (above / self.step - 1) * self.step;
+        self.value = if next >= self.min {
+            next
+        } else if self.wrap {
+            self.last()
+        } else {
+            self.min
+        };
+    }
+
+    // Highest value on the grid of the step
+    fn last(&self) -> i32 {
+        self.min + (self.max - self.min) / self.step * self.step
+    }
+
+    /// Characters the value takes up, without the prefix
+    pub fn width(&self) -> usize {
+        match self.kind {
+            Kind::Number(digits) => digits as usize,
+            Kind::Choice(labels) => labels.iter().map(|label| label.len()).max().unwrap_or(0),
+        }
+    }
+
+    fn write(&self, text: &mut String<U32>) {
+        let start = text.len();
+        match self.kind {
+            Kind::Number(digits) => {
+                let mut scale = 10i32.pow(digits.max(1) as u32 - 1);
+                while scale > 1 && self.value < scale {
+                    let _ = text.push('0');
+                    scale /= 10;
+                }
+                let _ = uwrite!(text, "{}", self.value);
+            }
+            Kind::Choice(labels) => {
+                let _ = text.push_str(labels.get(self.value as usize).copied().unwrap_or(""));
+            }
+        }
+        while text.len() < start + self.width() {
+            let _ = text.push(' ');
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Edit {
+    Editing,
+    Confirmed,
+    Cancelled,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Editor {
+    fields: Vec<Field, U8>,
+    selected: usize,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            fields: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    /// Appends a field, panics past eight fields
+    pub fn field(mut self, field: Field) -> Self {
+        if self.fields.push(field).is_err() {
+            panic!("Too many fields");
+        }
+        self
+    }
+
+    pub fn value(&self, idx: usize) -> i32 {
+        self.fields[idx].value
+    }
+
+    /// Changes the upper bound of a field, e.g. the days of the edited month
+    pub fn set_max(&mut self, idx: usize, max: i32) {
+        let field = &mut self.fields[idx];
+        field.max = max.max(field.min);
+        field.value = field.value.min(field.max);
+    }
+
+    /// Index of the field the buttons change
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn next_field(&mut self) {
+        self.selected = (self.selected + 1) % self.fields.len().max(1);
+    }
+
+    pub fn increment(&mut self) {
+        if let Some(field) = self.fields.get_mut(self.selected) {
+            field.increment();
+        }
+    }
+
+    pub fn decrement(&mut self) {
+        if let Some(field) = self.fields.get_mut(self.selected) {
+            field.decrement();
+        }
+    }
+
+    pub fn handle(&mut self, event: Event) -> Edit {
+        match (event.button, event.gesture) {
+            (Button::Left, Gesture::Press) | (Button::Left, Gesture::Repeat) => self.decrement(),
+            (Button::Right, Gesture::Press) | (Button::Right, Gesture::Repeat) => self.increment(),
+            // Quick clicks through the fields come as a double click
+            (Button::Enter, Gesture::Click) | (Button::Enter, Gesture::DoubleClick) => {
+                self.next_field()
+            }
+            (_, Gesture::Chord) => return Edit::Cancelled,
+            (Button::Enter, Gesture::LongPress) => return Edit::Confirmed,
+            _ => {}
+        }
+        Edit::Editing
+    }
+
+    /// Text of the fields in `range` along with the character offset and
+    /// width of the selected field, if it is one of them. The prefix of the
+    /// first field is left out.
+    pub fn text(&self, range: Range<usize>) -> (String<U32>, Option<(usize, usize)>) {
+        let mut text = String::new();
+        let mut cursor = None;
+        let first = range.start;
+        for (idx, field) in self.fields.iter().enumerate() {
+            if !range.contains(&idx) {
+                continue;
+            }
+            if idx != first {
+                let _ = text.push_str(field.prefix);
+            }
+            if idx == self.selected {
+                cursor = Some((text.len(), field.width()));
+            }
+            field.write(&mut text);
+        }
+        (text, cursor)
+    }
+
+    /// Draws the fields in `range` with the selected one underlined
+    pub fn render<D>(
+        &self,
+        display: &mut Display<D>,
+        range: Range<usize>,
+        size: FontSize,
+        x: i32,
+        y: i32,
+    ) where
+        D: DrawTarget<Color = Rgb565>,
+        D::Error: Debug,
+    {
+        let (text, cursor) = self.text(range);
+        display.print_text(&text, size, x, y);
+        if let Some((offset, width)) = cursor {
+            let start = x + (offset as u32 * size.width()) as i32;
+            let end = start + (width as u32 * size.width()) as i32 - 1;
+            let underline = y + size.height() as i32;
+            display.print_pointer(Point::new(start, underline), Point::new(end, underline));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(button: Button, gesture: Gesture) -> Event {
+        Event { button, gesture }
+    }
+
+    fn stepped(field: Field, steps: i32) -> i32 {
+        let mut field = field;
+        for _ in 0..steps.abs() {
+            if steps > 0 {
+                field.increment();
+            } else {
+                field.decrement();
+            }
+        }
+        field.value
+    }
+
+    #[test]
+    fn numbers_wrap_around() {
+        let minutes = Field::number(58, 0, 59, 2);
+        assert_eq!(stepped(minutes, 1), 59);
+        assert_eq!(stepped(minutes, 2), 0);
+        let minutes = Field::number(1, 0, 59, 2);
+        assert_eq!(stepped(minutes, -1), 0);
+        assert_eq!(stepped(minutes, -2), 59);
+
+        let month = Field::number(1, 1, 12, 2);
+        assert_eq!(stepped(month, -1), 12);
+        assert_eq!(stepped(month, 12), 1);
+    }
+
+    #[test]
+    fn clamped_numbers_stop_at_the_bounds() {
+        let year = Field::number(2001, 2000, 2099, 4).clamped();
+        assert_eq!(stepped(year, -5), 2000);
+        assert_eq!(stepped(year, 200), 2099);
+    }
+
+    #[test]
+    fn steps_stay_on_the_grid() {
+        let minutes = Field::number(55, 0, 59, 2).step(5);
+        assert_eq!(stepped(minutes, 1), 0);
+        assert_eq!(stepped(Field::number(0, 0, 59, 2).step(5), -1), 55);
+        assert_eq!(stepped(Field::number(5, 0, 59, 2).step(5), -2), 55);
+
+        // Values off the grid snap onto it
+        let minutes = Field::number(58, 0, 59, 2).step(5);
+        assert_eq!(stepped(minutes, 1), 0);
+        assert_eq!(stepped(minutes, -1), 55);
+        assert_eq!(stepped(Field::number(7, 0, 59, 2).step(5), 1), 10);
+        assert_eq!(stepped(Field::number(7, 0, 59, 2).step(5), -1), 5);
+
+        let clamped = Field::number(50, 0, 59, 2).step(5).clamped();
+        assert_eq!(stepped(clamped, 3), 55);
+        assert_eq!(
+            stepped(Field::number(58, 0, 59, 2).step(5).clamped(), 1),
+            58
+        );
+    }
+
+    #[test]
+    fn choices_wrap_around() {
+        let labels = &["Off", "On"];
+        assert_eq!(stepped(Field::choice(1, labels), 1), 0);
+        assert_eq!(stepped(Field::choice(0, labels), -1), 1);
+        assert_eq!(Field::choice(5, labels).value, 1);
+    }
+
+    #[test]
+    fn buttons_edit_confirm_and_cancel() {
+        let mut editor = Editor::new()
+            .field(Field::number(12, 0, 23, 2))
+            .field(Field::number(30, 0, 59, 2).prefix(":"));
+        let right = event(Button::Right, Gesture::Press);
+        assert_eq!(editor.handle(right), Edit::Editing);
+        assert_eq!(editor.value(0), 13);
+
+        // A quick second click moves on like the first one
+        editor.handle(event(Button::Enter, Gesture::Click));
+        editor.handle(event(Button::Enter, Gesture::DoubleClick));
+        assert_eq!(editor.selected(), 0);
+        editor.handle(event(Button::Enter, Gesture::Click));
+        editor.handle(event(Button::Left, Gesture::Repeat));
+        assert_eq!(editor.value(1), 29);
+
+        let chord = event(Button::Right, Gesture::Chord);
+        assert_eq!(editor.handle(chord), Edit::Cancelled);
+        let long_press = event(Button::Enter, Gesture::LongPress);
+        assert_eq!(editor.handle(long_press), Edit::Confirmed);
+    }
+
+    #[test]
+    fn max_follows_the_month() {
+        let mut editor = Editor::new().field(Field::number(31, 1, 31, 2));
+        editor.set_max(0, 28);
+        assert_eq!(editor.value(0), 28);
+        editor.increment();
+        assert_eq!(editor.value(0), 1);
+    }
+
+    #[test]
+    fn text_marks_the_selected_field() {
+        let mut editor = Editor::new()
+            .field(Field::number(7, 0, 23, 2))
+            .field(Field::number(5, 0, 59, 2).prefix(":"))
+            .field(Field::choice(0, &["Once", "Daily"]).prefix(" "));
+        let (text, cursor) = editor.text(0..3);
+        assert_eq!(text.as_str(), "07:05 Once ");
+        assert_eq!(cursor, Some((0, 2)));
+        editor.next_field();
+        editor.next_field();
+        assert_eq!(editor.text(0..3).1, Some((6, 5)));
+        // The prefix of the first field in the range is left out
+        assert_eq!(editor.text(1..3), (String::from("05 Once "), Some((3, 5))));
+    }
+}
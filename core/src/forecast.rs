@@ -0,0 +1,285 @@
+// Barometric weather forecast based on the Zambretti forecaster. The sea
+// level pressure and its 3 hour tendency select one of 26 forecasts (A - Z).
+
+use crate::history::{Bucket, History, Metric, Sample};
+
+/// Period the pressure tendency is measured over
+pub const TENDENCY_SECONDS: u32 = 3 * 3600;
+// Less history than this is too noisy to extrapolate a tendency from
+const MIN_TENDENCY_SECONDS: u32 = 3600;
+// Change in hPa over 3 hours above which pressure counts as rising or falling
+const TREND_THRESHOLD: f32 = 1.6;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Trend {
+    Falling,
+    Steady,
+    Rising,
+}
+
+impl Trend {
+    /// Classifies a pressure change in hPa over 3 hours
+    pub fn from_change(change: f32) -> Self {
+        if change <= -TREND_THRESHOLD {
+            Trend::Falling
+        } else if change >= TREND_THRESHOLD {
+            Trend::Rising
+        } else {
+            Trend::Steady
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Trend::Falling => "falling",
+            Trend::Steady => "steady",
+            Trend::Rising => "rising",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Forecast {
+    /// Zambretti letter, `A` is the most settled and `Z` the stormiest
+    pub letter: char,
+    pub text: &'static str,
+}
+
+const FORECASTS: [&str; 26] = [
+    "Settled fine",
+    "Fine weather",
+    "Becoming fine",
+    "Fine, becoming less settled",
+    "Fine, possible showers",
+    "Fairly fine, improving",
+    "Fairly fine, showers early",
+    "Fairly fine, showery later",
+    "Showery early, improving",
+    "Changeable, mending",
+    "Fairly fine, showers likely",
+    "Rather unsettled, clearing",
+    "Unsettled, probably improving",
+    "Showery, bright intervals",
+    "Showery, less settled",
+    "Changeable, some rain",
+    "Unsettled, short fine spells",
+    "Unsettled, rain later",
+    "Unsettled, some rain",
+    "Mostly very unsettled",
+    "Occasional rain, worsening",
+    "Rain at times, very unsettled",
+    "Rain at frequent intervals",
+    "Rain, very unsettled",
+    "Stormy, may improve",
+    "Stormy, much rain",
+];
+
+// Zambretti numbers mapped to forecast letters for each trend
+const FALLING: [u8; 9] = *b"ABDHORUXZ";
+const STEADY: [u8; 10] = *b"ABEKNPSWXZ";
+const RISING: [u8; 13] = *b"ABCFGIJLMQTYZ";
+
+/// Forecast for a sea level pressure in hPa and its trend
+pub fn zambretti(pressure: f32, trend: Trend) -> Forecast {
+    // Zambretti numbers 1-9 are used for falling, 10-19 for steady and 20-32
+    // for rising pressure
+    let (z, first, letters): (f32, i32, &[u8]) = match trend {
+        Trend::Falling => (127.0 - 0.12 * pressure, 1, &FALLING[..]),
+        Trend::Steady => (144.0 - 0.13 * pressure, 10, &STEADY[..]),
+        Trend::Rising => (185.0 - 0.16 * pressure, 20, &RISING[..]),
+    };
+    let idx = (libm::roundf(z) as i32 - first)
+        .max(0)
+        .min(letters.len() as i32 - 1);
+    let letter = letters[idx as usize];
+
+    Forecast {
+        letter: letter as char,
+        text: FORECASTS[(letter - b'A') as usize],
+    }
+}
+
+/// Reduces the station pressure to sea level using the barometric formula,
+/// pressure in Pa, temperature in degree Celsius and altitude in metres
+pub fn sea_level_pressure(pressure: f32, temperature: f32, altitude: f32) -> f32 {
+    let lapse = 0.0065 * altitude;
+    pressure * libm::powf(1.0 - lapse / (temperature + lapse + 273.15), -5.257)
+}
+
+/// Pressure change in hPa over the last 3 hours, extrapolated when there is
+/// at least an hour of history
+pub fn tendency(history: &History) -> Option<f32> {
+    // Only the buckets with a pressure reading
+    let pressure = |bucket: Bucket| Some((bucket.timestamp, bucket.stats(Metric::Pressure)?.avg));
+    let buckets = || history.iter().filter_map(pressure);
+
+    let (newest, newest_pressure) = buckets().last()?;
+    let since = newest.saturating_sub(TENDENCY_SECONDS);
+
+    let oldest = buckets().next()?;
+    let (reference, reference_pressure) = buckets()
+        .filter(|(timestamp, _)| *timestamp <= since)
+        .last()
+        .unwrap_or(oldest);
+
+    let span = newest - reference;
+    if span < MIN_TENDENCY_SECONDS {
+        return None;
+    }
+
+    let change = newest_pressure - reference_pressure;
+    // Pa to hPa, scaled to the 3 hour period
+    Some(change / 100.0 * TENDENCY_SECONDS as f32 / span as f32)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Weather {
+    /// Sea level pressure in hPa
+    pub pressure: f32,
+    pub trend: Trend,
+    pub forecast: Forecast,
+}
+
+/// Forecast from the latest measurement and the pressure history, `None`
+/// without a pressure and temperature reading or enough history
+pub fn forecast(history: &History, sample: &Sample, altitude: f32) -> Option<Weather> {
+    let (pressure, temperature) = (sample.pressure?, sample.temperature?);
+    let trend = Trend::from_change(tendency(history)?);
+    let pressure = sea_level_pressure(pressure, temperature, altitude) / 100.0;
+
+    Some(Weather {
+        pressure,
+        trend,
+        forecast: zambretti(pressure, trend),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::BUCKET_SECONDS;
+
+    fn letter(pressure: f32, trend: Trend) -> char {
+        zambretti(pressure, trend).letter
+    }
+
+    #[test]
+    fn trend_thresholds() {
+        assert_eq!(Trend::from_change(-1.6), Trend::Falling);
+        assert_eq!(Trend::from_change(-1.5), Trend::Steady);
+        assert_eq!(Trend::from_change(1.5), Trend::Steady);
+        assert_eq!(Trend::from_change(1.6), Trend::Rising);
+    }
+
+    #[test]
+    fn falling_pressure() {
+        // Z = 127 - 0.12 P: 3 at 1030 hPa, 8 at 990 hPa
+        assert_eq!(letter(1030.0, Trend::Falling), 'D');
+        assert_eq!(letter(990.0, Trend::Falling), 'X');
+        assert_eq!(letter(1050.0, Trend::Falling), 'A');
+        assert_eq!(letter(950.0, Trend::Falling), 'Z');
+        assert_eq!(
+            zambretti(990.0, Trend::Falling).text,
+            "Rain, very unsettled"
+        );
+    }
+
+    #[test]
+    fn steady_pressure() {
+        // Z = 144 - 0.13 P: 11 at 1020 hPa, 15 at 990 hPa
+        assert_eq!(letter(1020.0, Trend::Steady), 'B');
+        assert_eq!(letter(990.0, Trend::Steady), 'P');
+        assert_eq!(letter(1060.0, Trend::Steady), 'A');
+        assert_eq!(letter(950.0, Trend::Steady), 'Z');
+        assert_eq!(
+            zambretti(990.0, Trend::Steady).text,
+            "Changeable, some rain"
+        );
+    }
+
+    #[test]
+    fn rising_pressure() {
+        // Z = 185 - 0.16 P: 22 at 1020 hPa, 27 at 990 hPa
+        assert_eq!(letter(1020.0, Trend::Rising), 'C');
+        assert_eq!(letter(990.0, Trend::Rising), 'L');
+        assert_eq!(letter(1060.0, Trend::Rising), 'A');
+        assert_eq!(letter(950.0, Trend::Rising), 'Z');
+        assert_eq!(zambretti(1020.0, Trend::Rising).text, "Becoming fine");
+    }
+
+    #[test]
+    fn altitude_correction() {
+        assert_eq!(sea_level_pressure(101_325.0, 15.0, 0.0), 101_325.0);
+        // Standard atmosphere at 1000 m
+        let reduced = sea_level_pressure(89_876.0, 8.5, 1000.0);
+        assert!((reduced - 101_325.0).abs() < 10.0, "{}", reduced);
+        // A colder column of air is denser, the pressure rises less with depth
+        assert!(sea_level_pressure(89_876.0, -10.0, 1000.0) > reduced);
+    }
+
+    fn history(hours: u32, hpa_per_hour: f32) -> History {
+        let mut history = History::new();
+        for bucket in 0..=hours * 4 {
+            let sample = Sample {
+                temperature: Some(15.0),
+                humidity: Some(50.0),
+                pressure: Some(95_000.0 + bucket as f32 * hpa_per_hour * 25.0),
+            };
+            history.record(bucket * BUCKET_SECONDS, &sample);
+        }
+        history
+    }
+
+    #[test]
+    fn tendency_spans_three_hours() {
+        assert_eq!(tendency(&History::new()), None);
+        assert_eq!(tendency(&history(4, 1.0)), Some(3.0));
+        // Two hours of history are extrapolated
+        assert_eq!(tendency(&history(2, -1.0)), Some(-3.0));
+        assert_eq!(tendency(&history(0, 1.0)), None);
+    }
+
+    fn reading(pressure: f32) -> Sample {
+        Sample {
+            temperature: Some(15.0),
+            humidity: None,
+            pressure: Some(pressure),
+        }
+    }
+
+    #[test]
+    fn forecast_at_altitude() {
+        // 954 hPa at 540 m are about 1017 hPa at sea level
+        let weather = forecast(&history(4, 1.0), &reading(95_400.0), 540.0).unwrap();
+        assert_eq!(weather.trend, Trend::Rising);
+        assert!(
+            (weather.pressure - 1016.7).abs() < 0.5,
+            "{}",
+            weather.pressure
+        );
+        assert_eq!(weather.forecast.letter, 'C');
+        // Taken as sea level pressure it would be a storm
+        let uncorrected = forecast(&history(4, 1.0), &reading(95_400.0), 0.0).unwrap();
+        assert_eq!(uncorrected.forecast.letter, 'Z');
+    }
+
+    #[test]
+    fn no_forecast_without_pressure() {
+        let mut history = History::new();
+        let sample = Sample {
+            temperature: Some(15.0),
+            humidity: Some(50.0),
+            pressure: None,
+        };
+        for bucket in 0..=16 {
+            history.record(bucket * BUCKET_SECONDS, &sample);
+        }
+        assert_eq!(tendency(&history), None);
+        assert_eq!(forecast(&history, &sample, 0.0), None);
+        // Buckets from before the pressure was read are left out
+        for bucket in 17..=20 {
+            history.record(bucket * BUCKET_SECONDS, &reading(100_000.0));
+        }
+        assert_eq!(tendency(&history), None);
+    }
+}
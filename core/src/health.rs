@@ -0,0 +1,185 @@
+// Sensor health. A failed measurement is retried with an exponential backoff,
+// each retry initialises the sensor again. A few failures in a row report the
+// sensor offline until a measurement succeeds again.
+
+const FIRST_RETRY_MS: u32 = 1000;
+const MAX_RETRY_MS: u32 = 60_000;
+/// Failures in a row before the sensor counts as offline
+const OFFLINE_AFTER: u32 = 3;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum State {
+    Online,
+    Failing {
+        /// Failures in a row
+        failures: u32,
+        retry_at: u32,
+    },
+}
+
+/// What to do with the sensor next
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Action {
+    Measure,
+    /// Initialise the sensor before measuring
+    Reinit,
+    Wait,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Health {
+    state: State,
+    /// Failed measurements and initialisations since boot
+    pub errors: u32,
+    /// Times the sensor came back after failing
+    pub recoveries: u32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self {
+            state: State::Online,
+            errors: 0,
+            recoveries: 0,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn action(&self, now_ms: u32) -> Action {
+        match self.state {
+            State::Online => Action::Measure,
+            // Compared through the difference so the millisecond counter may wrap
+            State::Failing { retry_at, .. } if now_ms.wrapping_sub(retry_at) as i32 >= 0 => {
+                Action::Reinit
+            }
+            State::Failing { .. } => Action::Wait,
+        }
+    }
+
+    pub fn success(&mut self) {
+        if let State::Failing { .. } = self.state {
+            self.recoveries += 1;
+        }
+        self.state = State::Online;
+    }
+
+    pub fn failure(&mut self, now_ms: u32) {
+        self.errors += 1;
+        let failures = match self.state {
+            State::Online => 1,
+            State::Failing { failures, .. } => failures + 1,
+        };
+        let backoff = (FIRST_RETRY_MS << (failures - 1).min(6)).min(MAX_RETRY_MS);
+        self.state = State::Failing {
+            failures,
+            retry_at: now_ms.wrapping_add(backoff),
+        };
+    }
+
+    pub fn is_offline(&self) -> bool {
+        matches!(self.state, State::Failing { failures, .. } if failures >= OFFLINE_AFTER)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self.state {
+            State::Online => "online",
+            _ if self.is_offline() => "offline",
+            State::Failing { .. } => "retrying",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails every retry starting at `start` and returns the waits between them
+    fn backoff(health: &mut Health, start: u32, retries: usize) -> [u32; 8] {
+        let mut waits = [0; 8];
+        let mut now = start;
+        health.failure(now);
+        for wait in waits.iter_mut().take(retries) {
+            let retry_at = match health.state() {
+                State::Failing { retry_at, .. } => retry_at,
+                State::Online => panic!("not failing"),
+            };
+            *wait = retry_at.wrapping_sub(now);
+            assert_eq!(health.action(retry_at.wrapping_sub(1)), Action::Wait);
+            assert_eq!(health.action(retry_at), Action::Reinit);
+            now = retry_at;
+            health.failure(now);
+        }
+        waits
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_a_minute() {
+        let mut health = Health::new();
+        assert_eq!(health.action(0), Action::Measure);
+        let waits = backoff(&mut health, 5000, 8);
+        assert_eq!(
+            waits,
+            [1000, 2000, 4000, 8000, 16_000, 32_000, 60_000, 60_000]
+        );
+    }
+
+    #[test]
+    fn retries_across_the_timer_wrap() {
+        let mut health = Health::new();
+        let waits = backoff(&mut health, u32::MAX - 1500, 3);
+        assert_eq!(waits[..3], [1000, 2000, 4000]);
+
+        let mut health = Health::new();
+        health.failure(u32::MAX - 200);
+        assert_eq!(health.action(u32::MAX), Action::Wait);
+        assert_eq!(health.action(500), Action::Wait);
+        assert_eq!(health.action(799), Action::Reinit);
+    }
+
+    #[test]
+    fn offline_after_three_failures() {
+        let mut health = Health::new();
+        assert_eq!(health.as_str(), "online");
+        health.failure(0);
+        health.failure(1000);
+        assert!(!health.is_offline());
+        assert_eq!(health.as_str(), "retrying");
+        health.failure(3000);
+        assert!(health.is_offline());
+        assert_eq!(health.as_str(), "offline");
+    }
+
+    #[test]
+    fn success_recovers_and_counts() {
+        let mut health = Health::new();
+        health.success();
+        assert_eq!(health.recoveries, 0);
+
+        for now in [0, 1000, 3000, 7000].iter() {
+            health.failure(*now);
+        }
+        health.success();
+        assert_eq!(health.state(), State::Online);
+        assert_eq!(health.action(7001), Action::Measure);
+        assert!(!health.is_offline());
+
+        // The backoff starts over after a recovery
+        health.failure(10_000);
+        let expected = State::Failing {
+            failures: 1,
+            retry_at: 11_000,
+        };
+        assert_eq!(health.state(), expected);
+        health.success();
+        assert_eq!((health.errors, health.recoveries), (5, 2));
+    }
+}
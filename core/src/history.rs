@@ -0,0 +1,441 @@
+use heapless::{consts::*, Vec};
+use pomia_sensors::{Capabilities, Measurement};
+
+/// Samples are folded into buckets of this many seconds
+pub const BUCKET_SECONDS: u32 = 15 * 60;
+pub const HOUR: u32 = 3600;
+pub const DAY: u32 = 24 * HOUR;
+
+// 24 hours worth of 15 minute buckets
+type Capacity = U96;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Metric {
+    Temperature,
+    Humidity,
+    Pressure,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Temperature, Metric::Humidity, Metric::Pressure];
+
+    // Values are kept as i16 in hundredths of a degree/percent and in decapascal
+    fn scale(self) -> f32 {
+        match self {
+            Metric::Temperature | Metric::Humidity => 100.0,
+            Metric::Pressure => 0.1,
+        }
+    }
+
+    fn to_fixed(self, value: f32) -> i16 {
+        (value * self.scale()) as i16
+    }
+
+    fn to_float(self, value: i16) -> f32 {
+        value as f32 / self.scale()
+    }
+
+    pub fn measured_by(self, capabilities: &Capabilities) -> bool {
+        match self {
+            Metric::Temperature => capabilities.temperature,
+            Metric::Humidity => capabilities.humidity,
+            Metric::Pressure => capabilities.pressure,
+        }
+    }
+}
+
+/// Latest measurement, `None` for what the sensor does not measure
+#[derive(Copy, Clone)]
+pub struct Sample {
+    /// Degree Celsius
+    pub temperature: Option<f32>,
+    /// Relative humidity in percent
+    pub humidity: Option<f32>,
+    /// Pascal
+    pub pressure: Option<f32>,
+}
+
+impl From<Measurement> for Sample {
+    fn from(measurement: Measurement) -> Self {
+        Self {
+            temperature: measurement.temperature,
+            humidity: measurement.humidity,
+            pressure: measurement.pressure,
+        }
+    }
+}
+
+impl Sample {
+    pub fn get(&self, metric: Metric) -> Option<f32> {
+        match metric {
+            Metric::Temperature => self.temperature,
+            Metric::Humidity => self.humidity,
+            Metric::Pressure => self.pressure,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Stats {
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+}
+
+#[derive(Copy, Clone)]
+struct Aggregate {
+    min: i16,
+    max: i16,
+    avg: i16,
+    /// Samples that measured the metric, 0 if none did
+    count: u16,
+}
+
+#[derive(Copy, Clone)]
+pub struct Bucket {
+    /// RTC timestamp of the start of the bucket
+    pub timestamp: u32,
+    /// Number of samples folded into the bucket
+    pub count: u16,
+    values: [Aggregate; 3],
+}
+
+impl Bucket {
+    /// `None` if none of the samples measured the metric
+    pub fn stats(&self, metric: Metric) -> Option<Stats> {
+        let aggregate = &self.values[metric as usize];
+        if aggregate.count == 0 {
+            return None;
+        }
+        Some(Stats {
+            min: metric.to_float(aggregate.min),
+            max: metric.to_float(aggregate.max),
+            avg: metric.to_float(aggregate.avg),
+        })
+    }
+
+    fn count(&self, metric: Metric) -> u16 {
+        self.values[metric as usize].count
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Accumulator {
+    min: i16,
+    max: i16,
+    sum: i32,
+    count: i32,
+}
+
+impl Accumulator {
+    const EMPTY: Self = Self {
+        min: i16::MAX,
+        max: i16::MIN,
+        sum: 0,
+        count: 0,
+    };
+
+    fn add(&mut self, value: i16) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value as i32;
+        self.count += 1;
+    }
+
+    fn close(&self) -> Aggregate {
+        if self.count == 0 {
+            return Aggregate {
+                min: 0,
+                max: 0,
+                avg: 0,
+                count: 0,
+            };
+        }
+        Aggregate {
+            min: self.min,
+            max: self.max,
+            avg: (self.sum / self.count) as i16,
+            count: self.count as u16,
+        }
+    }
+}
+
+/// Collects the samples of the bucket currently being filled
+struct Current {
+    bucket: u32,
+    count: i32,
+    values: [Accumulator; 3],
+}
+
+impl Current {
+    fn new(bucket: u32, sample: &Sample) -> Self {
+        let mut current = Self {
+            bucket,
+            count: 0,
+            values: [Accumulator::EMPTY; 3],
+        };
+        current.add(sample);
+        current
+    }
+
+    fn add(&mut self, sample: &Sample) {
+        for (acc, metric) in self.values.iter_mut().zip(Metric::ALL.iter()) {
+            if let Some(value) = sample.get(*metric) {
+                acc.add(metric.to_fixed(value));
+            }
+        }
+        self.count += 1;
+    }
+
+    fn close(&self) -> Bucket {
+        let mut values = [Accumulator::EMPTY.close(); 3];
+        for (aggregate, acc) in values.iter_mut().zip(self.values.iter()) {
+            *aggregate = acc.close();
+        }
+        Bucket {
+            timestamp: self.bucket * BUCKET_SECONDS,
+            count: self.count as u16,
+            values,
+        }
+    }
+}
+
+/// Ring buffer of the last 24 hours of measurements
+pub struct History {
+    buckets: Vec<Bucket, Capacity>,
+    // Slot the next bucket is written to once the buffer is full
+    oldest: usize,
+    current: Option<Current>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            buckets: Vec::new(),
+            oldest: 0,
+            current: None,
+        }
+    }
+
+    pub fn record(&mut self, timestamp: u32, sample: &Sample) {
+        let bucket = timestamp / BUCKET_SECONDS;
+        match &mut self.current {
+            Some(current) if current.bucket == bucket => current.add(sample),
+            // The clock was set back, what was recorded since then never happened
+            Some(current) if current.bucket > bucket => {
+                self.forget_since(bucket * BUCKET_SECONDS);
+                self.current = Some(Current::new(bucket, sample));
+            }
+            _ => {
+                if let Some(current) = self.current.take() {
+                    self.push(current.close());
+                }
+                self.current = Some(Current::new(bucket, sample));
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buckets = Vec::new();
+        self.oldest = 0;
+        self.current = None;
+    }
+
+    /// Finished buckets from the oldest to the newest, followed by the one being filled
+    pub fn iter(&self) -> impl Iterator<Item = Bucket> + '_ {
+        let (newer, older) = self.buckets.split_at(self.oldest);
+        older
+            .iter()
+            .chain(newer.iter())
+            .copied()
+            .chain(self.current.as_ref().map(Current::close))
+    }
+
+    /// Min, max and average of the buckets started within `window` seconds
+    /// before `now`. A bucket reaching back past the start of the window is
+    /// left out, so an hour covers the last 45 to 60 minutes. The average is
+    /// weighted by the number of samples in each bucket, `None` if no sample
+    /// in the window measured the metric.
+    pub fn stats(&self, metric: Metric, now: u32, window: u32) -> Option<Stats> {
+        let since = now.saturating_sub(window);
+        let mut result: Option<Stats> = None;
+        let mut sum = 0.0;
+        let mut count = 0;
+        let in_window = |b: &Bucket| b.timestamp >= since && b.timestamp <= now;
+        for bucket in self.iter().filter(in_window) {
+            let stats = match bucket.stats(metric) {
+                Some(stats) => stats,
+                None => continue,
+            };
+            result = Some(match result {
+                Some(acc) => Stats {
+                    min: acc.min.min(stats.min),
+                    max: acc.max.max(stats.max),
+                    avg: 0.0,
+                },
+                None => stats,
+            });
+            sum += stats.avg * bucket.count(metric) as f32;
+            count += bucket.count(metric) as u32;
+        }
+        result.map(|stats| Stats {
+            avg: sum / count as f32,
+            ..stats
+        })
+    }
+
+    /// Drops the buckets started at or after `timestamp`
+    fn forget_since(&mut self, timestamp: u32) {
+        // Oldest first, the buckets are then sorted by their timestamps
+        self.buckets.rotate_left(self.oldest);
+        self.oldest = 0;
+        while matches!(self.buckets.last(), Some(bucket) if bucket.timestamp >= timestamp) {
+            self.buckets.pop();
+        }
+    }
+
+    fn push(&mut self, bucket: Bucket) {
+        if let Err(bucket) = self.buckets.push(bucket) {
+            self.buckets[self.oldest] = bucket;
+            self.oldest = (self.oldest + 1) % self.buckets.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u32 = 1_000 * BUCKET_SECONDS;
+    const MINUTE: u32 = 60;
+
+    fn sample(temperature: f32) -> Sample {
+        Sample {
+            temperature: Some(temperature),
+            humidity: Some(50.0),
+            pressure: Some(100_000.0),
+        }
+    }
+
+    fn temperature(history: &History, now: u32, window: u32) -> Option<Stats> {
+        history.stats(Metric::Temperature, now, window)
+    }
+
+    fn timestamps(history: &History) -> Vec<u32, U100> {
+        history.iter().map(|bucket| bucket.timestamp).collect()
+    }
+
+    #[test]
+    fn window_leaves_out_older_buckets() {
+        let mut history = History::new();
+        for (quarter, value) in [10.0, 20.0, 30.0, 40.0, 50.0].iter().enumerate() {
+            history.record(START + quarter as u32 * 15 * MINUTE, &sample(*value));
+        }
+        let now = START + 61 * MINUTE;
+        let stats = temperature(&history, now, HOUR).unwrap();
+        assert_eq!(stats.min, 20.0);
+        assert_eq!(stats.max, 50.0);
+        assert_eq!(stats.avg, 35.0);
+        assert_eq!(temperature(&history, now, DAY).unwrap().min, 10.0);
+        assert_eq!(temperature(&history, START + 61 * MINUTE + DAY, DAY), None);
+    }
+
+    #[test]
+    fn average_is_weighted_by_samples() {
+        let mut history = History::new();
+        for minute in 0..3 {
+            history.record(START + minute * MINUTE, &sample(10.0));
+        }
+        history.record(START + 15 * MINUTE, &sample(30.0));
+        let stats = temperature(&history, START + 20 * MINUTE, HOUR).unwrap();
+        assert_eq!(stats.avg, 15.0);
+        let counts: Vec<u16, U2> = history.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(&counts[..], &[3, 1]);
+    }
+
+    #[test]
+    fn clock_set_back_drops_later_buckets() {
+        let mut history = History::new();
+        for quarter in 0..8 {
+            history.record(START + quarter * 15 * MINUTE, &sample(quarter as f32));
+        }
+        // A sample from before the clock was set back
+        assert_eq!(
+            temperature(&history, START + 50 * MINUTE, HOUR)
+                .unwrap()
+                .max,
+            3.0
+        );
+
+        history.record(START + 35 * MINUTE, &sample(-5.0));
+        let expected = [START, START + 15 * MINUTE, START + 30 * MINUTE];
+        assert_eq!(&timestamps(&history)[..], &expected);
+        let stats = temperature(&history, START + 40 * MINUTE, DAY).unwrap();
+        assert_eq!(stats.min, -5.0);
+        assert_eq!(stats.max, 1.0);
+    }
+
+    #[test]
+    fn ring_keeps_the_newest_day() {
+        let mut history = History::new();
+        for quarter in 0..120 {
+            history.record(START + quarter * BUCKET_SECONDS, &sample(quarter as f32));
+        }
+        let stamps = timestamps(&history);
+        // 96 finished buckets and the one being filled
+        assert_eq!(stamps.len(), 97);
+        assert_eq!(stamps[0], START + 23 * BUCKET_SECONDS);
+        assert!(stamps
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] == BUCKET_SECONDS));
+
+        history.record(START + 100 * BUCKET_SECONDS, &sample(0.0));
+        let stamps = timestamps(&history);
+        assert_eq!(stamps.len(), 78);
+        assert_eq!(stamps[77], START + 100 * BUCKET_SECONDS);
+        assert!(stamps
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] == BUCKET_SECONDS));
+    }
+
+    #[test]
+    fn unmeasured_metrics_have_no_stats() {
+        let mut history = History::new();
+        let humid = Sample {
+            temperature: Some(20.0),
+            humidity: Some(40.0),
+            pressure: None,
+        };
+        let dry = Sample {
+            humidity: None,
+            ..humid
+        };
+        history.record(START, &humid);
+        history.record(START + MINUTE, &dry);
+        history.record(START + 2 * MINUTE, &dry);
+        let now = START + 3 * MINUTE;
+        assert_eq!(history.stats(Metric::Pressure, now, HOUR), None);
+        assert!(history
+            .iter()
+            .all(|bucket| bucket.stats(Metric::Pressure).is_none()));
+        // Only the sample that measured the humidity counts for its average
+        assert_eq!(
+            history.stats(Metric::Humidity, now, HOUR).unwrap().avg,
+            40.0
+        );
+        assert_eq!(temperature(&history, now, HOUR).unwrap().avg, 20.0);
+
+        // A later bucket without the humidity does not pull the day down
+        history.record(START + BUCKET_SECONDS, &dry);
+        let stats = history
+            .stats(Metric::Humidity, now + BUCKET_SECONDS, DAY)
+            .unwrap();
+        assert_eq!((stats.min, stats.avg), (40.0, 40.0));
+    }
+}
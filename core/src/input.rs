@@ -0,0 +1,349 @@
+use heapless::{consts::*, spsc::Queue};
+
+/// A level has to be stable this long before it is accepted
+pub const DEBOUNCE_MS: u32 = 20;
+pub const LONG_PRESS_MS: u32 = 1500;
+/// Second click within this time after the first one is a double click
+pub const DOUBLE_CLICK_MS: u32 = 300;
+pub const REPEAT_DELAY_MS: u32 = 500;
+pub const REPEAT_INTERVAL_MS: u32 = 150;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Button {
+    Enter,
+    Left,
+    Right,
+}
+
+impl Button {
+    const ALL: [Button; 3] = [Button::Enter, Button::Left, Button::Right];
+
+    // Holding the arrows scrolls, holding enter is a long press
+    fn repeats(self) -> bool {
+        !matches!(self, Button::Enter)
+    }
+
+    fn other_arrow(self) -> Option<Button> {
+        match self {
+            Button::Enter => None,
+            Button::Left => Some(Button::Right),
+            Button::Right => Some(Button::Left),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Gesture {
+    Press,
+    Release,
+    /// Press and release without a long press
+    Click,
+    /// Takes the place of the second `Click`
+    DoubleClick,
+    LongPress,
+    /// Sent while an arrow button is held down
+    Repeat,
+    /// Both arrows held together, takes the place of the `Press` of the
+    /// second one. Neither of them repeats or clicks until both are released.
+    Chord,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Event {
+    pub button: Button,
+    pub gesture: Gesture,
+}
+
+#[derive(Copy, Clone)]
+struct ButtonState {
+    raw: bool,
+    raw_since: u32,
+    pressed: bool,
+    pressed_at: u32,
+    held_reported: bool,
+    next_repeat: u32,
+    last_click: Option<u32>,
+}
+
+impl ButtonState {
+    const fn new() -> Self {
+        Self {
+            raw: false,
+            raw_since: 0,
+            pressed: false,
+            pressed_at: 0,
+            held_reported: false,
+            next_repeat: 0,
+            last_click: None,
+        }
+    }
+}
+
+/// Debounces the raw button levels and turns them into gesture events.
+/// Edges are fed from the EXTI interrupt, `poll` runs from a periodic timer.
+pub struct Input {
+    buttons: [ButtonState; 3],
+    events: Queue<Event, U16>,
+    /// Both arrows went down together and are not released yet
+    chord: bool,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            buttons: [ButtonState::new(); 3],
+            events: Queue::new(),
+            chord: false,
+        }
+    }
+
+    /// Records the level of a button after an edge, `pressed` is the raw level
+    pub fn on_edge(&mut self, button: Button, pressed: bool, now: u32) {
+        let state = &mut self.buttons[button as usize];
+        if state.raw != pressed {
+            state.raw = pressed;
+            state.raw_since = now;
+        }
+    }
+
+    /// Accepts levels that stayed stable long enough and generates the time
+    /// based gestures
+    pub fn poll(&mut self, now: u32) {
+        for button in Button::ALL.iter() {
+            let mut state = self.buttons[*button as usize];
+
+            if state.raw != state.pressed && now.wrapping_sub(state.raw_since) >= DEBOUNCE_MS {
+                state.pressed = state.raw;
+                if state.pressed {
+                    state.pressed_at = now;
+                    state.held_reported = false;
+                    state.next_repeat = now.wrapping_add(REPEAT_DELAY_MS);
+                    match button.other_arrow() {
+                        Some(other) if self.buttons[other as usize].pressed => {
+                            self.chord = true;
+                            state.held_reported = true;
+                            self.buttons[other as usize].held_reported = true;
+                            self.push(*button, Gesture::Chord);
+                        }
+                        _ => self.push(*button, Gesture::Press),
+                    }
+                } else {
+                    self.push(*button, Gesture::Release);
+                    if !state.held_reported {
+                        let double = matches!(state.last_click,
+                            Some(at) if now.wrapping_sub(at) <= DOUBLE_CLICK_MS);
+                        if double {
+                            state.last_click = None;
+                            self.push(*button, Gesture::DoubleClick);
+                        } else {
+                            state.last_click = Some(now);
+                            self.push(*button, Gesture::Click);
+                        }
+                    }
+                }
+            }
+
+            if state.pressed {
+                let held = now.wrapping_sub(state.pressed_at);
+                if button.repeats() {
+                    // Wrapping safe `now >= next_repeat`
+                    if !self.chord && (now.wrapping_sub(state.next_repeat) as i32) >= 0 {
+                        state.held_reported = true;
+                        state.next_repeat = now.wrapping_add(REPEAT_INTERVAL_MS);
+                        self.push(*button, Gesture::Repeat);
+                    }
+                } else if !state.held_reported && held >= LONG_PRESS_MS {
+                    state.held_reported = true;
+                    self.push(*button, Gesture::LongPress);
+                }
+            }
+
+            self.buttons[*button as usize] = state;
+        }
+
+        let arrows = [Button::Left, Button::Right];
+        if arrows
+            .iter()
+            .all(|arrow| !self.buttons[*arrow as usize].pressed)
+        {
+            self.chord = false;
+        }
+    }
+
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.dequeue()
+    }
+
+    pub fn has_event(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    /// No button is down and no event waits, nothing needs `poll` to run
+    pub fn is_idle(&self) -> bool {
+        !self.has_event()
+            && self
+                .buttons
+                .iter()
+                .all(|state| !state.raw && !state.pressed)
+    }
+
+    fn push(&mut self, button: Button, gesture: Gesture) {
+        // A full queue means nobody is reading, dropping the newest is fine
+        let _ = self.events.enqueue(Event { button, gesture });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+    use Gesture::*;
+
+    // The timer tick of the firmware
+    const TICK_MS: u32 = 10;
+
+    /// Feeds the `(ms, pressed)` edges of one button starting at `start`,
+    /// polls on every tick up to `until` and returns the gestures
+    fn run(button: Button, start: u32, edges: &[(u32, bool)], until: u32) -> Vec<Gesture, U32> {
+        let mut input = Input::new();
+        let mut next_edge = 0;
+        let mut gestures = Vec::new();
+        for tick in 0..=until / TICK_MS {
+            let ms = tick * TICK_MS;
+            while let Some((at, pressed)) = edges.get(next_edge).filter(|(at, _)| *at <= ms) {
+                input.on_edge(button, *pressed, start.wrapping_add(*at));
+                next_edge += 1;
+            }
+            input.poll(start.wrapping_add(ms));
+            while let Some(event) = input.next_event() {
+                assert_eq!(event.button, button);
+                gestures.push(event.gesture).unwrap();
+            }
+        }
+        assert!(input.is_idle());
+        gestures
+    }
+
+    #[test]
+    fn bounces_are_filtered() {
+        let edges = [
+            (0, true),
+            (3, false),
+            (5, true),
+            (8, false),
+            (12, true),
+            (100, false),
+            (102, true),
+            (104, false),
+        ];
+        assert_eq!(run(Button::Left, 0, &edges, 300), [Press, Release, Click]);
+        // Shorter than the debounce time
+        assert_eq!(run(Button::Left, 0, &[(0, true), (15, false)], 300), []);
+    }
+
+    #[test]
+    fn held_enter_is_a_long_press() {
+        let edges = [(0, true), (2000, false)];
+        assert_eq!(
+            run(Button::Enter, 0, &edges, 2500),
+            [Press, LongPress, Release]
+        );
+        let edges = [(0, true), (LONG_PRESS_MS - 50, false)];
+        assert_eq!(run(Button::Enter, 0, &edges, 2500), [Press, Release, Click]);
+    }
+
+    #[test]
+    fn held_arrow_repeats() {
+        let edges = [(0, true), (1000, false)];
+        let expected = [Press, Repeat, Repeat, Repeat, Repeat, Release];
+        assert_eq!(run(Button::Right, 0, &edges, 1500), expected);
+    }
+
+    #[test]
+    fn second_quick_click_is_a_double_click() {
+        let edges = [
+            (0, true),
+            (100, false),
+            (200, true),
+            (300, false),
+            // A third click starts over
+            (400, true),
+            (500, false),
+            // Too late for a double click
+            (1000, true),
+            (1100, false),
+        ];
+        let expected = [
+            Press,
+            Release,
+            Click,
+            Press,
+            Release,
+            DoubleClick,
+            Press,
+            Release,
+            Click,
+            Press,
+            Release,
+            Click,
+        ];
+        assert_eq!(run(Button::Enter, 0, &edges, 1500), expected);
+    }
+
+    #[test]
+    fn timestamps_wrap() {
+        let start = u32::MAX - 700;
+        let edges = [(0, true), (2000, false), (2100, true), (2200, false)];
+        let expected = [Press, LongPress, Release, Press, Release, Click];
+        assert_eq!(run(Button::Enter, start, &edges, 2500), expected);
+    }
+
+    #[test]
+    fn both_arrows_are_a_chord() {
+        let edges = [
+            (0, Button::Left, true),
+            (100, Button::Right, true),
+            (1200, Button::Left, false),
+            (1300, Button::Right, false),
+        ];
+        let mut input = Input::new();
+        let mut events: Vec<(Button, Gesture), U32> = Vec::new();
+        for ms in (0..=1500).step_by(TICK_MS as usize) {
+            for (at, button, pressed) in edges.iter().filter(|(at, ..)| *at == ms) {
+                input.on_edge(*button, *pressed, *at);
+            }
+            input.poll(ms);
+            while let Some(event) = input.next_event() {
+                events.push((event.button, event.gesture)).unwrap();
+            }
+        }
+        // Neither repeats nor clicks while both are held
+        let expected = [
+            (Button::Left, Press),
+            (Button::Right, Chord),
+            (Button::Left, Release),
+            (Button::Right, Release),
+        ];
+        assert_eq!(events, expected);
+
+        // Afterwards a single arrow works as before
+        let edges = [(2000, true), (2100, false)];
+        for ms in (2000..=2500).step_by(TICK_MS as usize) {
+            for (at, pressed) in edges.iter().filter(|(at, _)| *at == ms) {
+                input.on_edge(Button::Left, *pressed, *at);
+            }
+            input.poll(ms);
+        }
+        let mut gestures: Vec<Gesture, U8> = Vec::new();
+        while let Some(event) = input.next_event() {
+            gestures.push(event.gesture).unwrap();
+        }
+        assert_eq!(gestures, [Press, Release, Click]);
+    }
+}
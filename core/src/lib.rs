@@ -0,0 +1,34 @@
+//! Board independent logic of the firmware, kept apart from the HAL so it
+//! builds and is tested on the PC.
+
+#![no_std]
+// The suggested `is_multiple_of` and `div_ceil` are newer than the compiler
+// the firmware is built with
+#![allow(unknown_lints, clippy::manual_is_multiple_of, clippy::manual_div_ceil)]
+
+pub mod alarm;
+pub mod backlight;
+pub mod clock;
+pub mod comfort;
+pub mod decimal;
+pub mod display;
+pub mod editor;
+pub mod forecast;
+pub mod health;
+pub mod history;
+pub mod input;
+#[cfg(test)]
+mod memory;
+pub mod menu;
+pub mod note;
+pub mod rotation;
+pub mod rtttl;
+pub mod screens;
+pub mod segments;
+pub mod settings;
+pub mod shell;
+#[cfg(test)]
+mod snapshots;
+pub mod telemetry;
+pub mod theme;
+pub mod tone;
@@ -0,0 +1,85 @@
+// In-memory stand-ins for the settings storage, to test the settings code on
+// the PC
+
+use crate::settings::{Error, Flash, Registers};
+
+const PAGE_SIZE: usize = 1024;
+
+/// Behaves like a flash page: half words can only be written once erased
+pub struct MemoryFlash {
+    data: [u8; PAGE_SIZE],
+    /// Number of page erases, to check the wear of a sequence of saves
+    pub erases: u32,
+    /// Fails every write like a worn out page
+    pub broken: bool,
+}
+
+impl Default for MemoryFlash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryFlash {
+    pub fn new() -> Self {
+        Self {
+            data: [0xff; PAGE_SIZE],
+            erases: 0,
+            broken: false,
+        }
+    }
+
+    /// Raw content, e.g. to corrupt a record
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl Flash for MemoryFlash {
+    fn size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.data[offset..offset + buf.len()]);
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        if self.broken || offset % 2 != 0 || data.len() % 2 != 0 || offset + data.len() > PAGE_SIZE
+        {
+            return Err(Error::Flash);
+        }
+        let target = &mut self.data[offset..offset + data.len()];
+        if target.iter().any(|byte| *byte != 0xff) {
+            return Err(Error::Flash);
+        }
+        target.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&mut self) -> Result<(), Error> {
+        self.data = [0xff; PAGE_SIZE];
+        self.erases += 1;
+        Ok(())
+    }
+}
+
+/// Backup registers, all zero like after the backup domain was reset
+#[derive(Default)]
+pub struct MemoryRegisters {
+    pub data: [u16; 10],
+}
+
+impl Registers for MemoryRegisters {
+    fn count(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read(&self, idx: usize) -> u16 {
+        self.data[idx]
+    }
+
+    fn write(&mut self, idx: usize, value: u16) {
+        self.data[idx] = value;
+    }
+}
@@ -0,0 +1,95 @@
+use crate::alarm::Alarms;
+use crate::clock::Clock;
+use crate::display::Display;
+use crate::health::Health;
+use crate::history::{History, Sample};
+use crate::input::{Button, Event, Gesture};
+use crate::settings::Settings;
+use core::fmt::Debug;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use heapless::{consts::*, String};
+use pomia_sensors::Capabilities;
+
+/// Everything the screens show and edit
+pub struct Context<'a> {
+    pub clock: &'a mut dyn Clock,
+    pub alarms: &'a mut Alarms,
+    pub history: &'a mut History,
+    pub settings: &'a mut Settings,
+    /// Latest measurement, `None` while the sensor fails
+    pub sample: Option<Sample>,
+    /// What the sensor of this board measures
+    pub capabilities: Capabilities,
+    pub health: Health,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Response {
+    Handled,
+    /// Leaves the event to the navigation, the arrows move between screens
+    Ignored,
+    /// Opens the n-th child screen registered under this one
+    Open(usize),
+}
+
+/// A view the `Gui` can navigate to
+pub trait Screen<D> {
+    fn title(&self) -> &'static str;
+
+    /// Draws the whole screen every frame, the display skips unchanged widgets
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context);
+
+    fn handle(&mut self, _event: Event, _ctx: &mut Context) -> Response {
+        Response::Ignored
+    }
+}
+
+/// Lists the child screens, enter selects the next entry and a long press
+/// opens it. Inside the submenu the arrows move between the children and
+/// lead back here past the first and the last one.
+pub struct Menu {
+    title: &'static str,
+    /// Labels of the children in the order they are registered
+    entries: &'static [&'static str],
+    selected: usize,
+}
+
+impl Menu {
+    pub const fn new(title: &'static str, entries: &'static [&'static str]) -> Self {
+        Self {
+            title,
+            entries,
+            selected: 0,
+        }
+    }
+}
+
+impl<D> Screen<D> for Menu
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn render(&mut self, display: &mut Display<D>, _ctx: &mut Context) {
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let mut line: String<U16> = String::new();
+            let _ = line.push_str(if idx == self.selected { ">" } else { " " });
+            let _ = line.push_str(entry);
+            display.print_text_sm(&display.pad(&line), 0, display.row_y(idx));
+        }
+    }
+
+    fn handle(&mut self, event: Event, _ctx: &mut Context) -> Response {
+        match (event.button, event.gesture) {
+            (Button::Enter, Gesture::Click) | (Button::Enter, Gesture::DoubleClick) => {
+                self.selected = (self.selected + 1) % self.entries.len().max(1);
+                Response::Handled
+            }
+            (Button::Enter, Gesture::LongPress) => Response::Open(self.selected),
+            _ => Response::Ignored,
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// Equal temperament tuning with A4 = 440 Hz. Frequencies of octave 8 in
+// centihertz, every octave below halves them.
+const OCTAVE_8: [u32; 12] = [
+    418_601, 443_492, 469_863, 497_803, 527_404, 558_765, 591_991, 627_193, 664_488, 704_000,
+    745_862, 790_213,
+];
+
+pub const MAX_OCTAVE: u8 = 8;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Pitch {
+    C,
+    CSharp,
+    D,
+    DSharp,
+    E,
+    F,
+    FSharp,
+    G,
+    GSharp,
+    A,
+    ASharp,
+    B,
+}
+
+impl Pitch {
+    const ALL: [Pitch; 12] = [
+        Pitch::C,
+        Pitch::CSharp,
+        Pitch::D,
+        Pitch::DSharp,
+        Pitch::E,
+        Pitch::F,
+        Pitch::FSharp,
+        Pitch::G,
+        Pitch::GSharp,
+        Pitch::A,
+        Pitch::ASharp,
+        Pitch::B,
+    ];
+
+    /// Pitch class of the given number of semitones above C, wrapping every octave
+    pub fn from_semitone(semitone: u8) -> Self {
+        Self::ALL[(semitone % 12) as usize]
+    }
+
+    pub fn semitone(self) -> u8 {
+        self as u8
+    }
+
+    /// Natural pitch for a note letter, `h` is the german name of `b`
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter.to_ascii_lowercase() {
+            'c' => Some(Pitch::C),
+            'd' => Some(Pitch::D),
+            'e' => Some(Pitch::E),
+            'f' => Some(Pitch::F),
+            'g' => Some(Pitch::G),
+            'a' => Some(Pitch::A),
+            'b' | 'h' => Some(Pitch::B),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error {
+    /// Not a note letter with an optional accidental and an octave of 0 to 8
+    Invalid,
+    /// The accidental moves the note below C0 or above B8
+    OutOfRange,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Note {
+    Tone { pitch: Pitch, octave: u8 },
+    Rest,
+}
+
+impl Note {
+    pub const fn new(pitch: Pitch, octave: u8) -> Self {
+        Note::Tone { pitch, octave }
+    }
+
+    /// Note `semitones` above C0, saturating at the top of octave 8
+    pub fn from_semitones(semitones: u16) -> Self {
+        let semitones = semitones.min(MAX_OCTAVE as u16 * 12 + 11);
+        Note::Tone {
+            pitch: Pitch::from_semitone((semitones % 12) as u8),
+            octave: (semitones / 12) as u8,
+        }
+    }
+
+    /// Position in semitones above C0, `None` for a rest
+    pub fn semitones(&self) -> Option<u16> {
+        match self {
+            Note::Tone { pitch, octave } => Some(*octave as u16 * 12 + pitch.semitone() as u16),
+            Note::Rest => None,
+        }
+    }
+
+    /// The note a semitone higher, B# moves into the next octave
+    pub fn sharp(self) -> Self {
+        self.transpose(1)
+    }
+
+    /// The note a semitone lower, Cb moves into the previous octave
+    pub fn flat(self) -> Self {
+        self.transpose(-1)
+    }
+
+    /// The note `semitones` away, saturating at C0 and B8
+    pub fn transpose(self, semitones: i16) -> Self {
+        match self.semitones() {
+            Some(current) => {
+                let moved = (current as i16 + semitones).max(0);
+                Note::from_semitones(moved as u16)
+            }
+            None => Note::Rest,
+        }
+    }
+
+    /// The note `semitones` away, `None` when that is below C0 or above B8
+    pub fn checked_transpose(self, semitones: i16) -> Option<Self> {
+        match self.semitones() {
+            Some(current) => {
+                let moved = current as i16 + semitones;
+                if (0..=MAX_OCTAVE as i16 * 12 + 11).contains(&moved) {
+                    Some(Note::from_semitones(moved as u16))
+                } else {
+                    None
+                }
+            }
+            None => Some(Note::Rest),
+        }
+    }
+
+    /// Frequency in Hz rounded to the nearest integer, 0 for a rest
+    pub fn frequency(&self) -> u32 {
+        match self {
+            Note::Tone { pitch, octave } => {
+                let shift = MAX_OCTAVE - (*octave).min(MAX_OCTAVE);
+                let centihertz = OCTAVE_8[pitch.semitone() as usize] >> shift;
+                (centihertz + 50) / 100
+            }
+            Note::Rest => 0,
+        }
+    }
+
+    /// Parses names like `A4`, `C#5`, `Eb3` or `R` for a rest
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        let mut chars = name.chars();
+        let letter = chars.next().ok_or(Error::Invalid)?;
+        if letter == 'r' || letter == 'R' {
+            return if chars.next().is_none() {
+                Ok(Note::Rest)
+            } else {
+                Err(Error::Invalid)
+            };
+        }
+        let pitch = Pitch::from_letter(letter).ok_or(Error::Invalid)?;
+
+        let rest = chars.as_str();
+        let (accidental, octave) = match rest.as_bytes().first() {
+            Some(b'#') => (1, &rest[1..]),
+            Some(b'b') => (-1, &rest[1..]),
+            _ => (0, rest),
+        };
+        let octave = octave
+            .parse::<u8>()
+            .ok()
+            .filter(|o| *o <= MAX_OCTAVE)
+            .ok_or(Error::Invalid)?;
+
+        Note::new(pitch, octave)
+            .checked_transpose(accidental)
+            .ok_or(Error::OutOfRange)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequencies_follow_equal_temperament() {
+        assert_eq!(Note::new(Pitch::A, 4).frequency(), 440);
+        assert_eq!(Note::new(Pitch::C, 4).frequency(), 262);
+        assert_eq!(Note::new(Pitch::FSharp, 5).frequency(), 740);
+        assert_eq!(Note::new(Pitch::A, 0).frequency(), 28);
+        assert_eq!(Note::new(Pitch::C, 0).frequency(), 16);
+        assert_eq!(Note::new(Pitch::C, 8).frequency(), 4186);
+        assert_eq!(Note::new(Pitch::B, 8).frequency(), 7902);
+        assert_eq!(Note::Rest.frequency(), 0);
+        for semitones in 12..=MAX_OCTAVE as u16 * 12 + 11 {
+            let note = Note::from_semitones(semitones);
+            let below = note.transpose(-12).frequency();
+            assert!((note.frequency() as i32 - 2 * below as i32).abs() <= 1);
+        }
+    }
+
+    #[test]
+    fn accidentals_cross_octaves() {
+        assert_eq!(Note::parse("A4"), Ok(Note::new(Pitch::A, 4)));
+        assert_eq!(Note::parse("c#5"), Ok(Note::new(Pitch::CSharp, 5)));
+        assert_eq!(Note::parse("Eb3"), Ok(Note::new(Pitch::DSharp, 3)));
+        assert_eq!(Note::parse("Cb4"), Ok(Note::new(Pitch::B, 3)));
+        assert_eq!(Note::parse("B#3"), Ok(Note::new(Pitch::C, 4)));
+        assert_eq!(Note::parse("H4"), Ok(Note::new(Pitch::B, 4)));
+        assert_eq!(Note::parse("r"), Ok(Note::Rest));
+    }
+
+    #[test]
+    fn notes_off_the_range_are_errors() {
+        assert_eq!(Note::parse("Cb0"), Err(Error::OutOfRange));
+        assert_eq!(Note::parse("B#8"), Err(Error::OutOfRange));
+        for name in ["", "A", "A9", "X4", "A#", "Rx", "C##4"].iter() {
+            assert_eq!(Note::parse(name), Err(Error::Invalid), "{}", name);
+        }
+        // Stepping stays saturating
+        assert_eq!(Note::new(Pitch::C, 0).flat(), Note::new(Pitch::C, 0));
+        assert_eq!(Note::new(Pitch::B, 8).sharp(), Note::new(Pitch::B, 8));
+    }
+}
@@ -0,0 +1,41 @@
+// Quarter turns of the screen. The panel changes its scan direction for
+// them, a draw target implementing `display::Rotate` reports the turned size.
+
+use embedded_graphics::prelude::*;
+
+/// Clockwise turns of the screen, a quarter turn at a time
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Rotation {
+    Portrait,
+    Landscape,
+    PortraitFlipped,
+    LandscapeFlipped,
+}
+
+/// Choices of the rotation setting in degrees
+pub const ROTATION_LABELS: [&str; 4] = ["0", "90", "180", "270"];
+
+impl Rotation {
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Rotation::Portrait),
+            1 => Some(Rotation::Landscape),
+            2 => Some(Rotation::PortraitFlipped),
+            3 => Some(Rotation::LandscapeFlipped),
+            _ => None,
+        }
+    }
+
+    fn is_landscape(self) -> bool {
+        matches!(self, Rotation::Landscape | Rotation::LandscapeFlipped)
+    }
+
+    /// Size of the turned screen on a panel of `native` size
+    pub fn size(self, native: Size) -> Size {
+        if self.is_landscape() {
+            Size::new(native.height, native.width)
+        } else {
+            native
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// Parser for the RTTTL (Nokia Ring Tone Text Transfer Language) format:
+//   name:d=4,o=5,b=125:8e6,8d6,f#,g#,8c#6,4p,2a.
+// Every note is [duration]<letter>[#][.][octave][.] and settings left out of
+// the header fall back to the values from the spec (d=4, o=6, b=63).
+
+use crate::note::{Note, Pitch};
+
+const DEFAULT_DURATION: u8 = 4;
+const DEFAULT_OCTAVE: u8 = 6;
+const DEFAULT_BPM: u16 = 63;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error {
+    /// The song is not made of the name, settings and notes sections
+    MissingSection,
+    /// Unknown key or out of range value in the settings section
+    InvalidSetting,
+    /// The note at the given index could not be parsed
+    InvalidNote(usize),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RtttlNote {
+    pub note: Note,
+    pub duration_ms: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rtttl<'a> {
+    pub name: &'a str,
+    pub duration: u8,
+    pub octave: u8,
+    pub bpm: u16,
+    notes: &'a str,
+    count: usize,
+}
+
+impl<'a> Rtttl<'a> {
+    /// Parses the header and validates every note of the song
+    pub fn parse(song: &'a str) -> Result<Self, Error> {
+        let mut sections = song.splitn(3, ':');
+        let name = sections.next().ok_or(Error::MissingSection)?.trim();
+        let settings = sections.next().ok_or(Error::MissingSection)?;
+        let notes = sections.next().ok_or(Error::MissingSection)?;
+
+        let mut rtttl = Self {
+            name,
+            duration: DEFAULT_DURATION,
+            octave: DEFAULT_OCTAVE,
+            bpm: DEFAULT_BPM,
+            notes,
+            count: 0,
+        };
+
+        for setting in settings.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut kv = setting.splitn(2, '=');
+            let key = kv.next().map(str::trim);
+            let value = kv
+                .next()
+                .and_then(|v| v.trim().parse::<u16>().ok())
+                .ok_or(Error::InvalidSetting)?;
+            match key {
+                Some("d") if is_valid_duration(value) => rtttl.duration = value as u8,
+                Some("o") if is_valid_octave(value) => rtttl.octave = value as u8,
+                Some("b") if value > 0 => rtttl.bpm = value,
+                _ => return Err(Error::InvalidSetting),
+            }
+        }
+
+        for (idx, token) in rtttl.tokens().enumerate() {
+            rtttl.parse_note(token).ok_or(Error::InvalidNote(idx))?;
+            rtttl.count += 1;
+        }
+
+        Ok(rtttl)
+    }
+
+    /// Number of notes and pauses in the song
+    pub fn note_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn notes(&self) -> Notes<'a> {
+        Notes {
+            rtttl: *self,
+            rest: self.notes,
+        }
+    }
+
+    fn tokens(&self) -> impl Iterator<Item = &'a str> {
+        self.notes
+            .split(',')
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+    }
+
+    fn parse_note(&self, token: &str) -> Option<RtttlNote> {
+        let bytes = token.as_bytes();
+        let mut pos = 0;
+
+        let mut duration = self.duration as u16;
+        let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits > 0 {
+            duration = token[..digits].parse().ok()?;
+            if !is_valid_duration(duration) {
+                return None;
+            }
+            pos = digits;
+        }
+
+        let letter = *bytes.get(pos)? as char;
+        let pitch = match letter {
+            'p' | 'P' => None,
+            _ => Some(Pitch::from_letter(letter)?),
+        };
+        pos += 1;
+
+        let mut sharp = false;
+        if bytes.get(pos) == Some(&b'#') && pitch.is_some() {
+            sharp = true;
+            pos += 1;
+        }
+
+        // The dot is allowed both before and after the octave
+        let mut dotted = false;
+        if bytes.get(pos) == Some(&b'.') {
+            dotted = true;
+            pos += 1;
+        }
+
+        let mut octave = self.octave;
+        if let Some(digit) = bytes.get(pos).filter(|b| b.is_ascii_digit()) {
+            octave = digit - b'0';
+            if !is_valid_octave(octave as u16) {
+                return None;
+            }
+            pos += 1;
+        }
+
+        if bytes.get(pos) == Some(&b'.') {
+            dotted = true;
+            pos += 1;
+        }
+
+        if pos != bytes.len() {
+            return None;
+        }
+
+        // A whole note lasts four beats
+        let mut duration_ms = 240_000 / (self.bpm as u32 * duration as u32);
+        if dotted {
+            duration_ms += duration_ms / 2;
+        }
+
+        let note = match pitch {
+            Some(pitch) if sharp => Note::new(pitch, octave).sharp(),
+            Some(pitch) => Note::new(pitch, octave),
+            None => Note::Rest,
+        };
+
+        Some(RtttlNote { note, duration_ms })
+    }
+}
+
+fn is_valid_duration(duration: u16) -> bool {
+    matches!(duration, 1 | 2 | 4 | 8 | 16 | 32)
+}
+
+fn is_valid_octave(octave: u16) -> bool {
+    (4..=7).contains(&octave)
+}
+
+#[derive(Clone)]
+pub struct Notes<'a> {
+    rtttl: Rtttl<'a>,
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Notes<'a> {
+    type Item = RtttlNote;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            let (token, rest) = match self.rest.find(',') {
+                Some(idx) => (&self.rest[..idx], &self.rest[idx + 1..]),
+                None => (self.rest, ""),
+            };
+            self.rest = rest;
+
+            let token = token.trim();
+            if !token.is_empty() {
+                // Notes were validated in `parse` so nothing gets skipped here
+                if let Some(note) = self.rtttl.parse_note(token) {
+                    return Some(note);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(pitch: Pitch, octave: u8, duration_ms: u32) -> RtttlNote {
+        RtttlNote {
+            note: Note::new(pitch, octave),
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn header_defaults_to_the_spec() {
+        let rtttl = Rtttl::parse(" Tune :: c").unwrap();
+        assert_eq!(rtttl.name, "Tune");
+        assert_eq!(rtttl.duration, DEFAULT_DURATION);
+        assert_eq!(rtttl.octave, DEFAULT_OCTAVE);
+        assert_eq!(rtttl.bpm, DEFAULT_BPM);
+        // 240 s divided by 63 beats per minute times a quarter
+        assert_eq!(rtttl.notes().next(), Some(note(Pitch::C, 6, 952)));
+    }
+
+    #[test]
+    fn notes_follow_the_settings() {
+        let rtttl = Rtttl::parse("t:d=4,o=5,b=120:8e6,f#,4p,2a.,c.6,16c#,, 32g4").unwrap();
+        let mut notes = rtttl.notes();
+        assert_eq!(notes.next(), Some(note(Pitch::E, 6, 250)));
+        assert_eq!(notes.next(), Some(note(Pitch::FSharp, 5, 500)));
+        assert_eq!(
+            notes.next(),
+            Some(RtttlNote {
+                note: Note::Rest,
+                duration_ms: 500
+            })
+        );
+        assert_eq!(notes.next(), Some(note(Pitch::A, 5, 1500)));
+        assert_eq!(notes.next(), Some(note(Pitch::C, 6, 750)));
+        assert_eq!(notes.next(), Some(note(Pitch::CSharp, 5, 125)));
+        assert_eq!(notes.next(), Some(note(Pitch::G, 4, 62)));
+        assert_eq!(notes.next(), None);
+    }
+
+    #[test]
+    fn sharp_b_moves_into_the_next_octave() {
+        let rtttl = Rtttl::parse("t:o=5:b#,b#6,e#").unwrap();
+        let mut notes = rtttl.notes().map(|n| n.note);
+        assert_eq!(notes.next(), Some(Note::new(Pitch::C, 6)));
+        assert_eq!(notes.next(), Some(Note::new(Pitch::C, 7)));
+        assert_eq!(notes.next(), Some(Note::new(Pitch::F, 5)));
+    }
+
+    #[test]
+    fn bad_songs_are_rejected() {
+        assert_eq!(Rtttl::parse("c,d,e").unwrap_err(), Error::MissingSection);
+        assert_eq!(Rtttl::parse("t:d=4").unwrap_err(), Error::MissingSection);
+        for song in [
+            "t:d=3:c", "t:o=9:c", "t:b=0:c", "t:x=1:c", "t:d=:c", "t:d4:c",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Rtttl::parse(song).unwrap_err(),
+                Error::InvalidSetting,
+                "{}",
+                song
+            );
+        }
+        assert_eq!(Rtttl::parse("t::c,x,d").unwrap_err(), Error::InvalidNote(1));
+        assert_eq!(
+            Rtttl::parse("t::c,d,9c").unwrap_err(),
+            Error::InvalidNote(2)
+        );
+        assert_eq!(Rtttl::parse("t::c3").unwrap_err(), Error::InvalidNote(0));
+        assert_eq!(Rtttl::parse("t::c55").unwrap_err(), Error::InvalidNote(0));
+        assert_eq!(Rtttl::parse("t::p#").unwrap_err(), Error::InvalidNote(0));
+    }
+}
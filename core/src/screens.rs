@@ -0,0 +1,917 @@
+use crate::alarm::{Alarm, Weekdays, ALARM_COUNT};
+use crate::backlight::LEVELS;
+use crate::clock::{days_in_month, DateTime, Time};
+use crate::comfort::Comfort;
+use crate::decimal::Decimal;
+use crate::display::{color_tag, Display, FontSize, HEADER_HEIGHT};
+use crate::editor::{Edit, Editor, Field};
+use crate::forecast;
+use crate::history::{Metric, Sample, DAY, HOUR};
+use crate::input::{Button, Event, Gesture};
+use crate::menu::{Context, Response, Screen};
+use crate::rotation::ROTATION_LABELS;
+use crate::segments::{SevenSegment, COLORS, COLOR_LABELS, SIZES, SIZE_LABELS};
+use crate::settings::Settings;
+use crate::theme::{Theme, THEME_LABELS};
+use core::fmt::Debug;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*, primitives::Rectangle};
+use heapless::{consts::*, String};
+use pomia_dial::{Hands, Segment};
+use pomia_sensors::Capabilities;
+use ufmt::uwrite;
+
+// Margins around the minimum below the chart
+const GRAPH_FOOTER_MARGIN: i32 = 6;
+// Four values, a blank line and the verdict
+const COMFORT_LINES: usize = 6;
+
+/// Name, unit and the divider bringing the value into that unit
+fn metric_label(metric: Metric) -> (&'static str, &'static str, f32) {
+    match metric {
+        Metric::Temperature => ("Temperature", "C", 1.0),
+        Metric::Humidity => ("Humidity", "%", 1.0),
+        Metric::Pressure => ("Pressure", "hPa", 100.0),
+    }
+}
+
+fn is_click(event: Event) -> bool {
+    event.button == Button::Enter && matches!(event.gesture, Gesture::Click | Gesture::DoubleClick)
+}
+
+fn is_long_press(event: Event) -> bool {
+    event.button == Button::Enter && event.gesture == Gesture::LongPress
+}
+
+pub struct MeasureScreen;
+
+impl<D> Screen<D> for MeasureScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        "Measurements"
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        let sample = match ctx.sample {
+            Some(sample) => sample,
+            None => return,
+        };
+        let mut text: String<U16> = String::new();
+        // Three measurements and three lines of forecast
+        const LINES: usize = 6;
+
+        if let Some(temperature) = sample.temperature {
+            let _ = uwrite!(text, "T: {} C", Decimal(temperature));
+            display.print_text_lg(&display.pad_lg(&text), 0, display.line_y(0, LINES));
+        }
+        if let Some(humidity) = sample.humidity {
+            text = String::new();
+            let _ = uwrite!(text, "H: {} %", Decimal(humidity));
+            display.print_text_lg(&display.pad_lg(&text), 0, display.line_y(1, LINES));
+        }
+        let pressure = match sample.pressure {
+            Some(pressure) => pressure,
+            None => return,
+        };
+        text = String::new();
+        let _ = uwrite!(text, "P:{} hPa", (pressure / 100.0) as u32);
+        display.print_text_lg(&display.pad_lg(&text), 0, display.line_y(2, LINES));
+
+        let weather = forecast::forecast(ctx.history, &sample, ctx.settings.altitude as f32);
+        let mut trend: String<U16> = String::new();
+        match weather {
+            Some(weather) => {
+                let _ = uwrite!(
+                    trend,
+                    "{} {}",
+                    Decimal(weather.pressure),
+                    weather.trend.as_str()
+                );
+                display.print_text_sm(&display.pad(&trend), 0, display.line_y(3, LINES));
+
+                let (first, second) = display.wrap(weather.forecast.text);
+                display.print_text_sm(&display.pad(first), 0, display.line_y(4, LINES));
+                display.print_text_sm(&display.pad(second), 0, display.line_y(5, LINES));
+            }
+            None => {
+                display.print_text_sm(&display.pad("No forecast yet"), 0, display.line_y(3, LINES))
+            }
+        }
+    }
+}
+
+// Fields of the clock editor
+const HOURS: usize = 0;
+const MINUTES: usize = 1;
+const SECONDS: usize = 2;
+const YEAR: usize = 3;
+const MONTH: usize = 4;
+const DAY_OF_MONTH: usize = 5;
+
+// Room around the clock face, the header takes the top of the screen
+const FACE_MARGIN: u32 = 8;
+
+fn clock_editor(datetime: &DateTime) -> Editor {
+    let time = &datetime.time;
+    Editor::new()
+        .field(Field::number(time.hours as i32, 0, 23, 2))
+        .field(Field::number(time.minutes as i32, 0, 59, 2).prefix(":"))
+        .field(Field::number(time.seconds as i32, 0, 59, 2).prefix(":"))
+        .field(Field::number(datetime.year as i32, 2000, 2099, 4).clamped())
+        .field(Field::number(datetime.month as i32, 1, 12, 2).prefix("-"))
+        .field(Field::number(datetime.day as i32, 1, 31, 2).prefix("-"))
+}
+
+pub struct ClockScreen {
+    editor: Option<Editor>,
+}
+
+impl Default for ClockScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockScreen {
+    pub fn new() -> Self {
+        Self { editor: None }
+    }
+}
+
+impl<D> Screen<D> for ClockScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        if self.editor.is_some() {
+            "Clock (Edit)"
+        } else {
+            "Clock"
+        }
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        match &self.editor {
+            Some(editor) => {
+                // The time above the date, a line apart
+                let (time, date) = (HOURS..YEAR, YEAR..DAY_OF_MONTH + 1);
+                let x = display.centered_x(editor.text(time.clone()).0.len(), FontSize::Large);
+                editor.render(display, time, FontSize::Large, x, display.row_y(0));
+                let x = display.centered_x(editor.text(date.clone()).0.len(), FontSize::Small);
+                editor.render(display, date, FontSize::Small, x, display.row_y(2));
+            }
+            None => {
+                let datetime = ctx.clock.get_datetime();
+                let time = &datetime.time;
+                let settings = &ctx.settings;
+                let size = display.size();
+                let color = face_color(display, settings);
+                let digits = SevenSegment::fit(size.width - 2 * FACE_MARGIN, color)
+                    .scaled(SIZES[settings.clock_size as usize % SIZES.len()]);
+
+                // The digits with the date underneath, centered below the header
+                let block = digits.height() + FACE_MARGIN + FontSize::Small.height();
+                let top = HEADER_HEIGHT + size.height.saturating_sub(HEADER_HEIGHT + block) / 2;
+                let x = size.width.saturating_sub(digits.time_width()) / 2;
+                // The colon blinks with the seconds
+                let colon = time.seconds % 2 == 0;
+                digits.draw_time(
+                    display,
+                    time.hours,
+                    time.minutes,
+                    colon,
+                    Point::new(x as i32, top as i32),
+                );
+
+                let date = datetime.date_str();
+                let x = display.centered_x(date.len(), FontSize::Small);
+                let y = top + digits.height() + FACE_MARGIN;
+                display.print_text_sm(&date, x, y as i32);
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Event, ctx: &mut Context) -> Response {
+        let editor = match &mut self.editor {
+            Some(editor) => editor,
+            None if is_long_press(event) => {
+                self.editor = Some(clock_editor(&ctx.clock.get_datetime()));
+                return Response::Handled;
+            }
+            None => return Response::Ignored,
+        };
+
+        match editor.handle(event) {
+            Edit::Editing => {
+                let days = days_in_month(editor.value(YEAR) as u16, editor.value(MONTH) as u8);
+                editor.set_max(DAY_OF_MONTH, days as i32);
+            }
+            Edit::Confirmed => {
+                let time = Time {
+                    hours: editor.value(HOURS) as u8,
+                    minutes: editor.value(MINUTES) as u8,
+                    seconds: editor.value(SECONDS) as u8,
+                };
+                // The editor limits keep the fields within a valid date
+                if let Some(datetime) = DateTime::new(
+                    editor.value(YEAR) as u16,
+                    editor.value(MONTH) as u8,
+                    editor.value(DAY_OF_MONTH) as u8,
+                    time,
+                ) {
+                    ctx.clock.set_datetime(&datetime);
+                }
+                self.editor = None;
+            }
+            Edit::Cancelled => self.editor = None,
+        }
+        Response::Handled
+    }
+}
+
+/// Colour of the clock faces. The night theme keeps them dim red as well.
+fn face_color<D>(display: &Display<D>, settings: &Settings) -> Rgb565
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    if *display.theme() == Theme::NIGHT {
+        display.theme().foreground
+    } else {
+        COLORS[settings.clock_color as usize % COLORS.len()]
+    }
+}
+
+// Room between the dial and the edge of the screen
+const DIAL_MARGIN: i32 = 4;
+
+fn point((x, y): (i32, i32)) -> Point {
+    Point::new(x, y)
+}
+
+fn draw_hands<D>(display: &mut Display<D>, hands: &Hands, color: Rgb565, second: Rgb565)
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    let mut draw = |hand: &Segment, color: Rgb565, width: u32| {
+        display.print_stroke(point(hand.start), point(hand.end), color, width)
+    };
+    draw(&hands.hour, color, 3);
+    draw(&hands.minute, color, 2);
+    draw(&hands.second, second, 1);
+}
+
+/// Analog dial. Only the hands are drawn again when they move, the old ones
+/// are painted over with the background so the dial does not flicker.
+pub struct AnalogScreen {
+    /// Hands currently on the screen
+    drawn: Option<Hands>,
+}
+
+impl Default for AnalogScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnalogScreen {
+    pub fn new() -> Self {
+        Self { drawn: None }
+    }
+}
+
+impl<D> Screen<D> for AnalogScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        "Analog clock"
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        let size = display.size();
+        let height = size.height.saturating_sub(HEADER_HEIGHT);
+        let radius = size.width.min(height) as i32 / 2 - DIAL_MARGIN;
+        let center = (size.width as i32 / 2, (HEADER_HEIGHT + height / 2) as i32);
+        let color = face_color(display, ctx.settings);
+
+        // The whole dial is one widget, the display leaves it alone as long
+        // as it stays on the screen
+        let area = Rectangle::new(
+            Point::new(center.0 - radius - 1, center.1 - radius - 1),
+            Size::new(2 * radius as u32 + 3, 2 * radius as u32 + 3),
+        );
+        if display.claim(area, color_tag(color)) {
+            display.fill_background(&area);
+            self.drawn = None;
+        }
+
+        let time = ctx.clock.get_time();
+        let hands = pomia_dial::hands(center, radius, time.hours, time.minutes, time.seconds);
+        if self.drawn == Some(hands) {
+            return;
+        }
+        if let Some(old) = &self.drawn {
+            let background = display.theme().background;
+            draw_hands(display, old, background, background);
+        }
+        // Erasing the hands may have taken pixels off the marks
+        for tick in pomia_dial::ticks(center, radius, radius / 10).iter() {
+            display.print_line(point(tick.start), point(tick.end), color);
+        }
+        let second = display.theme().accent;
+        draw_hands(display, &hands, color, second);
+        self.drawn = Some(hands);
+    }
+}
+
+// Fields of the alarm editor
+const ALARM_ENABLED: usize = 0;
+const ALARM_HOURS: usize = 1;
+const ALARM_MINUTES: usize = 2;
+const ALARM_REPEAT: usize = 3;
+
+const REPEATS: [Weekdays; 4] = [
+    Weekdays::ONCE,
+    Weekdays::WORKDAYS,
+    Weekdays::WEEKEND,
+    Weekdays::EVERY_DAY,
+];
+const REPEAT_LABELS: [&str; 5] = ["Once", "Mo-Fr", "Sa-Su", "Daily", "Custom"];
+// Only offered for an alarm whose days are not one of the presets
+const CUSTOM: usize = REPEATS.len();
+
+fn alarm_editor(alarm: &Alarm) -> Editor {
+    let repeat = REPEATS.iter().position(|r| *r == alarm.repeat);
+    let mut editor = Editor::new()
+        .field(Field::choice(alarm.enabled as usize, &["Off", "On"]))
+        .field(Field::number(alarm.hours as i32, 0, 23, 2).prefix(" "))
+        // Alarms are set in 5 minute steps, like on most alarm clocks
+        .field(
+            Field::number(alarm.minutes as i32, 0, 59, 2)
+                .step(5)
+                .prefix(":"),
+        )
+        .field(Field::choice(repeat.unwrap_or(CUSTOM), &REPEAT_LABELS).prefix(" "));
+    if repeat.is_some() {
+        editor.set_max(ALARM_REPEAT, CUSTOM as i32 - 1);
+    }
+    editor
+}
+
+pub struct AlarmsScreen {
+    selected: usize,
+    editor: Option<Editor>,
+}
+
+impl Default for AlarmsScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlarmsScreen {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            editor: None,
+        }
+    }
+}
+
+impl<D> Screen<D> for AlarmsScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        if self.editor.is_some() {
+            "Alarms (Edit)"
+        } else {
+            "Alarms"
+        }
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        for (idx, alarm) in ctx.alarms.iter().enumerate() {
+            let mut text: String<U16> = String::new();
+            let marker = if idx == self.selected { ">" } else { " " };
+            let _ = uwrite!(text, "{}{} ", marker, idx + 1);
+            let time = Time {
+                hours: alarm.hours,
+                minutes: alarm.minutes,
+                seconds: 0,
+            };
+            let mut hms: String<U8> = String::new();
+            let _ = uwrite!(hms, "{}", time);
+            // Drop the seconds, alarms only have minute resolution
+            let _ = text.push_str(&hms[..5]);
+            if alarm.enabled {
+                let _ = uwrite!(text, " {}", alarm.repeat);
+            } else {
+                let _ = text.push_str(" off   ");
+            }
+            // The alarms and the editor below them
+            let y = display.line_y(idx, ALARM_COUNT + 1);
+            display.print_text_sm(&text, 0, y);
+        }
+        if let Some(editor) = &self.editor {
+            let y = display.line_y(ALARM_COUNT, ALARM_COUNT + 1);
+            editor.render(
+                display,
+                ALARM_ENABLED..ALARM_REPEAT + 1,
+                FontSize::Small,
+                0,
+                y,
+            );
+        }
+    }
+
+    fn handle(&mut self, event: Event, ctx: &mut Context) -> Response {
+        let editor = match &mut self.editor {
+            Some(editor) => editor,
+            None if is_click(event) => {
+                self.selected = (self.selected + 1) % ALARM_COUNT;
+                return Response::Handled;
+            }
+            None if is_long_press(event) => {
+                self.editor = ctx.alarms.get(self.selected).map(alarm_editor);
+                return Response::Handled;
+            }
+            None => return Response::Ignored,
+        };
+
+        match editor.handle(event) {
+            Edit::Editing => {}
+            Edit::Confirmed => {
+                if let Some(alarm) = ctx.alarms.get(self.selected) {
+                    // Custom keeps the days the alarm already had
+                    let choice = editor.value(ALARM_REPEAT) as usize;
+                    let repeat = REPEATS.get(choice).copied().unwrap_or(alarm.repeat);
+                    let alarm = Alarm {
+                        enabled: editor.value(ALARM_ENABLED) != 0,
+                        hours: editor.value(ALARM_HOURS) as u8,
+                        minutes: editor.value(ALARM_MINUTES) as u8,
+                        repeat,
+                    };
+                    ctx.alarms.set(self.selected, alarm);
+                }
+                self.editor = None;
+            }
+            Edit::Cancelled => self.editor = None,
+        }
+        Response::Handled
+    }
+}
+
+pub struct HistoryScreen {
+    /// Seconds of history the statistics are computed over
+    window: u32,
+}
+
+impl Default for HistoryScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryScreen {
+    pub fn new() -> Self {
+        Self { window: DAY }
+    }
+}
+
+impl<D> Screen<D> for HistoryScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        if self.window == DAY {
+            "Statistics 24h"
+        } else {
+            "Statistics 1h"
+        }
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        let now = ctx.clock.timestamp();
+        // Two lines for each metric
+        let lines = 2 * Metric::ALL.len();
+        for (idx, metric) in Metric::ALL.iter().enumerate() {
+            if !metric.measured_by(&ctx.capabilities) {
+                continue;
+            }
+            let y = display.line_y(2 * idx, lines);
+            let (name, _, divider) = metric_label(*metric);
+            let label = &name[..1];
+            let mut range: String<U16> = String::new();
+            let mut avg: String<U16> = String::new();
+            match ctx.history.stats(*metric, now, self.window) {
+                Some(stats) => {
+                    let _ = uwrite!(
+                        range,
+                        "{} {}..{}",
+                        label,
+                        Decimal(stats.min / divider),
+                        Decimal(stats.max / divider)
+                    );
+                    let _ = uwrite!(avg, "  avg {}", Decimal(stats.avg / divider));
+                }
+                None => {
+                    let _ = uwrite!(range, "{} no data", label);
+                }
+            }
+            display.print_text_sm(&range, 0, y);
+            display.print_text_sm(&avg, 0, display.line_y(2 * idx + 1, lines));
+        }
+    }
+
+    fn handle(&mut self, event: Event, ctx: &mut Context) -> Response {
+        if is_click(event) {
+            self.window = if self.window == DAY { HOUR } else { DAY };
+        } else if is_long_press(event) {
+            ctx.history.reset();
+        } else {
+            return Response::Ignored;
+        }
+        Response::Handled
+    }
+}
+
+pub struct GraphScreen {
+    metric: Metric,
+}
+
+impl Default for GraphScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GraphScreen {
+    pub fn new() -> Self {
+        Self {
+            metric: Metric::Temperature,
+        }
+    }
+
+    /// First metric the sensor measures after `self.metric`, the current one
+    /// again if no other is
+    fn next_metric(&self, capabilities: &Capabilities) -> Metric {
+        let start = self.metric as usize;
+        (1..=Metric::ALL.len())
+            .map(|offset| Metric::ALL[(start + offset) % Metric::ALL.len()])
+            .find(|metric| metric.measured_by(capabilities))
+            .unwrap_or(self.metric)
+    }
+}
+
+impl<D> Screen<D> for GraphScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        "Graph"
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        // Starts on the temperature, which not every sensor measures
+        if !self.metric.measured_by(&ctx.capabilities) {
+            self.metric = self.next_metric(&ctx.capabilities);
+        }
+        let (name, unit, divider) = metric_label(self.metric);
+        let mut title: String<U16> = String::new();
+        let _ = uwrite!(title, "{} {}", name, unit);
+        display.print_text_sm(&title, 0, display.row_y(0));
+
+        let metric = self.metric;
+        let history = &*ctx.history;
+        let values = || {
+            history
+                .iter()
+                .filter_map(move |b| b.stats(metric))
+                .map(move |stats| stats.avg / divider)
+        };
+        let count = values().count();
+        if count == 0 {
+            display.print_text_sm("no data", 0, display.row_y(1));
+            return;
+        }
+
+        let mut min = values().fold(f32::MAX, f32::min);
+        let mut max = values().fold(f32::MIN, f32::max);
+        // Keep a flat line in the middle instead of dividing by zero
+        if max - min < 1.0 {
+            let mid = (max + min) / 2.0;
+            min = mid - 0.5;
+            max = mid + 0.5;
+        }
+
+        let mut label: String<U16> = String::new();
+        let _ = uwrite!(label, "{}     ", Decimal(max));
+        display.print_text_sm(&label, 0, display.row_y(1));
+        label = String::new();
+        let _ = uwrite!(label, "{}     ", Decimal(min));
+        let size = display.size();
+        // The chart fills the rows below the title and the maximum
+        let footer = FontSize::Small.height() as i32 + 2 * GRAPH_FOOTER_MARGIN;
+        let (top, bottom) = (display.row_y(2), size.height as i32 - footer);
+        let width = size.width as i32;
+        display.print_text_sm(&label, 0, bottom + GRAPH_FOOTER_MARGIN);
+
+        // The chart only changes as samples come in, redraw it once a minute
+        let plot = Rectangle::new(
+            Point::new(0, top),
+            Size::new(width as u32, (bottom - top) as u32 + 1),
+        );
+        let now = ctx.clock.timestamp();
+        let tag = (now / 60 * 4 + metric as u32) ^ ((count as u32) << 24);
+        if !display.claim(plot, tag) {
+            return;
+        }
+        display.fill_background(&plot);
+        let theme = *display.theme();
+        display.print_line(Point::new(0, top), Point::new(0, bottom), theme.accent);
+        display.print_line(
+            Point::new(0, bottom),
+            Point::new(width - 1, bottom),
+            theme.accent,
+        );
+
+        let height = (bottom - top - 2) as f32;
+        let step = (width - 3) as f32 / (count.max(2) - 1) as f32;
+        let mut previous: Option<Point> = None;
+        for (idx, value) in values().enumerate() {
+            let x = 2 + (idx as f32 * step) as i32;
+            let y = bottom - 1 - ((value - min) / (max - min) * height) as i32;
+            let point = Point::new(x, y);
+            display.print_line(previous.unwrap_or(point), point, theme.foreground);
+            previous = Some(point);
+        }
+    }
+
+    fn handle(&mut self, event: Event, ctx: &mut Context) -> Response {
+        if !is_click(event) {
+            return Response::Ignored;
+        }
+        self.metric = self.next_metric(&ctx.capabilities);
+        Response::Handled
+    }
+}
+
+pub struct ComfortScreen;
+
+impl<D> Screen<D> for ComfortScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        "Comfort"
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        if !ctx.capabilities.humidity {
+            display.print_text_sm(&display.pad("Needs humidity"), 0, display.row_y(0));
+            return;
+        }
+        let comfort = match ctx.sample {
+            Some(Sample {
+                temperature: Some(temperature),
+                humidity: Some(humidity),
+                ..
+            }) => Comfort::new(temperature, humidity),
+            _ => return,
+        };
+        let mut text: String<U16> = String::new();
+
+        let mut y = [0; COMFORT_LINES];
+        for (idx, row) in y.iter_mut().enumerate() {
+            *row = display.line_y(idx, COMFORT_LINES);
+        }
+        let _ = uwrite!(text, "Dew pt {} C", Decimal(comfort.dew_point));
+        display.print_text_sm(&display.pad(&text), 0, y[0]);
+        text = String::new();
+        let _ = uwrite!(text, "Heat ix {} C", Decimal(comfort.heat_index));
+        display.print_text_sm(&display.pad(&text), 0, y[1]);
+        text = String::new();
+        let _ = uwrite!(text, "Humidex {}", Decimal(comfort.humidex));
+        display.print_text_sm(&display.pad(&text), 0, y[2]);
+        text = String::new();
+        let _ = uwrite!(text, "Abs {} g/m3", Decimal(comfort.absolute_humidity));
+        display.print_text_sm(&display.pad(&text), 0, y[3]);
+
+        display.print_text_sm(&display.pad(comfort.level.as_str()), 0, y[5]);
+    }
+}
+
+// Fields of the display editor
+const BRIGHTNESS: usize = 0;
+const NIGHT_BRIGHTNESS: usize = 1;
+const NIGHT_START: usize = 2;
+const NIGHT_END: usize = 3;
+const CLOCK_SIZE: usize = 4;
+const CLOCK_COLOR: usize = 5;
+const THEME: usize = 6;
+const ROTATION: usize = 7;
+
+const DISPLAY_LABELS: [&str; 7] = [
+    "Light", "Dimmed", "Night", "Size", "Color", "Theme", "Rotate",
+];
+
+fn display_editor(settings: &Settings) -> Editor {
+    let levels = LEVELS as i32;
+    Editor::new()
+        .field(Field::number(settings.brightness as i32, 1, levels, 2).clamped())
+        .field(Field::number(settings.night_brightness as i32, 0, levels, 2).clamped())
+        .field(Field::number(settings.night_start as i32, 0, 23, 2))
+        .field(Field::number(settings.night_end as i32, 0, 23, 2).prefix("-"))
+        .field(Field::choice(settings.clock_size as usize, &SIZE_LABELS))
+        .field(Field::choice(settings.clock_color as usize, &COLOR_LABELS))
+        .field(Field::choice(settings.theme as usize, &THEME_LABELS))
+        .field(Field::choice(settings.rotation as usize, &ROTATION_LABELS))
+}
+
+/// Backlight levels, the hours it is dimmed at night, the clock face, theme
+/// and rotation
+pub struct DisplayScreen {
+    editor: Option<Editor>,
+    /// First row shown when they do not all fit the screen
+    first: usize,
+}
+
+impl Default for DisplayScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DisplayScreen {
+    pub fn new() -> Self {
+        Self {
+            editor: None,
+            first: 0,
+        }
+    }
+}
+
+impl<D> Screen<D> for DisplayScreen
+where
+    D: DrawTarget<Color = Rgb565>,
+    D::Error: Debug,
+{
+    fn title(&self) -> &'static str {
+        if self.editor.is_some() {
+            "Display (Edit)"
+        } else {
+            "Display"
+        }
+    }
+
+    fn render(&mut self, display: &mut Display<D>, ctx: &mut Context) {
+        let rows = [
+            BRIGHTNESS..NIGHT_BRIGHTNESS,
+            NIGHT_BRIGHTNESS..NIGHT_START,
+            NIGHT_START..NIGHT_END + 1,
+            CLOCK_SIZE..CLOCK_COLOR,
+            CLOCK_COLOR..THEME,
+            THEME..ROTATION,
+            ROTATION..ROTATION + 1,
+        ];
+        // Values line up at the right edge, the labels get the room left of them
+        let shown = display_editor(ctx.settings);
+        let widest = rows
+            .iter()
+            .map(|range| shown.text(range.clone()).0.len())
+            .max()
+            .unwrap_or(0);
+        let char_width = FontSize::Small.width() as i32;
+        let value_x = display.size().width as i32 - widest as i32 * char_width;
+        let label_chars = (value_x / char_width - 1).max(0) as usize;
+
+        // Rows that do not fit scroll, the edited one stays in view
+        let visible = rows.len().min(display.line_count()).max(1);
+        if let Some(editor) = &self.editor {
+            let selected = editor.selected();
+            if let Some(row) = rows.iter().position(|range| range.contains(&selected)) {
+                self.first = self.first.min(row).max((row + 1).saturating_sub(visible));
+            }
+        }
+        if self.first + visible > rows.len() {
+            self.first = 0;
+        }
+
+        for line in 0..visible {
+            let idx = self.first + line;
+            let (label, range) = (DISPLAY_LABELS[idx], &rows[idx]);
+            let y = display.line_y(line, visible);
+            display.print_text_sm(&label[..label.len().min(label_chars)], 0, y);
+            match &self.editor {
+                Some(editor) => editor.render(display, range.clone(), FontSize::Small, value_x, y),
+                None => display.print_text_sm(&shown.text(range.clone()).0, value_x, y),
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Event, ctx: &mut Context) -> Response {
+        let editor = match &mut self.editor {
+            Some(editor) => editor,
+            None if is_long_press(event) => {
+                self.editor = Some(display_editor(ctx.settings));
+                return Response::Handled;
+            }
+            // Past the last row it starts over at the top
+            None if is_click(event) => {
+                self.first += 1;
+                return Response::Handled;
+            }
+            None => return Response::Ignored,
+        };
+
+        match editor.handle(event) {
+            Edit::Editing => {}
+            Edit::Confirmed => {
+                let settings = &mut ctx.settings;
+                settings.brightness = editor.value(BRIGHTNESS) as u8;
+                settings.night_brightness = editor.value(NIGHT_BRIGHTNESS) as u8;
+                settings.night_start = editor.value(NIGHT_START) as u8;
+                settings.night_end = editor.value(NIGHT_END) as u8;
+                settings.clock_size = editor.value(CLOCK_SIZE) as u8;
+                settings.clock_color = editor.value(CLOCK_COLOR) as u8;
+                settings.theme = editor.value(THEME) as u8;
+                settings.rotation = editor.value(ROTATION) as u8;
+                self.editor = None;
+            }
+            Edit::Cancelled => self.editor = None,
+        }
+        Response::Handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repeat_label(editor: &Editor) -> &'static str {
+        REPEAT_LABELS[editor.value(ALARM_REPEAT) as usize]
+    }
+
+    #[test]
+    fn alarm_editor_keeps_custom_days() {
+        let custom = Alarm::new(6, 30, Weekdays::from_bits(0b0000_0101));
+        let mut editor = alarm_editor(&custom);
+        let (text, _) = editor.text(ALARM_ENABLED..ALARM_REPEAT + 1);
+        assert_eq!(text.as_str(), "On  06:30 Custom");
+
+        for _ in 0..ALARM_REPEAT {
+            editor.next_field();
+        }
+        editor.increment();
+        assert_eq!(repeat_label(&editor), "Once");
+        editor.decrement();
+        assert_eq!(repeat_label(&editor), "Custom");
+    }
+
+    #[test]
+    fn alarm_editor_offers_custom_only_for_custom_days() {
+        let mut editor = alarm_editor(&Alarm::new(6, 30, Weekdays::EVERY_DAY));
+        assert_eq!(repeat_label(&editor), "Daily");
+        for _ in 0..ALARM_REPEAT {
+            editor.next_field();
+        }
+        editor.increment();
+        assert_eq!(repeat_label(&editor), "Once");
+        editor.decrement();
+        assert_eq!(repeat_label(&editor), "Daily");
+    }
+
+    #[test]
+    fn graph_shows_measured_metrics_only() {
+        let capabilities = Capabilities {
+            temperature: false,
+            humidity: true,
+            pressure: true,
+        };
+        let mut graph = GraphScreen::new();
+        graph.metric = graph.next_metric(&capabilities);
+        assert_eq!(graph.metric, Metric::Humidity);
+        graph.metric = graph.next_metric(&capabilities);
+        assert_eq!(graph.metric, Metric::Pressure);
+        graph.metric = graph.next_metric(&capabilities);
+        assert_eq!(graph.metric, Metric::Humidity);
+
+        let only_temperature = Capabilities {
+            temperature: true,
+            humidity: false,
+            pressure: false,
+        };
+        assert_eq!(graph.next_metric(&only_temperature), Metric::Temperature);
+    }
+}
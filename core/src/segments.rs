@@ -0,0 +1,275 @@
+// Seven-segment digits drawn from filled rectangles, unlike the fonts they
+// scale to any size. Segments a to f go clockwise from the top, g is the
+// middle one. The corners stay dark, which gives the segmented look.
+
+use crate::display::{color_tag, Display};
+use core::fmt::Debug;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*, primitives::Rectangle};
+
+/// Percent of the largest digits fitting the screen
+pub const SIZES: [u32; 3] = [50, 75, 100];
+pub const SIZE_LABELS: [&str; 3] = ["Small", "Mid", "Large"];
+pub const COLORS: [Rgb565; 5] = [
+    Rgb565::RED,
+    Rgb565::new(31, 40, 0),
+    Rgb565::GREEN,
+    Rgb565::CYAN,
+    Rgb565::WHITE,
+];
+pub const COLOR_LABELS: [&str; 5] = ["Red", "Amber", "Green", "Cyan", "White"];
+
+// Lit segments of 0 to 9, bit 0 is segment a
+const DIGITS: [u8; 10] = [
+    0b011_1111, 0b000_0110, 0b101_1011, 0b100_1111, 0b110_0110, 0b110_1101, 0b111_1101, 0b000_0111,
+    0b111_1111, 0b110_1111,
+];
+
+/// Digits of a given height, the width and stroke follow from it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SevenSegment {
+    height: u32,
+    color: Rgb565,
+}
+
+impl SevenSegment {
+    pub fn new(height: u32, color: Rgb565) -> Self {
+        Self {
+            height: height.max(10),
+            color,
+        }
+    }
+
+    /// Tallest digits for which `HH:MM` fits `width`
+    pub fn fit(width: u32, color: Rgb565) -> Self {
+        // Four digits of half the height, four gaps and a colon of a tenth
+        Self::new(width * 2 / 5, color)
+    }
+
+    /// Scales the height by `percent`
+    pub fn scaled(self, percent: u32) -> Self {
+        Self::new(self.height * percent / 100, self.color)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn width(&self) -> u32 {
+        self.height / 2
+    }
+
+    fn thickness(&self) -> u32 {
+        (self.height / 10).max(2)
+    }
+
+    /// Width of `HH:MM`
+    pub fn time_width(&self) -> u32 {
+        4 * self.width() + 5 * self.thickness()
+    }
+
+    /// Rectangles of segments a to g for a digit at `origin`
+    pub fn segments(&self, origin: Point) -> [Rectangle; 7] {
+        let (w, h, t) = (
+            self.width() as i32,
+            self.height as i32,
+            self.thickness() as i32,
+        );
+        // The upper half gets the odd pixel
+        let lower = (h - 3 * t) / 2;
+        let upper = h - 3 * t - lower;
+        let rect = |x: i32, y: i32, width: i32, height: i32| {
+            Rectangle::new(
+                origin + Point::new(x, y),
+                Size::new(width.max(1) as u32, height.max(1) as u32),
+            )
+        };
+        [
+            rect(t, 0, w - 2 * t, t),
+            rect(w - t, t, t, upper),
+            rect(w - t, 2 * t + upper, t, lower),
+            rect(t, h - t, w - 2 * t, t),
+            rect(0, 2 * t + upper, t, lower),
+            rect(0, t, t, upper),
+            rect(t, t + upper, w - 2 * t, t),
+        ]
+    }
+
+    fn tag(&self, symbol: u32) -> u32 {
+        (color_tag(self.color) << 8) | symbol
+    }
+
+    /// Draws a digit from 0 to 9 at `origin`, other values leave it blank
+    pub fn draw_digit<D>(&self, display: &mut Display<D>, digit: u8, origin: Point)
+    where
+        D: DrawTarget<Color = Rgb565>,
+        D::Error: Debug,
+    {
+        let area = Rectangle::new(origin, Size::new(self.width(), self.height));
+        if !display.claim(area, self.tag(digit as u32)) {
+            return;
+        }
+        display.fill_background(&area);
+        let lit = DIGITS.get(digit as usize).copied().unwrap_or(0);
+        for (idx, segment) in self.segments(origin).iter().enumerate() {
+            if lit & (1 << idx) != 0 {
+                display.fill(segment, self.color);
+            }
+        }
+    }
+
+    /// Draws the two dots of a colon, blank while not `visible`
+    pub fn draw_colon<D>(&self, display: &mut Display<D>, visible: bool, origin: Point)
+    where
+        D: DrawTarget<Color = Rgb565>,
+        D::Error: Debug,
+    {
+        let t = self.thickness();
+        let area = Rectangle::new(origin, Size::new(t, self.height));
+        if !display.claim(area, self.tag(visible as u32)) {
+            return;
+        }
+        display.fill_background(&area);
+        if visible {
+            let dot = Size::new(t, t);
+            let (upper, lower) = (self.height as i32 / 3, self.height as i32 * 2 / 3);
+            let center = t as i32 / 2;
+            display.fill(
+                &Rectangle::new(origin + Point::new(0, upper - center), dot),
+                self.color,
+            );
+            display.fill(
+                &Rectangle::new(origin + Point::new(0, lower - center), dot),
+                self.color,
+            );
+        }
+    }
+
+    /// Draws `HH:MM` with `origin` at the top left
+    pub fn draw_time<D>(
+        &self,
+        display: &mut Display<D>,
+        hours: u8,
+        minutes: u8,
+        colon: bool,
+        origin: Point,
+    ) where
+        D: DrawTarget<Color = Rgb565>,
+        D::Error: Debug,
+    {
+        let step = (self.width() + self.thickness()) as i32;
+        let mut x = origin.x;
+        for digit in [hours / 10, hours % 10].iter() {
+            self.draw_digit(display, *digit, Point::new(x, origin.y));
+            x += step;
+        }
+        self.draw_colon(display, colon, Point::new(x, origin.y));
+        x += 2 * self.thickness() as i32;
+        for digit in [minutes / 10, minutes % 10].iter() {
+            self.draw_digit(display, *digit, Point::new(x, origin.y));
+            x += step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::{consts::*, String};
+    use pomia_framebuffer::FrameBuffer;
+
+    const COLOR: Rgb565 = Rgb565::GREEN;
+
+    fn segment_letters(digit: u8) -> String<U8> {
+        let mut letters = String::new();
+        for (idx, letter) in "abcdefg".chars().enumerate() {
+            if DIGITS[digit as usize] & (1 << idx) != 0 {
+                letters.push(letter).unwrap();
+            }
+        }
+        letters
+    }
+
+    fn overlap(a: &Rectangle, b: &Rectangle) -> bool {
+        let (a_end, b_end) = (a.top_left + a.size, b.top_left + b.size);
+        a.top_left.x < b_end.x
+            && b.top_left.x < a_end.x
+            && a.top_left.y < b_end.y
+            && b.top_left.y < a_end.y
+    }
+
+    #[test]
+    fn glyph_table() {
+        let expected = [
+            "abcdef", "bc", "abdeg", "abcdg", "bcfg", "acdfg", "acdefg", "abc", "abcdefg", "abcdfg",
+        ];
+        for (digit, segments) in expected.iter().enumerate() {
+            assert_eq!(segment_letters(digit as u8).as_str(), *segments);
+        }
+    }
+
+    #[test]
+    fn segments_stay_apart_at_every_size() {
+        for height in 10..=160 {
+            let digit = SevenSegment::new(height, COLOR);
+            let origin = Point::new(3, 5);
+            let bounds = Rectangle::new(origin, Size::new(digit.width(), height));
+            let segments = digit.segments(origin);
+            for (idx, segment) in segments.iter().enumerate() {
+                assert!(bounds.contains(segment.top_left), "{} {}", height, idx);
+                assert!(
+                    bounds.contains(segment.top_left + segment.size - Size::new(1, 1)),
+                    "{} {}",
+                    height,
+                    idx
+                );
+                for other in &segments[idx + 1..] {
+                    assert!(!overlap(segment, other), "{} {}", height, idx);
+                }
+            }
+            // The corners stay dark
+            let corner = bounds.top_left + Size::new(digit.width() - 1, height - 1);
+            assert!(segments.iter().all(|s| !s.contains(origin)), "{}", height);
+            assert!(segments.iter().all(|s| !s.contains(corner)), "{}", height);
+        }
+    }
+
+    #[test]
+    fn scaling() {
+        let digit = SevenSegment::new(80, COLOR);
+        assert_eq!(digit.scaled(50).height(), 40);
+        assert_eq!(digit.scaled(100), digit);
+        // Never smaller than something drawable
+        assert_eq!(digit.scaled(1).height(), 10);
+        assert_eq!(SevenSegment::new(10, COLOR).thickness(), 2);
+        assert_eq!(SevenSegment::new(80, COLOR).thickness(), 8);
+
+        // The largest size fits the screen across in either orientation
+        for width in [128, 160, 320].iter() {
+            for percent in SIZES.iter() {
+                let digits = SevenSegment::fit(*width, COLOR).scaled(*percent);
+                assert!(digits.time_width() <= *width, "{} {}", width, percent);
+            }
+        }
+    }
+
+    #[test]
+    fn draws_the_lit_segments() {
+        let digit = SevenSegment::new(40, COLOR);
+        let (width, height) = (digit.width(), digit.height());
+        for value in 0..10 {
+            let mut display = Display::new(FrameBuffer::new(width, height));
+            digit.draw_digit(&mut display, value, Point::zero());
+            let frame = display.release();
+
+            let lit: u32 = digit
+                .segments(Point::zero())
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| DIGITS[value as usize] & (1 << idx) != 0)
+                .map(|(_, segment)| segment.size.width * segment.size.height)
+                .sum();
+            let drawn = frame.count(Point::zero(), width, height, COLOR);
+            assert_eq!(drawn as u32, lit, "{}", value);
+        }
+    }
+}
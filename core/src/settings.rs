@@ -0,0 +1,763 @@
+// Settings surviving a reset. Small values that change often live in the
+// backup registers, which are cheap to write but lost with the backup battery.
+// The alarms and the display options live in a reserved flash page written as
+// a log of records, so the page is only erased once it is full. Both are
+// stored as a frame of layout version, length, payload and CRC, anything that
+// does not check out is replaced by defaults.
+//
+// Layouts only append fields. An older record decodes the fields it has and
+// the newer ones keep their defaults, a record from a newer firmware is
+// rejected as its layout is unknown. The display options were moved from the
+// registers to flash in version 8, the registers were full.
+
+use crate::alarm::{Alarm, Alarms, Weekdays, ALARM_COUNT};
+use crate::backlight::LEVELS;
+use crate::rotation::ROTATION_LABELS;
+use crate::segments::{COLORS, SIZES};
+use crate::telemetry::Format;
+use crate::theme::{self, Theme, THEME_LABELS};
+use core::convert::TryFrom;
+use heapless::{consts::*, Vec};
+use pomia_crc::crc16;
+
+/// Layout version written by this firmware
+pub const VERSION: u8 = 8;
+
+// Marks the start of a record in flash, erased flash reads as 0xffff
+const MAGIC: u16 = 0x5e77;
+const ERASED: u16 = 0xffff;
+// Magic, version and length in front of the payload, CRC after it
+const HEADER_LEN: usize = 4;
+const CRC_LEN: usize = 2;
+const MAX_PAYLOAD: usize = 32;
+
+type Payload = Vec<u8, U32>;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error {
+    Flash,
+    /// The payload does not fit the storage
+    TooLarge,
+}
+
+impl Error {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Error::Flash => "flash write failed",
+            Error::TooLarge => "too large for the storage",
+        }
+    }
+}
+
+/// The reserved flash page, offsets are relative to its start. Writes are
+/// made of whole half words.
+pub trait Flash {
+    fn size(&self) -> usize;
+    fn read(&mut self, offset: usize, buf: &mut [u8]);
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Error>;
+    fn erase(&mut self) -> Result<(), Error>;
+}
+
+/// 16 bit backup registers
+pub trait Registers {
+    fn count(&self) -> usize;
+    fn read(&self, idx: usize) -> u16;
+    fn write(&mut self, idx: usize, value: u16);
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Settings {
+    /// Height of the station above sea level in metres, used for the forecast
+    pub altitude: i16,
+    pub alarms: [Alarm; ALARM_COUNT],
+    pub telemetry: Format,
+    /// Seconds between telemetry records
+    pub telemetry_interval: u16,
+    /// Seconds without a button press before the display sleeps, 0 keeps it on
+    pub display_timeout: u16,
+    /// Backlight level during the day, 1 to `LEVELS`
+    pub brightness: u8,
+    /// Backlight level at night, 0 switches it off
+    pub night_brightness: u8,
+    /// Hours the night starts and ends at, equal hours mean no night
+    pub night_start: u8,
+    pub night_end: u8,
+    /// Index into `segments::SIZES` for the digits of the clock face
+    pub clock_size: u8,
+    /// Index into `segments::COLORS`
+    pub clock_color: u8,
+    /// Index into `theme::THEME_LABELS`
+    pub theme: u8,
+    /// Index into `rotation::ROTATION_LABELS`
+    pub rotation: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let defaults = Alarms::new();
+        let mut alarms = [Alarm::disabled(); ALARM_COUNT];
+        for (alarm, default) in alarms.iter_mut().zip(defaults.iter()) {
+            *alarm = *default;
+        }
+        Self {
+            altitude: 100,
+            alarms,
+            telemetry: Format::Off,
+            telemetry_interval: 10,
+            display_timeout: 60,
+            brightness: 8,
+            night_brightness: 1,
+            night_start: 22,
+            night_end: 7,
+            clock_size: 2,
+            clock_color: 0,
+            theme: 0,
+            rotation: 0,
+        }
+    }
+}
+
+/// Names of the settings that can be read and changed by name
+pub const KEYS: [&str; 12] = [
+    "altitude",
+    "telemetry",
+    "telemetry_interval",
+    "display_timeout",
+    "brightness",
+    "night_brightness",
+    "night_start",
+    "night_end",
+    "clock_size",
+    "clock_color",
+    "theme",
+    "rotation",
+];
+
+impl Settings {
+    pub fn get(&self, key: &str) -> Option<i32> {
+        match key {
+            "altitude" => Some(self.altitude as i32),
+            "telemetry" => Some(self.telemetry.index() as i32),
+            "telemetry_interval" => Some(self.telemetry_interval as i32),
+            "display_timeout" => Some(self.display_timeout as i32),
+            "brightness" => Some(self.brightness as i32),
+            "night_brightness" => Some(self.night_brightness as i32),
+            "night_start" => Some(self.night_start as i32),
+            "night_end" => Some(self.night_end as i32),
+            "clock_size" => Some(self.clock_size as i32),
+            "clock_color" => Some(self.clock_color as i32),
+            "theme" => Some(self.theme as i32),
+            "rotation" => Some(self.rotation as i32),
+            _ => None,
+        }
+    }
+
+    /// Returns false for unknown keys and values out of range
+    pub fn set(&mut self, key: &str, value: i32) -> bool {
+        match key {
+            // Dead Sea shore up to the highest weather stations
+            "altitude" if (-500..=9000).contains(&value) => self.altitude = value as i16,
+            "telemetry" => match u8::try_from(value).ok().and_then(Format::from_index) {
+                Some(format) => self.telemetry = format,
+                _ => return false,
+            },
+            "telemetry_interval" if (1..=3600).contains(&value) => {
+                self.telemetry_interval = value as u16
+            }
+            "display_timeout" if (0..=3600).contains(&value) => self.display_timeout = value as u16,
+            "brightness" if (1..=LEVELS as i32).contains(&value) => self.brightness = value as u8,
+            "night_brightness" if (0..=LEVELS as i32).contains(&value) => {
+                self.night_brightness = value as u8
+            }
+            "night_start" if (0..=23).contains(&value) => self.night_start = value as u8,
+            "night_end" if (0..=23).contains(&value) => self.night_end = value as u8,
+            "clock_size" if (0..SIZES.len() as i32).contains(&value) => {
+                self.clock_size = value as u8
+            }
+            "clock_color" if (0..COLORS.len() as i32).contains(&value) => {
+                self.clock_color = value as u8
+            }
+            "theme" if (0..THEME_LABELS.len() as i32).contains(&value) => self.theme = value as u8,
+            "rotation" if (0..ROTATION_LABELS.len() as i32).contains(&value) => {
+                self.rotation = value as u8
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn is_night(&self, hour: u8) -> bool {
+        let (start, end) = (self.night_start, self.night_end);
+        if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+
+    /// Backlight level for the hour of the day
+    pub fn brightness_at(&self, hour: u8) -> u8 {
+        if self.is_night(hour) {
+            self.night_brightness
+        } else {
+            self.brightness
+        }
+    }
+
+    /// Theme for the hour of the day
+    pub fn theme_at(&self, hour: u8) -> Theme {
+        theme::select(self.theme, self.is_night(hour))
+    }
+
+    /// Part kept in the backup registers
+    fn encode_registers(&self) -> Payload {
+        let mut payload = Payload::new();
+        let _ = payload.extend_from_slice(&self.altitude.to_le_bytes());
+        let _ = payload.push(self.telemetry.index());
+        let _ = payload.extend_from_slice(&self.telemetry_interval.to_le_bytes());
+        let _ = payload.extend_from_slice(&self.display_timeout.to_le_bytes());
+        let _ = payload.extend_from_slice(&[
+            self.brightness,
+            self.night_brightness,
+            self.night_start,
+            self.night_end,
+        ]);
+        payload
+    }
+
+    fn decode_registers(&mut self, version: u8, payload: &[u8]) {
+        let mut reader = Reader(payload);
+        if let Some(altitude) = reader.i16() {
+            self.altitude = altitude;
+        }
+        // Added in version 2
+        if let Some(format) = reader.u8().and_then(Format::from_index) {
+            self.telemetry = format;
+        }
+        if let Some(interval) = reader.u16().filter(|&interval| interval > 0) {
+            self.telemetry_interval = interval;
+        }
+        // Added in version 3
+        if let Some(timeout) = reader.u16().filter(|&timeout| timeout <= 3600) {
+            self.display_timeout = timeout;
+        }
+        // Added in version 4
+        if let Some(level) = reader.u8().filter(|level| (1..=LEVELS).contains(level)) {
+            self.brightness = level;
+        }
+        if let Some(level) = reader.u8().filter(|&level| level <= LEVELS) {
+            self.night_brightness = level;
+        }
+        if let Some(hour) = reader.u8().filter(|&hour| hour < 24) {
+            self.night_start = hour;
+        }
+        if let Some(hour) = reader.u8().filter(|&hour| hour < 24) {
+            self.night_end = hour;
+        }
+        // Added in version 5 to 7, in flash since version 8
+        if version < 8 {
+            self.decode_display(&mut reader);
+        }
+    }
+
+    fn encode_display(&self, payload: &mut Payload) {
+        let _ = payload.extend_from_slice(&[
+            self.clock_size,
+            self.clock_color,
+            self.theme,
+            self.rotation,
+        ]);
+    }
+
+    fn decode_display(&mut self, reader: &mut Reader<'_>) {
+        if let Some(size) = reader.u8().filter(|&size| (size as usize) < SIZES.len()) {
+            self.clock_size = size;
+        }
+        if let Some(color) = reader.u8().filter(|&color| (color as usize) < COLORS.len()) {
+            self.clock_color = color;
+        }
+        if let Some(choice) = reader
+            .u8()
+            .filter(|&choice| (choice as usize) < THEME_LABELS.len())
+        {
+            self.theme = choice;
+        }
+        if let Some(choice) = reader
+            .u8()
+            .filter(|&choice| (choice as usize) < ROTATION_LABELS.len())
+        {
+            self.rotation = choice;
+        }
+    }
+
+    /// Part kept in flash
+    fn encode_flash(&self) -> Payload {
+        let mut payload = Payload::new();
+        for alarm in self.alarms.iter() {
+            let _ = payload.extend_from_slice(&[
+                alarm.enabled as u8,
+                alarm.hours,
+                alarm.minutes,
+                alarm.repeat.bits(),
+            ]);
+        }
+        self.encode_display(&mut payload);
+        payload
+    }
+
+    fn decode_flash(&mut self, _version: u8, payload: &[u8]) {
+        let mut reader = Reader(payload);
+        for alarm in self.alarms.iter_mut() {
+            let fields = (reader.u8(), reader.u8(), reader.u8(), reader.u8());
+            if let (Some(enabled), Some(hours), Some(minutes), Some(repeat)) = fields {
+                // A value out of range means the layout is not what we expect
+                if enabled > 1 || hours > 23 || minutes > 59 {
+                    continue;
+                }
+                *alarm = Alarm {
+                    enabled: enabled == 1,
+                    hours,
+                    minutes,
+                    repeat: Weekdays::from_bits(repeat),
+                };
+            }
+        }
+        // Added in version 8
+        self.decode_display(&mut reader);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn u8(&mut self) -> Option<u8> {
+        let (first, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*first)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        Some(i16::from_le_bytes([self.u8()?, self.u8()?]))
+    }
+}
+
+fn frame_crc(version: u8, payload: &[u8]) -> u16 {
+    let mut data: Vec<u8, U64> = Vec::new();
+    let _ = data.extend_from_slice(&[version, payload.len() as u8]);
+    let _ = data.extend_from_slice(payload);
+    crc16(&data)
+}
+
+fn accepted(version: u8) -> bool {
+    version != 0 && version <= VERSION
+}
+
+/// Loads and saves the settings, remembering where the flash log ends
+pub struct SettingsStore<F, R> {
+    flash: F,
+    registers: R,
+    /// Offset the next flash record is written at
+    free: usize,
+    /// Payloads last written, unchanged parts are not written again. A failed
+    /// write is not retried either until the part changes, so a broken page
+    /// is not erased over and over.
+    saved_flash: Option<Payload>,
+    saved_registers: Option<Payload>,
+    flash_error: Option<Error>,
+    registers_error: Option<Error>,
+}
+
+impl<F, R> SettingsStore<F, R>
+where
+    F: Flash,
+    R: Registers,
+{
+    pub fn new(flash: F, registers: R) -> Self {
+        Self {
+            flash,
+            registers,
+            free: 0,
+            saved_flash: None,
+            saved_registers: None,
+            flash_error: None,
+            registers_error: None,
+        }
+    }
+
+    pub fn release(self) -> (F, R) {
+        (self.flash, self.registers)
+    }
+
+    /// Reads the stored settings, parts that are missing or corrupt get defaults
+    pub fn load(&mut self) -> Settings {
+        let mut settings = Settings::default();
+        if let Some((version, payload)) = self.read_registers() {
+            settings.decode_registers(version, &payload);
+            self.saved_registers = Some(payload);
+        }
+        if let Some((version, payload)) = self.read_flash() {
+            settings.decode_flash(version, &payload);
+            self.saved_flash = Some(payload);
+        }
+        settings
+    }
+
+    /// Writes the parts that changed since they were last loaded or saved.
+    /// Fails as long as the last write of either part failed.
+    pub fn save(&mut self, settings: &Settings) -> Result<(), Error> {
+        // Flash first, it takes over fields from the registers on upgrades
+        let payload = settings.encode_flash();
+        if self.saved_flash.as_ref() != Some(&payload) {
+            self.flash_error = self.write_flash(&payload).err();
+            self.saved_flash = Some(payload);
+        }
+
+        let payload = settings.encode_registers();
+        if self.saved_registers.as_ref() != Some(&payload) {
+            self.registers_error = self.write_registers(&payload).err();
+            self.saved_registers = Some(payload);
+        }
+
+        match self.flash_error.or(self.registers_error) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    // Register 0 holds the version and length, the payload follows two bytes
+    // per register and the CRC takes the register after it
+    fn read_registers(&self) -> Option<(u8, Payload)> {
+        let header = self.registers.read(0);
+        let (version, len) = ((header >> 8) as u8, (header & 0xff) as usize);
+        let words = (len + 1) / 2;
+        if !accepted(version) || 2 + words > self.registers.count() {
+            return None;
+        }
+
+        let mut payload = Payload::new();
+        for idx in 0..words {
+            let bytes = self.registers.read(1 + idx).to_le_bytes();
+            // The last register of an odd length payload holds a single byte
+            let _ = payload.extend_from_slice(&bytes[..(len - idx * 2).min(2)]);
+        }
+        if self.registers.read(1 + words) != frame_crc(version, &payload) {
+            return None;
+        }
+        Some((version, payload))
+    }
+
+    fn write_registers(&mut self, payload: &[u8]) -> Result<(), Error> {
+        let words = (payload.len() + 1) / 2;
+        if 2 + words > self.registers.count() {
+            return Err(Error::TooLarge);
+        }
+
+        // Invalidate first, a reset halfway leaves no half written frame
+        self.registers.write(0, 0);
+        for (idx, chunk) in payload.chunks(2).enumerate() {
+            let low = chunk[0] as u16;
+            let high = chunk.get(1).copied().unwrap_or(0) as u16;
+            self.registers.write(1 + idx, low | (high << 8));
+        }
+        self.registers.write(1 + words, frame_crc(VERSION, payload));
+        self.registers
+            .write(0, ((VERSION as u16) << 8) | payload.len() as u16);
+        Ok(())
+    }
+
+    /// Scans the log for the last valid record and where free space begins
+    fn read_flash(&mut self) -> Option<(u8, Payload)> {
+        let mut offset = 0;
+        let mut latest = None;
+        while offset + HEADER_LEN <= self.flash.size() {
+            let mut header = [0u8; HEADER_LEN];
+            self.flash.read(offset, &mut header);
+            let magic = u16::from_le_bytes([header[0], header[1]]);
+            if magic == ERASED {
+                break;
+            }
+            if magic != MAGIC {
+                // Garbage, the next save starts over on an erased page
+                offset = self.flash.size();
+                break;
+            }
+
+            let (version, len) = (header[2], header[3] as usize);
+            let size = record_size(len);
+            if len > MAX_PAYLOAD || offset + size > self.flash.size() {
+                offset = self.flash.size();
+                break;
+            }
+            let mut data = [0u8; MAX_PAYLOAD + CRC_LEN];
+            self.flash
+                .read(offset + HEADER_LEN, &mut data[..size - HEADER_LEN]);
+            let payload = &data[..len];
+            let crc_at = size - HEADER_LEN - CRC_LEN;
+            let crc = u16::from_le_bytes([data[crc_at], data[crc_at + 1]]);
+            // A record torn by a reset is skipped, the one before it stays valid
+            if accepted(version) && crc == frame_crc(version, payload) {
+                let mut valid = Payload::new();
+                let _ = valid.extend_from_slice(payload);
+                latest = Some((version, valid));
+            }
+            offset += size;
+        }
+        self.free = offset;
+        latest
+    }
+
+    fn write_flash(&mut self, payload: &[u8]) -> Result<(), Error> {
+        let size = record_size(payload.len());
+        if size > self.flash.size() {
+            return Err(Error::TooLarge);
+        }
+        if self.free + size > self.flash.size() {
+            self.flash.erase()?;
+            self.free = 0;
+        }
+
+        let mut record: Vec<u8, U64> = Vec::new();
+        let _ = record.extend_from_slice(&MAGIC.to_le_bytes());
+        let _ = record.extend_from_slice(&[VERSION, payload.len() as u8]);
+        let _ = record.extend_from_slice(payload);
+        if payload.len() % 2 != 0 {
+            let _ = record.push(0);
+        }
+        let _ = record.extend_from_slice(&frame_crc(VERSION, payload).to_le_bytes());
+
+        // Claim the space before writing, a failed write is not retried over
+        let offset = self.free;
+        self.free += size;
+        self.flash.write(offset, &record)
+    }
+}
+
+/// Header, payload padded to whole half words and CRC
+fn record_size(len: usize) -> usize {
+    HEADER_LEN + (len + 1) / 2 * 2 + CRC_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{MemoryFlash, MemoryRegisters};
+
+    type Store = SettingsStore<MemoryFlash, MemoryRegisters>;
+
+    fn changed() -> Settings {
+        let mut settings = Settings {
+            altitude: -12,
+            telemetry: Format::Binary,
+            night_brightness: 0,
+            clock_size: 1,
+            theme: 2,
+            rotation: 3,
+            ..Settings::default()
+        };
+        settings.alarms[1] = Alarm::new(6, 45, Weekdays::from_bits(0b0100_0101));
+        settings
+    }
+
+    /// Loads from what the store wrote, like after a reset
+    fn reload(store: Store) -> (Store, Settings) {
+        let (flash, registers) = store.release();
+        let mut store = SettingsStore::new(flash, registers);
+        let settings = store.load();
+        (store, settings)
+    }
+
+    fn write_registers(registers: &mut MemoryRegisters, version: u8, payload: &[u8]) {
+        registers.data[0] = ((version as u16) << 8) | payload.len() as u16;
+        for (idx, chunk) in payload.chunks(2).enumerate() {
+            let high = chunk.get(1).copied().unwrap_or(0) as u16;
+            registers.data[1 + idx] = chunk[0] as u16 | (high << 8);
+        }
+        registers.data[1 + (payload.len() + 1) / 2] = frame_crc(version, payload);
+    }
+
+    fn write_record(flash: &mut MemoryFlash, offset: usize, version: u8, payload: &[u8]) {
+        let mut record: Vec<u8, U64> = Vec::new();
+        record.extend_from_slice(&MAGIC.to_le_bytes()).unwrap();
+        record
+            .extend_from_slice(&[version, payload.len() as u8])
+            .unwrap();
+        record.extend_from_slice(payload).unwrap();
+        if payload.len() % 2 != 0 {
+            record.push(0).unwrap();
+        }
+        record
+            .extend_from_slice(&frame_crc(version, payload).to_le_bytes())
+            .unwrap();
+        flash.write(offset, &record).unwrap();
+    }
+
+    #[test]
+    fn empty_storage_loads_defaults() {
+        let mut store = Store::new(MemoryFlash::new(), MemoryRegisters::default());
+        assert_eq!(store.load(), Settings::default());
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut store = Store::new(MemoryFlash::new(), MemoryRegisters::default());
+        store.load();
+        store.save(&changed()).unwrap();
+        let (mut store, settings) = reload(store);
+        assert_eq!(settings, changed());
+
+        // Unchanged parts are not written again
+        let mut later = changed();
+        later.altitude = 250;
+        store.save(&later).unwrap();
+        let free = store.free;
+        store.save(&later).unwrap();
+        assert_eq!(store.free, free);
+        assert_eq!(reload(store).1, later);
+    }
+
+    #[test]
+    fn registers_have_room_to_grow() {
+        let words = (Settings::default().encode_registers().len() + 1) / 2;
+        assert!(2 + words < MemoryRegisters::default().data.len());
+        assert!(Settings::default().encode_flash().len() < MAX_PAYLOAD);
+    }
+
+    #[test]
+    fn torn_flash_record_keeps_the_previous_one() {
+        let mut store = Store::new(MemoryFlash::new(), MemoryRegisters::default());
+        store.load();
+        store.save(&changed()).unwrap();
+        let first = store.free;
+        let mut later = changed();
+        later.alarms[0].hours = 9;
+        store.save(&later).unwrap();
+
+        // A reset in the middle of the second record, the rest stays erased
+        let (mut flash, registers) = store.release();
+        for byte in flash.data_mut()[first + HEADER_LEN + 2..store_end(&later, first)].iter_mut() {
+            *byte = 0xff;
+        }
+        let mut store = SettingsStore::new(flash, registers);
+        assert_eq!(store.load().alarms, changed().alarms);
+
+        // The torn record is not written over
+        store.save(&later).unwrap();
+        assert!(store.free > store_end(&later, first));
+        assert_eq!(reload(store).1, later);
+    }
+
+    fn store_end(settings: &Settings, offset: usize) -> usize {
+        offset + record_size(settings.encode_flash().len())
+    }
+
+    #[test]
+    fn torn_registers_load_defaults() {
+        let mut store = Store::new(MemoryFlash::new(), MemoryRegisters::default());
+        store.load();
+        store.save(&changed()).unwrap();
+        let (flash, mut registers) = store.release();
+        // Invalidated before the payload is written
+        registers.data[0] = 0;
+        let settings = SettingsStore::new(flash, registers).load();
+        assert_eq!(settings.altitude, Settings::default().altitude);
+        assert_eq!(settings.alarms, changed().alarms);
+        assert_eq!(settings.theme, changed().theme);
+    }
+
+    #[test]
+    fn full_page_is_erased() {
+        let mut store = Store::new(MemoryFlash::new(), MemoryRegisters::default());
+        store.load();
+        let mut settings = changed();
+        for minutes in 0..60 {
+            settings.alarms[0].minutes = minutes;
+            store.save(&settings).unwrap();
+        }
+        let (flash, registers) = store.release();
+        assert_eq!(flash.erases, 1);
+        let mut store = SettingsStore::new(flash, registers);
+        assert_eq!(store.load(), settings);
+    }
+
+    #[test]
+    fn version_7_is_upgraded() {
+        let mut flash = MemoryFlash::new();
+        let mut registers = MemoryRegisters::default();
+        // Altitude 300, binary telemetry every 30 s, timeout 120 s, brightness
+        // 5 and 2, night 23 to 6, then the display options
+        let payload = [44, 1, 2, 30, 0, 120, 0, 5, 2, 23, 6, 0, 1, 1, 2];
+        write_registers(&mut registers, 7, &payload);
+        // Only the alarms
+        let alarms = [1, 6, 30, 0b0001_1111, 0, 7, 0, 0, 0, 0, 0, 0, 1, 12, 0, 0];
+        write_record(&mut flash, 0, 7, &alarms);
+
+        let mut store = SettingsStore::new(flash, registers);
+        let settings = store.load();
+        assert_eq!(settings.altitude, 300);
+        assert_eq!(settings.telemetry, Format::Binary);
+        assert_eq!(settings.telemetry_interval, 30);
+        assert_eq!(settings.display_timeout, 120);
+        assert_eq!((settings.brightness, settings.night_brightness), (5, 2));
+        assert_eq!((settings.night_start, settings.night_end), (23, 6));
+        assert_eq!((settings.clock_size, settings.clock_color), (0, 1));
+        assert_eq!((settings.theme, settings.rotation), (1, 2));
+        assert_eq!(settings.alarms[0], Alarm::new(6, 30, Weekdays::WORKDAYS));
+        assert_eq!(settings.alarms[3], Alarm::new(12, 0, Weekdays::ONCE));
+
+        // Written back in the current layout without losing anything
+        store.save(&settings).unwrap();
+        let (flash, registers) = store.release();
+        assert_eq!(registers.data[0] >> 8, VERSION as u16);
+        assert_eq!(SettingsStore::new(flash, registers).load(), settings);
+    }
+
+    #[test]
+    fn older_layouts_keep_defaults_for_new_fields() {
+        let mut registers = MemoryRegisters::default();
+        // Version 1 only had the altitude
+        write_registers(&mut registers, 1, &(-20i16).to_le_bytes());
+        let settings = SettingsStore::new(MemoryFlash::new(), registers).load();
+        assert_eq!(settings.altitude, -20);
+        assert_eq!(settings.telemetry_interval, 10);
+    }
+
+    #[test]
+    fn newer_layouts_are_rejected() {
+        let mut registers = MemoryRegisters::default();
+        write_registers(&mut registers, VERSION + 1, &[1, 2, 3]);
+        let mut flash = MemoryFlash::new();
+        write_record(&mut flash, 0, VERSION + 1, &[1, 6, 30, 0]);
+        let settings = SettingsStore::new(flash, registers).load();
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn failed_write_is_reported_and_not_retried() {
+        let mut flash = MemoryFlash::new();
+        flash.broken = true;
+        let mut store = Store::new(flash, MemoryRegisters::default());
+        store.load();
+        assert_eq!(store.save(&changed()), Err(Error::Flash));
+        let free = store.free;
+        assert_eq!(store.save(&changed()), Err(Error::Flash));
+        assert_eq!(store.free, free);
+
+        // Goes away with the next write that works
+        store.flash.broken = false;
+        let mut later = changed();
+        later.theme = 0;
+        assert_eq!(store.save(&later), Ok(()));
+    }
+
+    #[test]
+    fn too_large_for_the_registers() {
+        let mut store = Store::new(MemoryFlash::new(), MemoryRegisters::default());
+        let payload = [0u8; 17];
+        assert_eq!(store.write_registers(&payload), Err(Error::TooLarge));
+        assert_eq!(store.write_registers(&payload[..16]), Ok(()));
+    }
+}
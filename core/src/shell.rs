@@ -0,0 +1,605 @@
+// Line oriented command shell. `LineBuffer` collects the received bytes into
+// lines, `execute` runs a line against a `Device` and writes the reply to any
+// `uWrite`, so neither knows about the UART.
+
+use crate::alarm::Alarms;
+use crate::clock::{DateTime, Time};
+use crate::decimal::Decimal;
+use crate::health::Health;
+use crate::history::Sample;
+use crate::rtttl::Rtttl;
+use crate::settings::{self, Settings, KEYS};
+use heapless::{consts::*, String};
+use ufmt::{uWrite, uwrite};
+
+pub const PROMPT: &str = "> ";
+
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+pub type Line = String<U128>;
+/// Room for the longest reply, the help and the settings take about half
+pub type Reply = String<U1024>;
+
+const HELP: [(&str, &str); 9] = [
+    ("help", "list the commands"),
+    ("time [set HH:MM[:SS]]", "show or set the time"),
+    ("date [set YYYY-MM-DD]", "show or set the date"),
+    ("sensor read", "latest measurement"),
+    ("sensor status", "sensor health and error counters"),
+    ("alarm list", "configured alarms"),
+    ("play <rtttl>", "play a ringtone"),
+    ("settings get [key]", "show the settings"),
+    ("settings set <key> <value>", "change a setting"),
+];
+
+/// Collects received bytes into a line, echoing them back
+pub struct LineBuffer {
+    line: Line,
+    /// Completed line waiting to be executed
+    ready: Option<Line>,
+    /// Swallows the `\n` of a `\r\n` line ending
+    after_cr: bool,
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineBuffer {
+    pub fn new() -> Self {
+        Self {
+            line: Line::new(),
+            ready: None,
+            after_cr: false,
+        }
+    }
+
+    /// Adds a received byte, characters past the buffer size are dropped.
+    /// Input is ignored while the previous line has not been taken yet.
+    pub fn feed<W: uWrite + ?Sized>(&mut self, byte: u8, echo: &mut W) -> Result<(), W::Error> {
+        let after_cr = self.after_cr;
+        self.after_cr = byte == b'\r';
+        if self.ready.is_some() {
+            return Ok(());
+        }
+        match byte {
+            b'\n' if after_cr => {}
+            b'\r' | b'\n' => {
+                self.ready = Some(core::mem::replace(&mut self.line, Line::new()));
+                echo.write_str("\r\n")?;
+            }
+            BACKSPACE | DELETE if !self.line.is_empty() => {
+                self.line.pop();
+                echo.write_str("\x08 \x08")?;
+            }
+            0x20..=0x7e if self.line.len() < self.line.capacity() => {
+                let _ = self.line.push(byte as char);
+                let mut buf = [0u8; 4];
+                echo.write_str((byte as char).encode_utf8(&mut buf))?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn take(&mut self) -> Option<Line> {
+        self.ready.take()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.is_some()
+    }
+
+    /// Nothing typed since the last line
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_none() && self.line.is_empty()
+    }
+}
+
+/// What the shell commands act on
+pub trait Device {
+    fn datetime(&self) -> DateTime;
+    fn set_datetime(&mut self, datetime: &DateTime);
+    /// Latest measurement, `None` if the sensor failed
+    fn sample(&self) -> Option<Sample>;
+    fn health(&self) -> Health;
+    fn alarms(&self) -> &Alarms;
+    fn settings(&mut self) -> &mut Settings;
+    /// Why the settings could not be stored, `None` once they are
+    fn save_error(&self) -> Option<settings::Error>;
+    fn play(&mut self, song: &Rtttl);
+}
+
+#[derive(Copy, Clone)]
+pub enum Command<'a> {
+    Help,
+    Time,
+    SetTime(Time),
+    Date,
+    SetDate { year: u16, month: u8, day: u8 },
+    SensorRead,
+    SensorStatus,
+    AlarmList,
+    Play(Rtttl<'a>),
+    SettingsGet(Option<&'a str>),
+    SettingsSet(&'a str, i32),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error {
+    Unknown,
+    /// Wrong arguments, holds the usage of the command
+    Usage(&'static str),
+    Invalid,
+}
+
+/// Parses a line, `None` for an empty one
+pub fn parse(line: &str) -> Result<Option<Command<'_>>, Error> {
+    let line = line.trim();
+    let (name, rest) = match line.find(' ') {
+        Some(at) => (&line[..at], line[at + 1..].trim()),
+        None => (line, ""),
+    };
+    let mut args = rest.split_whitespace();
+    let command = match (name, args.next(), args.next(), args.next()) {
+        ("", ..) => return Ok(None),
+        ("help", None, ..) => Command::Help,
+        ("time", None, ..) => Command::Time,
+        ("time", Some("set"), Some(time), None) => {
+            Command::SetTime(parse_time(time).ok_or(Error::Invalid)?)
+        }
+        ("time", ..) => return Err(Error::Usage(HELP[1].0)),
+        ("date", None, ..) => Command::Date,
+        ("date", Some("set"), Some(date), None) => {
+            let (year, month, day) = parse_date(date).ok_or(Error::Invalid)?;
+            Command::SetDate { year, month, day }
+        }
+        ("date", ..) => return Err(Error::Usage(HELP[2].0)),
+        ("sensor", Some("read"), None, _) => Command::SensorRead,
+        ("sensor", Some("status"), None, _) => Command::SensorStatus,
+        ("sensor", ..) => return Err(Error::Usage("sensor read | status")),
+        ("alarm", Some("list"), None, _) => Command::AlarmList,
+        ("alarm", ..) => return Err(Error::Usage(HELP[5].0)),
+        // The ringtone is taken whole, it may contain spaces in its name
+        ("play", Some(_), ..) => Command::Play(Rtttl::parse(rest).map_err(|_| Error::Invalid)?),
+        ("play", ..) => return Err(Error::Usage(HELP[6].0)),
+        ("settings", Some("get"), key, None) => Command::SettingsGet(key),
+        ("settings", Some("set"), Some(key), Some(value)) if args.next().is_none() => {
+            Command::SettingsSet(key, value.parse().map_err(|_| Error::Invalid)?)
+        }
+        ("settings", ..) => return Err(Error::Usage("settings get [key] | set <key> <value>")),
+        _ => return Err(Error::Unknown),
+    };
+    Ok(Some(command))
+}
+
+/// "HH:MM" or "HH:MM:SS"
+fn parse_time(text: &str) -> Option<Time> {
+    let mut parts = text.split(':');
+    let hours: u8 = parts.next()?.parse().ok()?;
+    let minutes: u8 = parts.next()?.parse().ok()?;
+    let seconds: u8 = match parts.next() {
+        Some(seconds) => seconds.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Time::new(hours, minutes, seconds)
+}
+
+/// "YYYY-MM-DD" within the years the clock screen can set
+fn parse_date(text: &str) -> Option<(u16, u8, u8)> {
+    let mut parts = text.split('-');
+    let year: u16 = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day: u8 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !(2000..=2099).contains(&year) {
+        return None;
+    }
+    DateTime::new(year, month, day, Time::new(0, 0, 0)?)?;
+    Some((year, month, day))
+}
+
+/// Runs a line and writes the reply followed by the prompt
+pub fn execute<D, W>(line: &str, device: &mut D, out: &mut W) -> Result<(), W::Error>
+where
+    D: Device,
+    W: uWrite + ?Sized,
+{
+    match parse(line) {
+        Ok(None) => {}
+        Ok(Some(command)) => run(command, device, out)?,
+        Err(Error::Unknown) => out.write_str("unknown command, try help\r\n")?,
+        Err(Error::Usage(usage)) => uwrite!(out, "usage: {}\r\n", usage)?,
+        Err(Error::Invalid) => out.write_str("invalid value\r\n")?,
+    }
+    out.write_str(PROMPT)
+}
+
+fn run<D, W>(command: Command, device: &mut D, out: &mut W) -> Result<(), W::Error>
+where
+    D: Device,
+    W: uWrite + ?Sized,
+{
+    match command {
+        Command::Help => {
+            for (usage, description) in HELP.iter() {
+                uwrite!(out, "{}", *usage)?;
+                for _ in usage.len()..28 {
+                    out.write_str(" ")?;
+                }
+                uwrite!(out, "{}\r\n", *description)?;
+            }
+        }
+        Command::Time => uwrite!(out, "{}\r\n", device.datetime().time)?,
+        Command::SetTime(time) => {
+            let mut datetime = device.datetime();
+            datetime.time = time;
+            device.set_datetime(&datetime);
+            uwrite!(out, "{}\r\n", device.datetime().time)?;
+        }
+        Command::Date => uwrite!(out, "{}\r\n", device.datetime())?,
+        Command::SetDate { year, month, day } => {
+            match DateTime::new(year, month, day, device.datetime().time) {
+                Some(datetime) => {
+                    device.set_datetime(&datetime);
+                    uwrite!(out, "{}\r\n", device.datetime())?;
+                }
+                None => out.write_str("invalid value\r\n")?,
+            }
+        }
+        Command::SensorRead => match device.sample() {
+            Some(sample) => {
+                if let Some(temperature) = sample.temperature {
+                    uwrite!(out, "temperature {} C\r\n", Decimal(temperature))?;
+                }
+                if let Some(humidity) = sample.humidity {
+                    uwrite!(out, "humidity {} %\r\n", Decimal(humidity))?;
+                }
+                if let Some(pressure) = sample.pressure {
+                    uwrite!(out, "pressure {} hPa\r\n", Decimal(pressure / 100.0))?;
+                }
+            }
+            None => out.write_str("sensor not available\r\n")?,
+        },
+        Command::SensorStatus => {
+            let health = device.health();
+            uwrite!(
+                out,
+                "{}, {} errors, {} recoveries\r\n",
+                health.as_str(),
+                health.errors,
+                health.recoveries
+            )?;
+        }
+        Command::AlarmList => {
+            let pad = |num: u8| if num < 10 { "0" } else { "" };
+            for (idx, alarm) in device.alarms().iter().enumerate() {
+                uwrite!(
+                    out,
+                    "{} {}{}:{}{} {} {}\r\n",
+                    idx + 1,
+                    pad(alarm.hours),
+                    alarm.hours,
+                    pad(alarm.minutes),
+                    alarm.minutes,
+                    alarm.repeat,
+                    if alarm.enabled { "on" } else { "off" }
+                )?;
+            }
+        }
+        Command::Play(song) => {
+            device.play(&song);
+            out.write_str("playing\r\n")?;
+        }
+        Command::SettingsGet(Some(key)) => match device.settings().get(key) {
+            Some(value) => uwrite!(out, "{} = {}\r\n", key, value)?,
+            None => out.write_str("unknown setting\r\n")?,
+        },
+        Command::SettingsGet(None) => {
+            for key in KEYS.iter() {
+                if let Some(value) = device.settings().get(key) {
+                    uwrite!(out, "{} = {}\r\n", *key, value)?;
+                }
+            }
+            if let Some(error) = device.save_error() {
+                uwrite!(out, "not saved: {}\r\n", error.as_str())?;
+            }
+        }
+        Command::SettingsSet(key, value) => {
+            if device.settings().set(key, value) {
+                uwrite!(out, "{} = {}\r\n", key, value)?;
+            } else if device.settings().get(key).is_none() {
+                out.write_str("unknown setting\r\n")?;
+            } else {
+                out.write_str("value out of range\r\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes to a serial port, blocking until every byte is queued
+pub struct SerialWriter<'a, S>(pub &'a mut S);
+
+impl<S> SerialWriter<'_, S>
+where
+    S: embedded_hal::serial::Write<u8>,
+{
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), S::Error> {
+        for byte in bytes {
+            nb::block!(self.0.write(*byte))?;
+        }
+        Ok(())
+    }
+}
+
+impl<S> uWrite for SerialWriter<'_, S>
+where
+    S: embedded_hal::serial::Write<u8>,
+{
+    type Error = S::Error;
+
+    fn write_str(&mut self, s: &str) -> Result<(), S::Error> {
+        self.write_bytes(s.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::{Alarm, Weekdays};
+
+    struct FakeDevice {
+        datetime: DateTime,
+        sample: Option<Sample>,
+        health: Health,
+        alarms: Alarms,
+        settings: Settings,
+        save_error: Option<settings::Error>,
+        /// Notes of the song last played
+        played: Option<usize>,
+    }
+
+    impl FakeDevice {
+        fn new() -> Self {
+            let time = Time {
+                hours: 12,
+                minutes: 34,
+                seconds: 56,
+            };
+            Self {
+                datetime: DateTime::new(2021, 1, 4, time).unwrap(),
+                sample: Some(Sample {
+                    temperature: Some(21.46),
+                    humidity: Some(45.0),
+                    pressure: Some(101_325.0),
+                }),
+                health: Health::new(),
+                alarms: Alarms::new(),
+                settings: Settings::default(),
+                save_error: None,
+                played: None,
+            }
+        }
+    }
+
+    impl Device for FakeDevice {
+        fn datetime(&self) -> DateTime {
+            self.datetime
+        }
+
+        fn set_datetime(&mut self, datetime: &DateTime) {
+            self.datetime = *datetime;
+        }
+
+        fn sample(&self) -> Option<Sample> {
+            self.sample
+        }
+
+        fn health(&self) -> Health {
+            self.health
+        }
+
+        fn alarms(&self) -> &Alarms {
+            &self.alarms
+        }
+
+        fn settings(&mut self) -> &mut Settings {
+            &mut self.settings
+        }
+
+        fn save_error(&self) -> Option<settings::Error> {
+            self.save_error
+        }
+
+        fn play(&mut self, song: &Rtttl) {
+            self.played = Some(song.note_count());
+        }
+    }
+
+    /// Types `input` byte by byte, runs every completed line and returns the
+    /// echo and the replies as the terminal shows them
+    fn session(device: &mut FakeDevice, input: &str) -> Reply {
+        let mut shell = LineBuffer::new();
+        let mut out = Reply::new();
+        for byte in input.bytes() {
+            shell.feed(byte, &mut out).unwrap();
+            if let Some(line) = shell.take() {
+                execute(&line, device, &mut out).unwrap();
+            }
+        }
+        out
+    }
+
+    fn reply(device: &mut FakeDevice, line: &str) -> Reply {
+        let mut out = Reply::new();
+        execute(line, device, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn lines_are_echoed_and_edited() {
+        let mut device = FakeDevice::new();
+        let out = session(&mut device, "tine\x08\x08me\r\n");
+        assert_eq!(out.as_str(), "tine\x08 \x08\x08 \x08me\r\n12:34:56\r\n> ");
+        // Bare \n and \r endings work as well, empty lines only prompt
+        let out = session(&mut device, "\ntime\n");
+        assert_eq!(out.as_str(), "\r\n> time\r\n12:34:56\r\n> ");
+        // Backspace on an empty line and control characters do nothing
+        assert_eq!(session(&mut device, "\x7f\x1b").as_str(), "");
+    }
+
+    #[test]
+    fn input_waits_for_the_pending_line() {
+        let mut shell = LineBuffer::new();
+        let mut out = Reply::new();
+        for byte in b"time\rdate\r".iter() {
+            shell.feed(*byte, &mut out).unwrap();
+        }
+        assert_eq!(shell.take().as_deref(), Some("time"));
+        assert!(shell.is_empty());
+        assert_eq!(out.as_str(), "time\r\n");
+    }
+
+    #[test]
+    fn long_lines_are_cut() {
+        let mut shell = LineBuffer::new();
+        let mut out = String::<U256>::new();
+        for _ in 0..200 {
+            shell.feed(b'x', &mut out).unwrap();
+        }
+        shell.feed(b'\r', &mut out).unwrap();
+        assert_eq!(shell.take().unwrap().len(), 128);
+    }
+
+    #[test]
+    fn time_and_date() {
+        let mut device = FakeDevice::new();
+        assert_eq!(
+            reply(&mut device, "date").as_str(),
+            "Mon 2021-01-04 12:34:56\r\n> "
+        );
+        assert_eq!(
+            reply(&mut device, "time set 7:05").as_str(),
+            "07:05:00\r\n> "
+        );
+        let out = reply(&mut device, "date set 2024-02-29");
+        assert_eq!(out.as_str(), "Thu 2024-02-29 07:05:00\r\n> ");
+
+        assert_eq!(
+            reply(&mut device, "date set 2023-02-29").as_str(),
+            "invalid value\r\n> "
+        );
+        assert_eq!(
+            reply(&mut device, "time set 24:00").as_str(),
+            "invalid value\r\n> "
+        );
+        let out = reply(&mut device, "time 12:00");
+        assert_eq!(out.as_str(), "usage: time [set HH:MM[:SS]]\r\n> ");
+        assert_eq!(device.datetime.time.hours, 7);
+    }
+
+    #[test]
+    fn sensor_reads_what_is_measured() {
+        let mut device = FakeDevice::new();
+        let out = reply(&mut device, "sensor read");
+        assert_eq!(
+            out.as_str(),
+            "temperature 21.5 C\r\nhumidity 45.0 %\r\npressure 1013.3 hPa\r\n> "
+        );
+
+        device.sample = device.sample.map(|sample| Sample {
+            pressure: None,
+            ..sample
+        });
+        let out = reply(&mut device, "sensor read");
+        assert_eq!(out.as_str(), "temperature 21.5 C\r\nhumidity 45.0 %\r\n> ");
+
+        device.sample = None;
+        for _ in 0..3 {
+            device.health.failure(0);
+        }
+        let out = reply(&mut device, "sensor read");
+        assert_eq!(out.as_str(), "sensor not available\r\n> ");
+        let out = reply(&mut device, "sensor status");
+        assert_eq!(out.as_str(), "offline, 3 errors, 0 recoveries\r\n> ");
+    }
+
+    #[test]
+    fn alarm_list() {
+        let mut device = FakeDevice::new();
+        device.alarms.set(1, Alarm::new(9, 5, Weekdays::WEEKEND));
+        let out = reply(&mut device, "alarm list");
+        assert_eq!(
+            out.as_str(),
+            concat!(
+                "1 07:00 MTWTF-- off\r\n2 09:05 -----SS on\r\n",
+                "3 00:00 ------- off\r\n4 00:00 ------- off\r\n> "
+            )
+        );
+    }
+
+    #[test]
+    fn play_checks_the_song() {
+        let mut device = FakeDevice::new();
+        let out = reply(&mut device, "play Beep:d=8,o=5,b=120:c,e,g");
+        assert_eq!(out.as_str(), "playing\r\n> ");
+        assert_eq!(device.played, Some(3));
+        assert_eq!(
+            reply(&mut device, "play Beep:d=8:x9").as_str(),
+            "invalid value\r\n> "
+        );
+        assert_eq!(
+            reply(&mut device, "play").as_str(),
+            "usage: play <rtttl>\r\n> "
+        );
+    }
+
+    #[test]
+    fn settings_get_and_set() {
+        let mut device = FakeDevice::new();
+        assert_eq!(
+            reply(&mut device, "settings get altitude").as_str(),
+            "altitude = 100\r\n> "
+        );
+        let out = reply(&mut device, "settings set altitude 350");
+        assert_eq!(out.as_str(), "altitude = 350\r\n> ");
+        assert_eq!(device.settings.altitude, 350);
+
+        let out = reply(&mut device, "settings set brightness 11");
+        assert_eq!(out.as_str(), "value out of range\r\n> ");
+        let out = reply(&mut device, "settings set volume 1");
+        assert_eq!(out.as_str(), "unknown setting\r\n> ");
+        let out = reply(&mut device, "settings set altitude high");
+        assert_eq!(out.as_str(), "invalid value\r\n> ");
+
+        let out = reply(&mut device, "settings get");
+        assert!(out.starts_with("altitude = 350\r\ntelemetry = 0\r\n"));
+        assert!(out.ends_with("rotation = 0\r\n> "));
+        assert_eq!(out.matches("\r\n").count(), KEYS.len());
+
+        device.save_error = Some(settings::Error::TooLarge);
+        let out = reply(&mut device, "settings get");
+        assert!(out.ends_with("rotation = 0\r\nnot saved: too large for the storage\r\n> "));
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let mut device = FakeDevice::new();
+        assert_eq!(
+            reply(&mut device, "reboot").as_str(),
+            "unknown command, try help\r\n> "
+        );
+        let out = reply(&mut device, "sensor");
+        assert_eq!(out.as_str(), "usage: sensor read | status\r\n> ");
+        let out = reply(&mut device, "help");
+        assert!(out.starts_with("help "));
+        assert!(out.contains("\ndate [set YYYY-MM-DD]       show or set the date\r\n"));
+        assert_eq!(out.matches("\r\n").count(), HELP.len());
+    }
+}
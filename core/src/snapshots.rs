@@ -0,0 +1,267 @@
+// Renders the views into a frame buffer and compares them with the reference
+// images in `core/snapshots`, along with a few checks that do not depend on
+// the fonts.
+
+extern crate std;
+
+use crate::alarm::{Alarm, Alarms, Weekdays};
+use crate::clock::{Clock, DateTime, Time};
+use crate::display::{Display, FontSize, Gui, HEADER_HEIGHT};
+use crate::health::Health;
+use crate::history::{History, Sample, HOUR};
+use crate::input::{Button, Event, Gesture};
+use crate::menu::{Context, Screen};
+use crate::screens::{ClockScreen, MeasureScreen};
+use crate::segments::COLORS;
+use crate::settings::Settings;
+use crate::theme::Theme;
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+use pomia_framebuffer::FrameBuffer;
+use pomia_sensors::Capabilities;
+use std::{boxed::Box, path::Path};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 160;
+const THEME: Theme = Theme::DAY;
+
+struct FixedClock(u32);
+
+impl Clock for FixedClock {
+    fn timestamp(&self) -> u32 {
+        self.0
+    }
+
+    fn set_timestamp(&mut self, timestamp: u32) {
+        self.0 = timestamp;
+    }
+}
+
+/// What the screens read, also used by the navigation tests
+pub(crate) struct State {
+    clock: FixedClock,
+    alarms: Alarms,
+    history: History,
+    settings: Settings,
+    sample: Option<Sample>,
+    capabilities: Capabilities,
+    health: Health,
+}
+
+impl State {
+    // Monday 2021-01-04 12:34:56 with the sensor not read yet
+    pub(crate) fn new() -> Self {
+        let time = Time {
+            hours: 12,
+            minutes: 34,
+            seconds: 56,
+        };
+        Self {
+            clock: FixedClock(u32::from(&DateTime::new(2021, 1, 4, time).unwrap())),
+            alarms: Alarms::new(),
+            history: History::new(),
+            settings: Settings::default(),
+            sample: None,
+            capabilities: Capabilities {
+                temperature: true,
+                humidity: true,
+                pressure: true,
+            },
+            health: Health::new(),
+        }
+    }
+
+    pub(crate) fn context(&mut self) -> Context<'_> {
+        Context {
+            clock: &mut self.clock,
+            alarms: &mut self.alarms,
+            history: &mut self.history,
+            settings: &mut self.settings,
+            sample: self.sample,
+            capabilities: self.capabilities,
+            health: self.health,
+        }
+    }
+}
+
+fn enter(gesture: Gesture) -> Event {
+    Event {
+        button: Button::Enter,
+        gesture,
+    }
+}
+
+/// Passes the events to the screen and draws one frame
+fn render<S>(screen: S, state: &mut State, events: &[Event]) -> FrameBuffer
+where
+    S: Screen<FrameBuffer> + 'static,
+{
+    let mut display = Display::new(FrameBuffer::new(WIDTH, HEIGHT));
+    display.set_theme(THEME);
+    display.clear();
+    let mut gui = Gui::new(display);
+    gui.register(Box::leak(Box::new(screen)));
+
+    let mut ctx = state.context();
+    for event in events {
+        gui.handle(*event, &mut ctx);
+    }
+    gui.begin_frame();
+    gui.render(&mut ctx);
+    gui.end_frame();
+    gui.release()
+}
+
+fn assert_snapshot(frame: &FrameBuffer, name: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+    frame.assert_snapshot(&dir, name);
+}
+
+fn header_pixels(frame: &FrameBuffer, color: Rgb565) -> usize {
+    frame.count(Point::zero(), WIDTH, HEADER_HEIGHT, color)
+}
+
+fn body_pixels(frame: &FrameBuffer, top: u32, height: u32, color: Rgb565) -> usize {
+    frame.count(Point::new(0, top as i32), WIDTH, height, color)
+}
+
+#[test]
+fn header() {
+    let mut state = State::new();
+    let frame = render(MeasureScreen, &mut state, &[]);
+    assert_snapshot(&frame, "header");
+
+    // The accent frame around the title and nothing below without a sample
+    assert_eq!(frame.pixel(Point::new(0, 10)), Some(THEME.accent));
+    assert_eq!(frame.pixel(Point::new(64, 0)), Some(THEME.accent));
+    assert!(header_pixels(&frame, THEME.foreground) > 0);
+    let body = HEIGHT - HEADER_HEIGHT - 1;
+    assert_eq!(
+        body_pixels(&frame, HEADER_HEIGHT + 1, body, THEME.background),
+        (WIDTH * body) as usize
+    );
+}
+
+#[test]
+fn measurements() {
+    let mut state = State::new();
+    let now = state.clock.timestamp();
+    // Three hours of slowly falling pressure for the forecast
+    for step in 0..=12 {
+        let sample = Sample {
+            temperature: Some(21.5),
+            humidity: Some(45.0),
+            pressure: Some(101_800.0 - step as f32 * 25.0),
+        };
+        state
+            .history
+            .record(now - 3 * HOUR + step * HOUR / 4, &sample);
+    }
+    state.sample = Some(Sample {
+        temperature: Some(21.5),
+        humidity: Some(45.0),
+        pressure: Some(101_500.0),
+    });
+    let frame = render(MeasureScreen, &mut state, &[]);
+    assert_snapshot(&frame, "measurements");
+
+    // The three measurements and the trend, the forecast may take one line
+    let layout = Display::new(FrameBuffer::new(WIDTH, HEIGHT));
+    for line in 0..4 {
+        let top = layout.line_y(line, 6) as u32;
+        assert!(body_pixels(&frame, top, 16, THEME.foreground) > 0);
+    }
+}
+
+#[test]
+fn measurements_without_pressure() {
+    let mut state = State::new();
+    state.capabilities.pressure = false;
+    state.sample = Some(Sample {
+        temperature: Some(21.5),
+        humidity: Some(45.0),
+        pressure: None,
+    });
+    let frame = render(MeasureScreen, &mut state, &[]);
+    assert_snapshot(&frame, "measurements_without_pressure");
+
+    // No pressure line and no forecast below the temperature and humidity
+    let layout = Display::new(FrameBuffer::new(WIDTH, HEIGHT));
+    for line in 0..2 {
+        let top = layout.line_y(line, 6) as u32;
+        assert!(body_pixels(&frame, top, 16, THEME.foreground) > 0);
+    }
+    let top = layout.line_y(2, 6) as u32;
+    assert_eq!(body_pixels(&frame, top, HEIGHT - top, THEME.foreground), 0);
+}
+
+#[test]
+fn lines_follow_the_width() {
+    let portrait = Display::new(FrameBuffer::new(WIDTH, HEIGHT));
+    let landscape = Display::new(FrameBuffer::new(HEIGHT, WIDTH));
+    assert_eq!(portrait.line_chars(FontSize::Small), 16);
+    assert_eq!(portrait.line_chars(FontSize::Large), 10);
+    assert_eq!(landscape.line_chars(FontSize::Small), 20);
+    assert_eq!(landscape.line_chars(FontSize::Large), 13);
+    assert_eq!(landscape.pad("No forecast yet").len(), 20);
+    assert_eq!(landscape.pad_lg("T: 21.5 C").len(), 13);
+
+    let text = "Rain at times, very unsettled";
+    assert_eq!(portrait.wrap(text), ("Rain at times,", "very unsettled"));
+    assert_eq!(landscape.wrap(text), ("Rain at times, very", "unsettled"));
+}
+
+#[test]
+fn clock() {
+    let mut state = State::new();
+    let frame = render(ClockScreen::new(), &mut state, &[]);
+    assert_snapshot(&frame, "clock");
+
+    let face = COLORS[state.settings.clock_color as usize % COLORS.len()];
+    assert!(body_pixels(&frame, HEADER_HEIGHT, HEIGHT - HEADER_HEIGHT, face) > 0);
+}
+
+#[test]
+fn edit_cursor() {
+    let mut state = State::new();
+    // Into the editor and on to the minutes
+    let events = [enter(Gesture::LongPress), enter(Gesture::Click)];
+    let frame = render(ClockScreen::new(), &mut state, &events);
+    assert_snapshot(&frame, "clock_edit");
+
+    // "12:34:56" in the large font centered on the first line below the
+    // header, the minutes are underlined
+    let left = (WIDTH - 8 * 12) as i32 / 2;
+    let underline = HEADER_HEIGHT as i32 + 4 + 16;
+    let (start, end) = (left + 3 * 12, left + 5 * 12 - 1);
+    let at = |x| frame.pixel(Point::new(x, underline));
+    assert_eq!(at(start), Some(THEME.foreground));
+    assert_eq!(at(end), Some(THEME.foreground));
+    assert_eq!(at(start - 1), Some(THEME.background));
+    assert_eq!(at(end + 1), Some(THEME.background));
+    assert_eq!(at(left), Some(THEME.background));
+}
+
+#[test]
+fn sensor_offline() {
+    let mut state = State::new();
+    for _ in 0..3 {
+        state.health.failure(0);
+    }
+    let frame = render(MeasureScreen, &mut state, &[]);
+    assert_snapshot(&frame, "sensor_offline");
+
+    assert!(header_pixels(&frame, THEME.error) > 0);
+    assert_eq!(header_pixels(&frame, THEME.foreground), 0);
+}
+
+#[test]
+fn alarm_ringing() {
+    let mut state = State::new();
+    state.alarms.set(0, Alarm::new(12, 34, Weekdays::EVERY_DAY));
+    assert!(state.alarms.trigger(state.clock.timestamp()));
+    let frame = render(ClockScreen::new(), &mut state, &[]);
+    assert_snapshot(&frame, "alarm_ringing");
+
+    assert!(header_pixels(&frame, THEME.warning) > 0);
+    assert_eq!(header_pixels(&frame, THEME.foreground), 0);
+}
@@ -0,0 +1,198 @@
+// Periodic sensor telemetry on the serial port, either as CSV lines or as the
+// binary frames `telemetry-decode` turns back into CSV on the PC
+
+use crate::shell::SerialWriter;
+use pomia_telemetry::{Record, CSV_HEADER, MAX_FRAME};
+use ufmt::uWrite;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Format {
+    Off,
+    Csv,
+    Binary,
+}
+
+impl Format {
+    pub fn from_index(idx: u8) -> Option<Self> {
+        match idx {
+            0 => Some(Format::Off),
+            1 => Some(Format::Csv),
+            2 => Some(Format::Binary),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+pub struct Telemetry {
+    /// Timestamp of the last record sent
+    last: Option<u32>,
+    /// The CSV header goes out once, before the first line
+    header_sent: bool,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self {
+            last: None,
+            header_sent: false,
+        }
+    }
+
+    /// Sends the record once `interval` seconds passed since the last one
+    pub fn update<S>(
+        &mut self,
+        format: Format,
+        interval: u32,
+        record: &Record,
+        out: &mut SerialWriter<S>,
+    ) -> Result<(), S::Error>
+    where
+        S: embedded_hal::serial::Write<u8>,
+    {
+        match format {
+            Format::Off => *self = Self::new(),
+            Format::Csv if self.due(record.timestamp, interval) => {
+                if !self.header_sent {
+                    out.write_str(CSV_HEADER)?;
+                    out.write_str("\r\n")?;
+                    self.header_sent = true;
+                }
+                record.write_csv(out)?;
+                out.write_str("\r\n")?;
+            }
+            Format::Binary if self.due(record.timestamp, interval) => {
+                self.header_sent = false;
+                let mut frame = [0u8; MAX_FRAME];
+                let len = record.encode(&mut frame);
+                out.write_bytes(&frame[..len])?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn due(&mut self, now: u32, interval: u32) -> bool {
+        match self.last {
+            Some(last) if now.wrapping_sub(last) < interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use heapless::{consts::*, Vec};
+    use pomia_telemetry::Decoder;
+
+    /// Serial port keeping what was written
+    struct Port(Vec<u8, U512>);
+
+    impl embedded_hal::serial::Write<u8> for Port {
+        type Error = Infallible;
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+            self.0.push(byte).unwrap();
+            Ok(())
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Infallible> {
+            Ok(())
+        }
+    }
+
+    fn record(timestamp: u32) -> Record {
+        Record {
+            timestamp,
+            temperature: Some(21.5),
+            humidity: Some(45.0),
+            pressure: None,
+        }
+    }
+
+    /// Offers a record every second from `start` to `end` and returns the output
+    fn run(telemetry: &mut Telemetry, format: Format, start: u32, end: u32) -> Port {
+        let mut port = Port(Vec::new());
+        for now in start..end {
+            let mut out = SerialWriter(&mut port);
+            telemetry
+                .update(format, 10, &record(now), &mut out)
+                .unwrap();
+        }
+        port
+    }
+
+    fn lines(port: &Port) -> Vec<&str, U16> {
+        let text = core::str::from_utf8(&port.0).unwrap();
+        text.split_terminator("\r\n").collect()
+    }
+
+    fn frames(port: &Port) -> Vec<Record, U16> {
+        let mut decoder = Decoder::new();
+        let records = port.0.iter().filter_map(|byte| decoder.feed(*byte));
+        records.map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn csv_header_goes_out_once() {
+        let mut telemetry = Telemetry::new();
+        let port = run(&mut telemetry, Format::Csv, 100, 125);
+        assert_eq!(
+            lines(&port)[..],
+            [
+                CSV_HEADER,
+                "100,21.50,45.00,",
+                "110,21.50,45.00,",
+                "120,21.50,45.00,"
+            ]
+        );
+        let port = run(&mut telemetry, Format::Csv, 125, 135);
+        assert_eq!(lines(&port)[..], ["130,21.50,45.00,"]);
+    }
+
+    #[test]
+    fn records_follow_the_interval() {
+        let mut telemetry = Telemetry::new();
+        let port = run(&mut telemetry, Format::Binary, 100, 131);
+        let stamps: Vec<u32, U16> = frames(&port).iter().map(|r| r.timestamp).collect();
+        assert_eq!(stamps[..], [100, 110, 120, 130]);
+        assert_eq!(frames(&port)[0], record(100));
+
+        // Across the wrap of the counter
+        let mut telemetry = Telemetry::new();
+        run(&mut telemetry, Format::Binary, u32::MAX - 5, u32::MAX);
+        let port = run(&mut telemetry, Format::Binary, 0, 10);
+        let stamps: Vec<u32, U16> = frames(&port).iter().map(|r| r.timestamp).collect();
+        assert_eq!(stamps[..], [4]);
+    }
+
+    #[test]
+    fn format_switch_sends_the_header_again() {
+        let mut telemetry = Telemetry::new();
+        run(&mut telemetry, Format::Csv, 100, 101);
+        let port = run(&mut telemetry, Format::Binary, 110, 111);
+        assert_eq!(frames(&port).len(), 1);
+        let port = run(&mut telemetry, Format::Csv, 120, 121);
+        assert_eq!(lines(&port)[..], [CSV_HEADER, "120,21.50,45.00,"]);
+
+        // Switched off and on again, the next record goes out right away
+        let port = run(&mut telemetry, Format::Off, 121, 200);
+        assert!(port.0.is_empty());
+        let port = run(&mut telemetry, Format::Csv, 200, 201);
+        assert_eq!(lines(&port)[..], [CSV_HEADER, "200,21.50,45.00,"]);
+    }
+}
@@ -0,0 +1,95 @@
+// Colours of the GUI. The display draws with whatever theme it was given, the
+// theme setting picks one or follows the night schedule of the backlight.
+
+use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Theme {
+    /// Text and everything drawn with it
+    pub foreground: Rgb565,
+    pub background: Rgb565,
+    /// Header frame and highlights
+    pub accent: Rgb565,
+    pub warning: Rgb565,
+    pub error: Rgb565,
+}
+
+impl Theme {
+    pub const DAY: Theme = Theme {
+        foreground: Rgb565::RED,
+        background: Rgb565::BLACK,
+        accent: Rgb565::MAGENTA,
+        warning: Rgb565::YELLOW,
+        error: Rgb565::new(31, 16, 0),
+    };
+
+    /// Dim red only, it keeps the eyes adapted to the dark
+    pub const NIGHT: Theme = Theme {
+        foreground: Rgb565::new(12, 0, 0),
+        background: Rgb565::BLACK,
+        accent: Rgb565::new(7, 0, 0),
+        warning: Rgb565::new(20, 0, 0),
+        error: Rgb565::new(31, 0, 0),
+    };
+
+    pub const HIGH_CONTRAST: Theme = Theme {
+        foreground: Rgb565::WHITE,
+        background: Rgb565::BLACK,
+        accent: Rgb565::CYAN,
+        warning: Rgb565::YELLOW,
+        error: Rgb565::RED,
+    };
+}
+
+/// Choices of the theme setting, the first one switches between day and
+/// night with the night schedule
+pub const THEME_LABELS: [&str; 4] = ["Auto", "Day", "Night", "Contrast"];
+
+/// Theme of a setting from `THEME_LABELS`
+pub fn select(choice: u8, night: bool) -> Theme {
+    match choice {
+        1 => Theme::DAY,
+        2 => Theme::NIGHT,
+        3 => Theme::HIGH_CONTRAST,
+        _ if night => Theme::NIGHT,
+        _ => Theme::DAY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    #[test]
+    fn fixed_themes_ignore_the_schedule() {
+        for night in [false, true].iter() {
+            assert_eq!(select(1, *night), Theme::DAY);
+            assert_eq!(select(2, *night), Theme::NIGHT);
+            assert_eq!(select(3, *night), Theme::HIGH_CONTRAST);
+        }
+        assert_eq!(select(0, false), Theme::DAY);
+        assert_eq!(select(0, true), Theme::NIGHT);
+    }
+
+    #[test]
+    fn auto_switches_with_the_night_schedule() {
+        let settings = Settings {
+            theme: 0,
+            night_start: 22,
+            night_end: 7,
+            ..Settings::default()
+        };
+        assert_eq!(settings.theme_at(21), Theme::DAY);
+        assert_eq!(settings.theme_at(22), Theme::NIGHT);
+        assert_eq!(settings.theme_at(0), Theme::NIGHT);
+        assert_eq!(settings.theme_at(6), Theme::NIGHT);
+        assert_eq!(settings.theme_at(7), Theme::DAY);
+
+        let contrast = Settings {
+            theme: 3,
+            ..settings
+        };
+        assert_eq!(contrast.theme_at(23), Theme::HIGH_CONTRAST);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::note::Note;
+use crate::rtttl::{Notes, Rtttl, RtttlNote};
+use core::slice::Iter;
+use embedded_hal::Pwm;
+
+/// Length of one beat of a `Song::Beats` melody
+const TEMPO_MS: u32 = 100;
+/// Notes a `Melody` holds, longer songs are cut off
+pub const MELODY_LEN: usize = 64;
+
+/// Song decoded into RAM, for songs received at runtime rather than kept in flash
+#[derive(Copy, Clone)]
+pub struct Melody {
+    notes: [RtttlNote; MELODY_LEN],
+    len: usize,
+}
+
+impl Melody {
+    pub fn from_rtttl(rtttl: &Rtttl) -> Self {
+        let mut melody = Self {
+            notes: [RtttlNote {
+                note: Note::Rest,
+                duration_ms: 0,
+            }; MELODY_LEN],
+            len: 0,
+        };
+        for (slot, note) in melody.notes.iter_mut().zip(rtttl.notes()) {
+            *slot = note;
+            melody.len += 1;
+        }
+        melody
+    }
+}
+
+// There is no allocator to box the melody in
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone)]
+pub enum Song {
+    /// Hand transcribed `(note, beats)` pairs
+    Beats(&'static [(Note, u32)]),
+    Rtttl(Rtttl<'static>),
+    Melody(Melody),
+}
+
+impl Song {
+    fn len(&self) -> usize {
+        match self {
+            Song::Beats(notes) => notes.len(),
+            Song::Rtttl(rtttl) => rtttl.note_count(),
+            Song::Melody(melody) => melody.len,
+        }
+    }
+}
+
+/// Place in a playing song, an RTTTL song is parsed a note at a time as the
+/// timer interrupt reaches it
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Cursor {
+    Beats(Iter<'static, (Note, u32)>),
+    Rtttl(Notes<'static>),
+    Melody(Melody, usize),
+}
+
+impl From<Song> for Cursor {
+    fn from(song: Song) -> Self {
+        match song {
+            Song::Beats(notes) => Cursor::Beats(notes.iter()),
+            Song::Rtttl(rtttl) => Cursor::Rtttl(rtttl.notes()),
+            Song::Melody(melody) => Cursor::Melody(melody, 0),
+        }
+    }
+}
+
+impl Iterator for Cursor {
+    type Item = (Note, u32);
+
+    /// Next note and its duration in milliseconds
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Cursor::Beats(notes) => notes.next().map(|(note, beats)| (*note, beats * TEMPO_MS)),
+            Cursor::Rtttl(notes) => notes.next().map(|note| (note.note, note.duration_ms)),
+            Cursor::Melody(melody, idx) => {
+                let note = melody.notes[..melody.len].get(*idx)?;
+                *idx += 1;
+                Some((note.note, note.duration_ms))
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum PlayerState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+/// What the PWM output should do after a sequencer call
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Output {
+    Play(u32),
+    Silence,
+    Unchanged,
+}
+
+/// Steps through a song on timer ticks, independent of the PWM hardware
+pub struct Sequencer {
+    cursor: Option<Cursor>,
+    position: usize,
+    length: usize,
+    remaining_ms: u32,
+    frequency: u32,
+    state: PlayerState,
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sequencer {
+    pub const fn new() -> Self {
+        Self {
+            cursor: None,
+            position: 0,
+            length: 0,
+            remaining_ms: 0,
+            frequency: 0,
+            state: PlayerState::Stopped,
+        }
+    }
+
+    pub fn start(&mut self, song: Song) -> Output {
+        self.length = song.len();
+        self.cursor = Some(song.into());
+        self.position = 0;
+        self.state = PlayerState::Playing;
+        self.load_note()
+    }
+
+    pub fn stop(&mut self) -> Output {
+        self.cursor = None;
+        self.state = PlayerState::Stopped;
+        Output::Silence
+    }
+
+    pub fn pause(&mut self) -> Output {
+        match self.state {
+            PlayerState::Playing => {
+                self.state = PlayerState::Paused;
+                Output::Silence
+            }
+            _ => Output::Unchanged,
+        }
+    }
+
+    pub fn resume(&mut self) -> Output {
+        match self.state {
+            PlayerState::Paused => {
+                self.state = PlayerState::Playing;
+                self.current_output()
+            }
+            _ => Output::Unchanged,
+        }
+    }
+
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    /// Number of notes started so far and the total number of notes
+    pub fn progress(&self) -> (usize, usize) {
+        (self.position, self.length)
+    }
+
+    /// Advances the song by `elapsed_ms`, called from the timer interrupt
+    pub fn tick(&mut self, elapsed_ms: u32) -> Output {
+        if self.state != PlayerState::Playing {
+            return Output::Unchanged;
+        }
+
+        let mut elapsed_ms = elapsed_ms;
+        let mut output = Output::Unchanged;
+        while elapsed_ms >= self.remaining_ms && self.state == PlayerState::Playing {
+            elapsed_ms -= self.remaining_ms;
+            self.position += 1;
+            output = self.load_note();
+        }
+        if self.state == PlayerState::Playing {
+            self.remaining_ms -= elapsed_ms;
+        }
+        output
+    }
+
+    fn load_note(&mut self) -> Output {
+        match self.cursor.as_mut().and_then(Iterator::next) {
+            Some((note, duration_ms)) => {
+                self.frequency = note.frequency();
+                self.remaining_ms = duration_ms;
+                self.current_output()
+            }
+            None => self.stop(),
+        }
+    }
+
+    fn current_output(&self) -> Output {
+        match self.frequency {
+            0 => Output::Silence,
+            freq => Output::Play(freq),
+        }
+    }
+}
+
+pub struct Tone<P: Pwm> {
+    pwm: P,
+    channel: P::Channel,
+    period: fn(u32) -> P::Time,
+    sequencer: Sequencer,
+}
+
+impl<P> Tone<P>
+where
+    P: Pwm<Duty = u16>,
+    P::Channel: Copy,
+{
+    /// `period` turns a frequency in Hz into the time unit of the timer
+    pub fn new(mut pwm: P, channel: P::Channel, period: fn(u32) -> P::Time) -> Self {
+        pwm.set_duty(channel, pwm.get_max_duty() / 2);
+        Self {
+            pwm,
+            channel,
+            period,
+            sequencer: Sequencer::new(),
+        }
+    }
+
+    pub fn play(&mut self, song: Song) {
+        let output = self.sequencer.start(song);
+        self.apply(output);
+    }
+
+    pub fn stop(&mut self) {
+        let output = self.sequencer.stop();
+        self.apply(output);
+    }
+
+    pub fn pause(&mut self) {
+        let output = self.sequencer.pause();
+        self.apply(output);
+    }
+
+    pub fn resume(&mut self) {
+        let output = self.sequencer.resume();
+        self.apply(output);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.sequencer.state() != PlayerState::Stopped
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        self.sequencer.progress()
+    }
+
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        let output = self.sequencer.tick(elapsed_ms);
+        self.apply(output);
+    }
+
+    fn apply(&mut self, output: Output) {
+        match output {
+            Output::Play(freq) => {
+                self.pwm.set_period((self.period)(freq));
+                self.pwm.enable(self.channel);
+            }
+            Output::Silence => self.pwm.disable(self.channel),
+            Output::Unchanged => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::Pitch;
+
+    /// Timer output recording what the player last set
+    #[derive(Default)]
+    struct FakePwm {
+        period: u32,
+        duty: u16,
+        enabled: bool,
+    }
+
+    impl Pwm for FakePwm {
+        type Channel = ();
+        type Time = u32;
+        type Duty = u16;
+
+        fn disable(&mut self, _: ()) {
+            self.enabled = false;
+        }
+
+        fn enable(&mut self, _: ()) {
+            self.enabled = true;
+        }
+
+        fn get_period(&self) -> u32 {
+            self.period
+        }
+
+        fn get_duty(&self, _: ()) -> u16 {
+            self.duty
+        }
+
+        fn get_max_duty(&self) -> u16 {
+            1000
+        }
+
+        fn set_duty(&mut self, _: (), duty: u16) {
+            self.duty = duty;
+        }
+
+        fn set_period<P: Into<u32>>(&mut self, period: P) {
+            self.period = period.into();
+        }
+    }
+
+    fn tone() -> Tone<FakePwm> {
+        Tone::new(FakePwm::default(), (), |freq| freq)
+    }
+
+    // 600 beats per minute, a quarter lasts 100 ms
+    const SONG: &str = "t:d=4,o=5,b=600:c,p,8e,2a4";
+
+    fn output(tone: &Tone<FakePwm>) -> Option<u32> {
+        Some(tone.pwm.period).filter(|_| tone.pwm.enabled)
+    }
+
+    #[test]
+    fn rtttl_song_plays_note_by_note() {
+        let mut tone = tone();
+        assert_eq!(tone.pwm.duty, 500);
+        tone.play(Song::Rtttl(Rtttl::parse(SONG).unwrap()));
+        assert_eq!(output(&tone), Some(523));
+        assert_eq!(tone.progress(), (0, 4));
+
+        tone.tick(90);
+        assert_eq!(output(&tone), Some(523));
+        tone.tick(10);
+        assert_eq!(output(&tone), None);
+        tone.tick(100);
+        assert_eq!(output(&tone), Some(659));
+        assert_eq!(tone.progress(), (2, 4));
+        tone.tick(50);
+        assert_eq!(output(&tone), Some(440));
+        tone.tick(199);
+        assert!(tone.is_playing());
+        tone.tick(1);
+        assert_eq!(output(&tone), None);
+        assert!(!tone.is_playing());
+    }
+
+    #[test]
+    fn long_tick_skips_notes() {
+        let mut tone = tone();
+        tone.play(Song::Rtttl(Rtttl::parse(SONG).unwrap()));
+        tone.tick(260);
+        assert_eq!(output(&tone), Some(440));
+        assert_eq!(tone.progress(), (3, 4));
+    }
+
+    #[test]
+    fn pause_keeps_the_place() {
+        let mut tone = tone();
+        tone.play(Song::Melody(Melody::from_rtttl(
+            &Rtttl::parse(SONG).unwrap(),
+        )));
+        tone.tick(60);
+        tone.pause();
+        assert_eq!(output(&tone), None);
+        tone.tick(1000);
+        tone.resume();
+        assert_eq!(output(&tone), Some(523));
+        tone.tick(40);
+        assert_eq!(output(&tone), None);
+
+        tone.stop();
+        tone.resume();
+        assert!(!tone.is_playing());
+    }
+
+    #[test]
+    fn beats_last_a_tempo_step_each() {
+        static BEATS: [(Note, u32); 2] = [(Note::new(Pitch::A, 4), 2), (Note::Rest, 1)];
+        let mut tone = tone();
+        tone.play(Song::Beats(&BEATS));
+        assert_eq!(output(&tone), Some(440));
+        tone.tick(2 * TEMPO_MS - 1);
+        assert_eq!(output(&tone), Some(440));
+        tone.tick(1);
+        assert_eq!(output(&tone), None);
+        tone.tick(TEMPO_MS);
+        assert!(!tone.is_playing());
+    }
+}
@@ -0,0 +1,211 @@
+//! Geometry of the analog clock face in fixed point, the Cortex-M3 has no FPU.
+//!
+//! Angles are given in tenths of a minute of arc on the dial, so 3600 make a
+//! full turn and every second moves the minute hand by one. They run
+//! clockwise from 12 o'clock in screen coordinates, where y grows downwards.
+//! Sine and cosine come from a table of whole minute steps with linear
+//! interpolation in between, which is off by less than a fifth of a pixel at
+//! the radius of the 128 pixel wide panel.
+
+#![no_std]
+
+/// Angle of a full turn
+pub const TURN: u32 = 3600;
+/// Fixed point one of `sin` and `cos`
+pub const ONE: i32 = 1 << 14;
+
+// Angle between the table entries, one minute on the dial
+const STEP: u32 = TURN / 60;
+// sin of 0 to 90 degrees in steps of 6 degrees
+const SIN: [i32; 16] = [
+    0, 1713, 3406, 5063, 6664, 8192, 9630, 10963, 12176, 13255, 14189, 14968, 15582, 16026, 16294,
+    16384,
+];
+
+// Between 0 and a quarter turn inclusive
+fn quarter_sin(angle: u32) -> i32 {
+    let idx = (angle / STEP) as usize;
+    let rest = (angle % STEP) as i32;
+    if rest == 0 {
+        return SIN[idx];
+    }
+    SIN[idx] + (SIN[idx + 1] - SIN[idx]) * rest / STEP as i32
+}
+
+/// Sine scaled by `ONE`
+pub fn sin(angle: u32) -> i32 {
+    let angle = angle % TURN;
+    let quarter = TURN / 4;
+    match angle / quarter {
+        0 => quarter_sin(angle),
+        1 => quarter_sin(2 * quarter - angle),
+        2 => -quarter_sin(angle - 2 * quarter),
+        _ => -quarter_sin(TURN - angle),
+    }
+}
+
+/// Cosine scaled by `ONE`
+pub fn cos(angle: u32) -> i32 {
+    sin(angle + TURN / 4)
+}
+
+/// Point at `radius` from `center` in the direction of `angle`, rounded to
+/// the nearest pixel
+pub fn polar(center: (i32, i32), radius: i32, angle: u32) -> (i32, i32) {
+    let scale = |value: i32| {
+        let scaled = radius * value;
+        // Round half away from zero
+        (scaled + scaled.signum() * ONE / 2) / ONE
+    };
+    (center.0 + scale(sin(angle)), center.1 - scale(cos(angle)))
+}
+
+/// A straight line from `start` to `end`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Segment {
+    pub start: (i32, i32),
+    pub end: (i32, i32),
+}
+
+/// The hour, minute and second hand
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Hands {
+    pub hour: Segment,
+    pub minute: Segment,
+    pub second: Segment,
+}
+
+// Hand lengths in percent of the radius, the second hand sticks out behind
+// the center a bit
+const HOUR_LEN: i32 = 50;
+const MINUTE_LEN: i32 = 80;
+const SECOND_LEN: i32 = 90;
+const SECOND_TAIL: i32 = 15;
+
+/// Angles of the hour, minute and second hand. The hour and minute hands
+/// move on with the minutes and seconds rather than jumping.
+pub fn angles(hours: u8, minutes: u8, seconds: u8) -> (u32, u32, u32) {
+    let (hours, minutes, seconds) = (hours as u32 % 12, minutes as u32, seconds as u32);
+    (
+        hours * TURN / 12 + minutes * TURN / 720 + seconds * TURN / 43200,
+        minutes * STEP + seconds,
+        seconds * STEP,
+    )
+}
+
+/// Hands of a dial of `radius` around `center` showing the time
+pub fn hands(center: (i32, i32), radius: i32, hours: u8, minutes: u8, seconds: u8) -> Hands {
+    let (hour, minute, second) = angles(hours, minutes, seconds);
+    let hand = |angle: u32, len: i32| Segment {
+        start: center,
+        end: polar(center, radius * len / 100, angle),
+    };
+    Hands {
+        hour: hand(hour, HOUR_LEN),
+        minute: hand(minute, MINUTE_LEN),
+        second: Segment {
+            start: polar(center, radius * SECOND_TAIL / 100, second + TURN / 2),
+            end: polar(center, radius * SECOND_LEN / 100, second),
+        },
+    }
+}
+
+/// Marks of the twelve hours at the rim, pointing inwards by `len`. The
+/// quarters are twice as long.
+pub fn ticks(center: (i32, i32), radius: i32, len: i32) -> [Segment; 12] {
+    let mut ticks = [Segment {
+        start: center,
+        end: center,
+    }; 12];
+    for (hour, tick) in ticks.iter_mut().enumerate() {
+        let angle = hour as u32 * TURN / 12;
+        let inner = if hour % 3 == 0 {
+            radius - 2 * len
+        } else {
+            radius - len
+        };
+        *tick = Segment {
+            start: polar(center, inner, angle),
+            end: polar(center, radius, angle),
+        };
+    }
+    ticks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: (i32, i32) = (64, 90);
+
+    #[test]
+    fn sine_matches_the_quadrants() {
+        assert_eq!(sin(0), 0);
+        assert_eq!(sin(TURN / 4), ONE);
+        assert_eq!(sin(TURN / 2), 0);
+        assert_eq!(sin(TURN * 3 / 4), -ONE);
+        assert_eq!(cos(0), ONE);
+        assert_eq!(cos(TURN / 2), -ONE);
+        assert_eq!(sin(TURN + 300), sin(300));
+        assert_eq!(sin(TURN / 2 + 300), -sin(300));
+    }
+
+    #[test]
+    fn interpolation_stays_close() {
+        for angle in 0..TURN {
+            let radians = angle as f64 * core::f64::consts::PI * 2.0 / TURN as f64;
+            let exact = taylor_sin(radians) * ONE as f64;
+            // Under a tenth of a pixel at a radius of 60
+            assert!((sin(angle) as f64 - exact).abs() < 24.0, "angle {}", angle);
+        }
+    }
+
+    // The crate is no_std, the reference is summed up here
+    fn taylor_sin(x: f64) -> f64 {
+        let mut term = x;
+        let mut sum = x;
+        for n in 1..20 {
+            term *= -x * x / ((2 * n) * (2 * n + 1)) as f64;
+            sum += term;
+        }
+        sum
+    }
+
+    #[test]
+    fn hands_point_at_the_time() {
+        // 3:00:00, hour hand to the right, minute hand up
+        let three = hands(CENTER, 60, 3, 0, 0);
+        assert_eq!(three.hour.start, CENTER);
+        assert_eq!(three.hour.end, (94, 90));
+        assert_eq!(three.minute.end, (64, 42));
+        assert_eq!(three.second.end, (64, 36));
+        assert_eq!(three.second.start, (64, 99));
+
+        // 6:30:45, hour hand half way between 6 and 7, second hand at 9
+        let later = hands(CENTER, 60, 18, 30, 45);
+        assert_eq!(later.hour.end, (56, 119));
+        assert_eq!(later.minute.end, (60, 138));
+        assert_eq!(later.second.end, (10, 90));
+        assert_eq!(later.second.start, (73, 90));
+    }
+
+    #[test]
+    fn hour_hand_moves_with_the_minutes() {
+        let (at_nine, _, _) = angles(9, 0, 0);
+        let (half_past, _, _) = angles(21, 30, 0);
+        assert_eq!(at_nine, TURN * 3 / 4);
+        assert_eq!(half_past - at_nine, TURN / 24);
+        let (_, minute, _) = angles(0, 15, 30);
+        assert_eq!(minute, TURN / 4 + TURN / 120);
+    }
+
+    #[test]
+    fn quarter_ticks_are_longer() {
+        let ticks = ticks(CENTER, 60, 4);
+        assert_eq!(ticks[0].start, (64, 38));
+        assert_eq!(ticks[0].end, (64, 30));
+        assert_eq!(ticks[1].start, (92, 42));
+        assert_eq!(ticks[3].end, (124, 90));
+        assert_eq!(ticks[6].end, (64, 150));
+    }
+}
@@ -0,0 +1,105 @@
+//! In-memory draw target to render the GUI on the PC and compare it against
+//! reference images.
+//!
+//! The references are binary PPM files committed next to the tests, a missing
+//! one fails the test. `UPDATE_SNAPSHOTS=1` writes all of them after an
+//! intended change of the GUI, to be looked at and committed. On a mismatch
+//! the new frame is left as `<name>.new.ppm` to compare with the reference.
+
+use embedded_graphics::{
+    pixelcolor::{Rgb565, Rgb888},
+    prelude::*,
+};
+use std::{env, fs, path::Path};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FrameBuffer {
+    size: Size,
+    pixels: Vec<Rgb565>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            size: Size::new(width, height),
+            pixels: vec![Rgb565::BLACK; (width * height) as usize],
+        }
+    }
+
+    pub fn pixel(&self, point: Point) -> Option<Rgb565> {
+        self.index(point).map(|idx| self.pixels[idx])
+    }
+
+    /// Pixels of `color` in the `width` by `height` area at `top_left`
+    pub fn count(&self, top_left: Point, width: u32, height: u32, color: Rgb565) -> usize {
+        let mut count = 0;
+        for y in top_left.y..top_left.y + height as i32 {
+            for x in top_left.x..top_left.x + width as i32 {
+                if self.pixel(Point::new(x, y)) == Some(color) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// The content as a binary PPM (P6) image
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", self.size.width, self.size.height).into_bytes();
+        for pixel in self.pixels.iter() {
+            let rgb = Rgb888::from(*pixel);
+            ppm.extend_from_slice(&[rgb.r(), rgb.g(), rgb.b()]);
+        }
+        ppm
+    }
+
+    /// Compares the content with the reference image `dir/name.ppm`
+    pub fn assert_snapshot(&self, dir: &Path, name: &str) {
+        let path = dir.join(format!("{}.ppm", name));
+        let actual = self.to_ppm();
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(&path, &actual).unwrap();
+            eprintln!("Wrote {}", path.display());
+            return;
+        }
+        let expected = fs::read(&path)
+            .unwrap_or_else(|_| panic!("{} is missing, see UPDATE_SNAPSHOTS", path.display()));
+        if expected != actual {
+            let new = dir.join(format!("{}.new.ppm", name));
+            fs::write(&new, &actual).unwrap();
+            panic!("{} differs from {}", new.display(), path.display());
+        }
+    }
+
+    fn index(&self, point: Point) -> Option<usize> {
+        let (width, height) = (self.size.width as i32, self.size.height as i32);
+        if point.x < 0 || point.y < 0 || point.x >= width || point.y >= height {
+            return None;
+        }
+        Some((point.y * width + point.x) as usize)
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some(idx) = self.index(point) {
+                self.pixels[idx] = color;
+            }
+        }
+        Ok(())
+    }
+}
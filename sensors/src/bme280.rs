@@ -0,0 +1,260 @@
+//! Bosch BME280, the BMP280 with a humidity sensor
+
+use crate::bmp280::Calibration;
+use crate::{Capabilities, Error, Measurement, Sensor};
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Write, WriteRead},
+};
+
+pub use crate::bmp280::{PRIMARY_ADDRESS, SECONDARY_ADDRESS};
+
+const CHIP_ID: u8 = 0x60;
+
+const REG_CALIBRATION: u8 = 0x88;
+const REG_CALIBRATION_H1: u8 = 0xa1;
+const REG_CALIBRATION_H2: u8 = 0xe1;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_RESET: u8 = 0xe0;
+const REG_CTRL_HUM: u8 = 0xf2;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_DATA: u8 = 0xf7;
+
+const RESET: u8 = 0xb6;
+/// Humidity oversampling x1, only applied by the next write of CTRL_MEAS
+const CTRL_HUM: u8 = 0b001;
+/// Temperature and pressure oversampling x1 in forced mode
+const CTRL_MEAS_FORCED: u8 = (0b001 << 5) | (0b001 << 2) | 0b01;
+// Worst case times from the datasheet
+const STARTUP_MS: u8 = 2;
+const MEASURE_MS: u8 = 10;
+
+/// Trimming parameters of the humidity
+#[derive(Copy, Clone, Debug)]
+struct HumidityCalibration {
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl HumidityCalibration {
+    /// `h1` from 0xA1 and the 7 bytes from 0xE1, H4 and H5 share a nibble
+    fn from_bytes(h1: u8, data: &[u8; 7]) -> Self {
+        Self {
+            h1,
+            h2: i16::from_le_bytes([data[0], data[1]]),
+            h3: data[2],
+            h4: ((data[3] as i8 as i16) << 4) | (data[4] & 0x0f) as i16,
+            h5: ((data[5] as i8 as i16) << 4) | (data[4] >> 4) as i16,
+            h6: data[6] as i8,
+        }
+    }
+
+    /// Relative humidity in percent as Q22.10, the integer formula of the
+    /// datasheet
+    fn humidity(&self, adc: i32, fine: i32) -> u32 {
+        let (h1, h2, h3) = (self.h1 as i32, self.h2 as i32, self.h3 as i32);
+        let (h4, h5, h6) = (self.h4 as i32, self.h5 as i32, self.h6 as i32);
+        let x = fine - 76800;
+        let x = ((((adc << 14) - (h4 << 20) - (h5 * x)) + 16384) >> 15)
+            * (((((((x * h6) >> 10) * (((x * h3) >> 11) + 32768)) >> 10) + 2_097_152) * h2 + 8192)
+                >> 14);
+        let x = x - (((((x >> 15) * (x >> 15)) >> 7) * h1) >> 4);
+        (x.clamp(0, 419_430_400) >> 12) as u32
+    }
+}
+
+pub struct Bme280<I2C> {
+    i2c: I2C,
+    address: u8,
+    calibration: Option<(Calibration, HumidityCalibration)>,
+}
+
+impl<I2C, E> Bme280<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            calibration: None,
+        }
+    }
+
+    pub fn new_primary(i2c: I2C) -> Self {
+        Self::new(i2c, PRIMARY_ADDRESS)
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.i2c
+            .write_read(self.address, &[register], buffer)
+            .map_err(Error::Bus)
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .map_err(Error::Bus)
+    }
+}
+
+impl<I2C, E> Sensor for Bme280<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            temperature: true,
+            humidity: true,
+            pressure: true,
+        }
+    }
+
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        self.calibration = None;
+        let mut id = [0u8];
+        self.read(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(Error::UnknownChip(id[0]));
+        }
+        self.write(REG_RESET, RESET)?;
+        delay.delay_ms(STARTUP_MS);
+
+        let mut data = [0u8; 24];
+        self.read(REG_CALIBRATION, &mut data)?;
+        let mut h1 = [0u8];
+        self.read(REG_CALIBRATION_H1, &mut h1)?;
+        let mut humidity = [0u8; 7];
+        self.read(REG_CALIBRATION_H2, &mut humidity)?;
+        self.calibration = Some((
+            Calibration::from_bytes(&data),
+            HumidityCalibration::from_bytes(h1[0], &humidity),
+        ));
+        Ok(())
+    }
+
+    fn measure<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<Measurement, Error<E>> {
+        let (calibration, humidity) = self.calibration.ok_or(Error::Uninitialized)?;
+        self.write(REG_CTRL_HUM, CTRL_HUM)?;
+        self.write(REG_CTRL_MEAS, CTRL_MEAS_FORCED)?;
+        delay.delay_ms(MEASURE_MS);
+
+        // Pressure and temperature as 20 bit values left aligned in 3 bytes,
+        // then the 16 bit humidity
+        let mut data = [0u8; 8];
+        self.read(REG_DATA, &mut data)?;
+        let adc = |at: usize| {
+            ((data[at] as i32) << 12) | ((data[at + 1] as i32) << 4) | ((data[at + 2] as i32) >> 4)
+        };
+        let (temperature, fine) = calibration.temperature(adc(3));
+        let pressure = calibration.pressure(adc(0), fine);
+        let humidity = humidity.humidity(((data[6] as i32) << 8) | data[7] as i32, fine);
+        Ok(Measurement {
+            temperature: Some(temperature as f32 / 100.0),
+            humidity: Some(humidity as f32 / 1024.0),
+            pressure: Some(pressure as f32 / 256.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bus, NoDelay, Transaction};
+
+    const ADDRESS: u8 = PRIMARY_ADDRESS;
+
+    // Temperature and pressure trimming of the compensation example in the
+    // BMP280 datasheet
+    const CALIBRATION: [u8; 24] = [
+        0x70, 0x6b, 0x43, 0x67, 0x18, 0xfc, 0x7d, 0x8e, 0x43, 0xd6, 0xd0, 0x0b, 0x27, 0x0b, 0x8c,
+        0x00, 0xf9, 0xff, 0x8c, 0x3c, 0xf8, 0xc6, 0x70, 0x17,
+    ];
+    // Humidity trimming of a chip: H1 75, H2 362, H3 0, H4 313, H5 50, H6 30
+    const H1: u8 = 75;
+    const HUMIDITY_CALIBRATION: [u8; 7] = [0x6a, 0x01, 0x00, 0x13, 0x29, 0x03, 0x1e];
+    // Fine temperature of 25.08 degrees in the example
+    const FINE: i32 = 128_422;
+
+    // The floating point formula of the datasheet
+    fn reference(adc: f64) -> f64 {
+        let (h1, h2, h3, h4, h5, h6) = (75.0, 362.0, 0.0, 313.0, 50.0, 30.0);
+        let x = FINE as f64 - 76800.0;
+        let x = (adc - (h4 * 64.0 + h5 / 16384.0 * x))
+            * (h2 / 65536.0 * (1.0 + h6 / 67_108_864.0 * x * (1.0 + h3 / 67_108_864.0 * x)));
+        (x * (1.0 - h1 * x / 524_288.0)).clamp(0.0, 100.0)
+    }
+
+    fn humidity(adc: i32) -> f64 {
+        let calibration = HumidityCalibration::from_bytes(H1, &HUMIDITY_CALIBRATION);
+        calibration.humidity(adc, FINE) as f64 / 1024.0
+    }
+
+    #[test]
+    fn splits_the_shared_nibble() {
+        let calibration = HumidityCalibration::from_bytes(H1, &HUMIDITY_CALIBRATION);
+        assert_eq!(calibration.h2, 362);
+        assert_eq!(calibration.h4, 313);
+        assert_eq!(calibration.h5, 50);
+        assert_eq!(calibration.h6, 30);
+        // H4 and H5 are signed 12 bit values
+        let negative = HumidityCalibration::from_bytes(H1, &[0, 0, 0, 0xff, 0xff, 0xff, 0]);
+        assert_eq!((negative.h4, negative.h5), (-1, -1));
+    }
+
+    #[test]
+    fn humidity_follows_the_datasheet() {
+        for adc in [0x6000, 0x6e2d, 0x7000, 0x8000].iter() {
+            let expected = reference(*adc as f64);
+            assert!((humidity(*adc) - expected).abs() < 0.01, "{}", adc);
+        }
+        // Clamped to 0 and 100 %
+        assert_eq!(humidity(0), 0.0);
+        assert_eq!(humidity(0xffff), 100.0);
+    }
+
+    #[test]
+    fn measures_all_three() {
+        let bus = Bus::new(&[
+            Transaction::WriteRead(ADDRESS, &[REG_CHIP_ID], &[CHIP_ID]),
+            Transaction::Write(ADDRESS, &[REG_RESET, RESET]),
+            Transaction::WriteRead(ADDRESS, &[REG_CALIBRATION], &CALIBRATION),
+            Transaction::WriteRead(ADDRESS, &[REG_CALIBRATION_H1], &[H1]),
+            Transaction::WriteRead(ADDRESS, &[REG_CALIBRATION_H2], &HUMIDITY_CALIBRATION),
+            Transaction::Write(ADDRESS, &[REG_CTRL_HUM, CTRL_HUM]),
+            Transaction::Write(ADDRESS, &[REG_CTRL_MEAS, CTRL_MEAS_FORCED]),
+            // Raw pressure 415148, temperature 519888 and humidity 0x6e2d
+            Transaction::WriteRead(
+                ADDRESS,
+                &[REG_DATA],
+                &[0x65, 0x5a, 0xc0, 0x7e, 0xed, 0x00, 0x6e, 0x2d],
+            ),
+        ]);
+        let mut sensor = Bme280::new_primary(bus);
+        sensor.init(&mut NoDelay).unwrap();
+        let measurement = sensor.measure(&mut NoDelay).unwrap();
+        sensor.release().done();
+
+        assert_eq!(measurement.temperature, Some(25.08));
+        assert!((measurement.pressure.unwrap() - 100653.27).abs() < 0.05);
+        assert!((measurement.humidity.unwrap() - 45.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rejects_the_bmp280() {
+        let bus = Bus::new(&[Transaction::WriteRead(ADDRESS, &[REG_CHIP_ID], &[0x58])]);
+        let mut sensor = Bme280::new_primary(bus);
+        assert_eq!(sensor.init(&mut NoDelay), Err(Error::UnknownChip(0x58)));
+        assert_eq!(sensor.measure(&mut NoDelay), Err(Error::Uninitialized));
+    }
+}
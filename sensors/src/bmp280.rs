@@ -0,0 +1,240 @@
+//! Bosch BMP280 temperature and pressure sensor, the BME280 without humidity
+
+use crate::{Capabilities, Error, Measurement, Sensor};
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Write, WriteRead},
+};
+
+/// SDO pin low
+pub const PRIMARY_ADDRESS: u8 = 0x76;
+/// SDO pin high
+pub const SECONDARY_ADDRESS: u8 = 0x77;
+
+const CHIP_ID: u8 = 0x58;
+
+const REG_CALIBRATION: u8 = 0x88;
+const REG_CHIP_ID: u8 = 0xd0;
+const REG_RESET: u8 = 0xe0;
+const REG_CTRL_MEAS: u8 = 0xf4;
+const REG_DATA: u8 = 0xf7;
+
+const RESET: u8 = 0xb6;
+/// Temperature and pressure oversampling x1 in forced mode
+const CTRL_MEAS_FORCED: u8 = (0b001 << 5) | (0b001 << 2) | 0b01;
+// Worst case times from the datasheet
+const STARTUP_MS: u8 = 2;
+const MEASURE_MS: u8 = 7;
+
+/// Trimming parameters read from the chip, the BME280 has the same ones
+/// for temperature and pressure
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p: [i16; 8],
+}
+
+impl Calibration {
+    pub(crate) fn from_bytes(data: &[u8; 24]) -> Self {
+        let word = |idx: usize| [data[idx * 2], data[idx * 2 + 1]];
+        let mut p = [0i16; 8];
+        for (idx, value) in p.iter_mut().enumerate() {
+            *value = i16::from_le_bytes(word(4 + idx));
+        }
+        Self {
+            t1: u16::from_le_bytes(word(0)),
+            t2: i16::from_le_bytes(word(1)),
+            t3: i16::from_le_bytes(word(2)),
+            p1: u16::from_le_bytes(word(3)),
+            p,
+        }
+    }
+
+    /// Temperature in hundredths of a degree and the fine resolution value
+    /// the pressure compensation needs, the integer formulas of the datasheet
+    pub(crate) fn temperature(&self, adc: i32) -> (i32, i32) {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc >> 4) - t1) * ((adc >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        let fine = var1 + var2;
+        ((fine * 5 + 128) >> 8, fine)
+    }
+
+    /// Pressure in Pascal as Q24.8
+    pub(crate) fn pressure(&self, adc: i32, fine: i32) -> u32 {
+        let [p2, p3, p4, p5, p6, p7, p8, p9] = self.p;
+        let mut var1 = fine as i64 - 128000;
+        let mut var2 = var1 * var1 * p6 as i64;
+        var2 += (var1 * p5 as i64) << 17;
+        var2 += (p4 as i64) << 35;
+        var1 = ((var1 * var1 * p3 as i64) >> 8) + ((var1 * p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        if var1 == 0 {
+            return 0;
+        }
+        let mut p = 1_048_576 - adc as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        let var1 = (p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        let var2 = (p8 as i64 * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((p7 as i64) << 4)) as u32
+    }
+}
+
+pub struct Bmp280<I2C> {
+    i2c: I2C,
+    address: u8,
+    calibration: Option<Calibration>,
+}
+
+impl<I2C, E> Bmp280<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self {
+            i2c,
+            address,
+            calibration: None,
+        }
+    }
+
+    pub fn new_primary(i2c: I2C) -> Self {
+        Self::new(i2c, PRIMARY_ADDRESS)
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    fn read(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        self.i2c
+            .write_read(self.address, &[register], buffer)
+            .map_err(Error::Bus)
+    }
+
+    fn write(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .map_err(Error::Bus)
+    }
+}
+
+impl<I2C, E> Sensor for Bmp280<I2C>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            temperature: true,
+            humidity: false,
+            pressure: true,
+        }
+    }
+
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        self.calibration = None;
+        let mut id = [0u8];
+        self.read(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(Error::UnknownChip(id[0]));
+        }
+        self.write(REG_RESET, RESET)?;
+        delay.delay_ms(STARTUP_MS);
+
+        let mut data = [0u8; 24];
+        self.read(REG_CALIBRATION, &mut data)?;
+        self.calibration = Some(Calibration::from_bytes(&data));
+        Ok(())
+    }
+
+    fn measure<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<Measurement, Error<E>> {
+        let calibration = self.calibration.ok_or(Error::Uninitialized)?;
+        self.write(REG_CTRL_MEAS, CTRL_MEAS_FORCED)?;
+        delay.delay_ms(MEASURE_MS);
+
+        // Pressure then temperature, 20 bit values left aligned in 3 bytes
+        let mut data = [0u8; 6];
+        self.read(REG_DATA, &mut data)?;
+        let adc = |at: usize| {
+            ((data[at] as i32) << 12) | ((data[at + 1] as i32) << 4) | ((data[at + 2] as i32) >> 4)
+        };
+        let (temperature, fine) = calibration.temperature(adc(3));
+        let pressure = calibration.pressure(adc(0), fine);
+        Ok(Measurement {
+            temperature: Some(temperature as f32 / 100.0),
+            humidity: None,
+            pressure: Some(pressure as f32 / 256.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bus, NoDelay, Transaction};
+
+    const ADDRESS: u8 = PRIMARY_ADDRESS;
+
+    // Trimming parameters of the compensation example in the datasheet
+    const CALIBRATION: [u8; 24] = [
+        0x70, 0x6b, // t1 27504
+        0x43, 0x67, // t2 26435
+        0x18, 0xfc, // t3 -1000
+        0x7d, 0x8e, // p1 36477
+        0x43, 0xd6, // p2 -10685
+        0xd0, 0x0b, // p3 3024
+        0x27, 0x0b, // p4 2855
+        0x8c, 0x00, // p5 140
+        0xf9, 0xff, // p6 -7
+        0x8c, 0x3c, // p7 15500
+        0xf8, 0xc6, // p8 -14600
+        0x70, 0x17, // p9 6000
+    ];
+
+    // Raw values 415148 and 519888 of the same example
+    const DATA: [u8; 6] = [0x65, 0x5a, 0xc0, 0x7e, 0xed, 0x00];
+
+    #[test]
+    fn compensates_like_the_datasheet() {
+        let calibration = Calibration::from_bytes(&CALIBRATION);
+        let (temperature, fine) = calibration.temperature(519888);
+        assert_eq!(temperature, 2508);
+        // The datasheet gives 100653.27 Pa, the integer formula is a few
+        // 1/256 Pa off that
+        let pressure = calibration.pressure(415148, fine) as f32 / 256.0;
+        assert!((pressure - 100653.27).abs() < 0.05);
+    }
+
+    #[test]
+    fn measures_temperature_and_pressure() {
+        let bus = Bus::new(&[
+            Transaction::WriteRead(ADDRESS, &[REG_CHIP_ID], &[CHIP_ID]),
+            Transaction::Write(ADDRESS, &[REG_RESET, RESET]),
+            Transaction::WriteRead(ADDRESS, &[REG_CALIBRATION], &CALIBRATION),
+            Transaction::Write(ADDRESS, &[REG_CTRL_MEAS, CTRL_MEAS_FORCED]),
+            Transaction::WriteRead(ADDRESS, &[REG_DATA], &DATA),
+        ]);
+        let mut sensor = Bmp280::new_primary(bus);
+        sensor.init(&mut NoDelay).unwrap();
+        let measurement = sensor.measure(&mut NoDelay).unwrap();
+        sensor.release().done();
+
+        assert_eq!(measurement.temperature, Some(25.08));
+        assert!((measurement.pressure.unwrap() - 100653.27).abs() < 0.05);
+        assert_eq!(measurement.humidity, None);
+    }
+
+    #[test]
+    fn rejects_other_chips() {
+        // The BME280 answers with 0x60
+        let bus = Bus::new(&[Transaction::WriteRead(ADDRESS, &[REG_CHIP_ID], &[0x60])]);
+        let mut sensor = Bmp280::new_primary(bus);
+        assert_eq!(sensor.init(&mut NoDelay), Err(Error::UnknownChip(0x60)));
+        assert_eq!(sensor.measure(&mut NoDelay), Err(Error::Uninitialized));
+    }
+}
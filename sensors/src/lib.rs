@@ -0,0 +1,69 @@
+//! Environmental sensors behind one trait, so boards with different parts
+//! share the GUI and history code.
+//!
+//! Drivers for the SHT3x, BMP280 and BME280 live here.
+
+#![no_std]
+
+pub mod bme280;
+pub mod bmp280;
+#[cfg(test)]
+mod mock;
+pub mod sht3x;
+
+use embedded_hal::blocking::delay::DelayMs;
+use ufmt::{uDebug, uWrite, Formatter};
+
+/// Quantities a sensor measures
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Capabilities {
+    pub temperature: bool,
+    pub humidity: bool,
+    pub pressure: bool,
+}
+
+/// One reading, `None` for what the sensor does not measure
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Measurement {
+    /// Degree Celsius
+    pub temperature: Option<f32>,
+    /// Relative humidity in percent
+    pub humidity: Option<f32>,
+    /// Pascal
+    pub pressure: Option<f32>,
+}
+
+pub trait Sensor {
+    type Error: uDebug;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Resets and configures the sensor, called again to recover from errors
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Self::Error>;
+
+    fn measure<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<Measurement, Self::Error>;
+}
+
+/// Errors of the drivers in this crate
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error<E> {
+    Bus(E),
+    /// A checksum of the received data did not match
+    Crc,
+    /// The chip id is not the one of the expected part
+    UnknownChip(u8),
+    /// `measure` was called before a successful `init`
+    Uninitialized,
+}
+
+impl<E> uDebug for Error<E> {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        // The bus errors of the HALs have no ufmt support
+        f.write_str(match self {
+            Error::Bus(_) => "Bus",
+            Error::Crc => "Crc",
+            Error::UnknownChip(_) => "UnknownChip",
+            Error::Uninitialized => "Uninitialized",
+        })
+    }
+}
@@ -0,0 +1,97 @@
+//! Scripted I2C bus for testing the drivers on the host
+
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Read, Write, WriteRead},
+};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Transaction {
+    Write(u8, &'static [u8]),
+    Read(u8, &'static [u8]),
+    /// Register address written and the bytes read back
+    WriteRead(u8, &'static [u8], &'static [u8]),
+    /// The device does not acknowledge the next transaction
+    Nack(u8),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Nack;
+
+/// Panics on any transaction that differs from the script
+pub struct Bus {
+    script: &'static [Transaction],
+    next: usize,
+}
+
+impl Bus {
+    pub fn new(script: &'static [Transaction]) -> Self {
+        Self { script, next: 0 }
+    }
+
+    /// Checks the whole script was played
+    pub fn done(&self) {
+        assert_eq!(self.next, self.script.len(), "transactions left over");
+    }
+
+    fn next(&mut self) -> Transaction {
+        let transaction = *self
+            .script
+            .get(self.next)
+            .expect("more transactions than scripted");
+        self.next += 1;
+        transaction
+    }
+}
+
+impl Write for Bus {
+    type Error = Nack;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Nack> {
+        match self.next() {
+            Transaction::Write(expected, data) if expected == address => {
+                assert_eq!(bytes, data);
+                Ok(())
+            }
+            Transaction::Nack(expected) if expected == address => Err(Nack),
+            other => panic!("unexpected write of {:?}, expected {:?}", bytes, other),
+        }
+    }
+}
+
+impl Read for Bus {
+    type Error = Nack;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Nack> {
+        match self.next() {
+            Transaction::Read(expected, data) if expected == address => {
+                buffer.copy_from_slice(data);
+                Ok(())
+            }
+            Transaction::Nack(expected) if expected == address => Err(Nack),
+            other => panic!("unexpected read, expected {:?}", other),
+        }
+    }
+}
+
+impl WriteRead for Bus {
+    type Error = Nack;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Nack> {
+        match self.next() {
+            Transaction::WriteRead(expected, written, data) if expected == address => {
+                assert_eq!(bytes, written);
+                buffer.copy_from_slice(data);
+                Ok(())
+            }
+            Transaction::Nack(expected) if expected == address => Err(Nack),
+            other => panic!("unexpected write_read of {:?}, expected {:?}", bytes, other),
+        }
+    }
+}
+
+pub struct NoDelay;
+
+impl DelayMs<u8> for NoDelay {
+    fn delay_ms(&mut self, _ms: u8) {}
+}
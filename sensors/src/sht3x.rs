@@ -0,0 +1,147 @@
+//! Sensirion SHT3x temperature and humidity sensor
+
+use crate::{Capabilities, Error, Measurement, Sensor};
+use embedded_hal::blocking::{
+    delay::DelayMs,
+    i2c::{Read, Write},
+};
+
+/// ADDR pin low
+pub const PRIMARY_ADDRESS: u8 = 0x44;
+/// ADDR pin high
+pub const SECONDARY_ADDRESS: u8 = 0x45;
+
+const SOFT_RESET: [u8; 2] = [0x30, 0xa2];
+/// Single shot, high repeatability, without clock stretching
+const MEASURE: [u8; 2] = [0x24, 0x00];
+// Worst case times from the datasheet
+const RESET_MS: u8 = 2;
+const MEASURE_MS: u8 = 16;
+
+pub struct Sht3x<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C, E> Sht3x<I2C>
+where
+    I2C: Read<Error = E> + Write<Error = E>,
+{
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+
+    pub fn new_primary(i2c: I2C) -> Self {
+        Self::new(i2c, PRIMARY_ADDRESS)
+    }
+
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C, E> Sensor for Sht3x<I2C>
+where
+    I2C: Read<Error = E> + Write<Error = E>,
+{
+    type Error = Error<E>;
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            temperature: true,
+            humidity: true,
+            pressure: false,
+        }
+    }
+
+    fn init<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<(), Error<E>> {
+        self.i2c
+            .write(self.address, &SOFT_RESET)
+            .map_err(Error::Bus)?;
+        delay.delay_ms(RESET_MS);
+        Ok(())
+    }
+
+    fn measure<D: DelayMs<u8>>(&mut self, delay: &mut D) -> Result<Measurement, Error<E>> {
+        self.i2c.write(self.address, &MEASURE).map_err(Error::Bus)?;
+        delay.delay_ms(MEASURE_MS);
+
+        // Temperature and humidity words, each followed by its CRC
+        let mut data = [0u8; 6];
+        self.i2c.read(self.address, &mut data).map_err(Error::Bus)?;
+        if crc8(&data[0..2]) != data[2] || crc8(&data[3..5]) != data[5] {
+            return Err(Error::Crc);
+        }
+        let temperature = u16::from_be_bytes([data[0], data[1]]) as f32;
+        let humidity = u16::from_be_bytes([data[3], data[4]]) as f32;
+        Ok(Measurement {
+            temperature: Some(-45.0 + 175.0 * temperature / 65535.0),
+            humidity: Some(100.0 * humidity / 65535.0),
+            pressure: None,
+        })
+    }
+}
+
+/// CRC-8 with polynomial 0x31 and initial value 0xff
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0xff;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Bus, NoDelay, Transaction};
+
+    const ADDRESS: u8 = PRIMARY_ADDRESS;
+
+    #[test]
+    fn crc_matches_datasheet_example() {
+        assert_eq!(crc8(&[0xbe, 0xef]), 0x92);
+    }
+
+    #[test]
+    fn measures_temperature_and_humidity() {
+        let bus = Bus::new(&[
+            Transaction::Write(ADDRESS, &SOFT_RESET),
+            Transaction::Write(ADDRESS, &MEASURE),
+            // 0x6666 is 25 C, 0x8000 is 50 %
+            Transaction::Read(ADDRESS, &[0x66, 0x66, 0x93, 0x80, 0x00, 0xa2]),
+        ]);
+        let mut sensor = Sht3x::new_primary(bus);
+        sensor.init(&mut NoDelay).unwrap();
+        let measurement = sensor.measure(&mut NoDelay).unwrap();
+        sensor.release().done();
+
+        assert!((measurement.temperature.unwrap() - 25.0).abs() < 0.01);
+        assert!((measurement.humidity.unwrap() - 50.0).abs() < 0.01);
+        assert_eq!(measurement.pressure, None);
+    }
+
+    #[test]
+    fn rejects_corrupt_data() {
+        let bus = Bus::new(&[
+            Transaction::Write(ADDRESS, &MEASURE),
+            Transaction::Read(ADDRESS, &[0x66, 0x66, 0x00, 0x80, 0x00, 0xa2]),
+        ]);
+        let mut sensor = Sht3x::new_primary(bus);
+        assert_eq!(sensor.measure(&mut NoDelay), Err(Error::Crc));
+    }
+
+    #[test]
+    fn reports_bus_errors() {
+        let bus = Bus::new(&[Transaction::Nack(ADDRESS)]);
+        let mut sensor = Sht3x::new_primary(bus);
+        assert!(matches!(sensor.init(&mut NoDelay), Err(Error::Bus(_))));
+    }
+}
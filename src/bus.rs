@@ -0,0 +1,147 @@
+// I2C1 on PB8/PB9 for the sensor. A slave reset in the middle of a transfer
+// can keep holding SDA low, which no amount of retrying on the peripheral
+// clears. After a failed transfer the bus is recovered before the next one:
+// SCL is clocked by hand until the slave lets go of SDA, a STOP condition is
+// sent and the peripheral is set up again.
+
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use stm32f1xx_hal::{
+    afio::MAPR,
+    gpio::{
+        gpiob::{CRH, PB8, PB9},
+        Alternate, OpenDrain,
+    },
+    i2c::{BlockingI2c, DutyCycle, Error, Mode},
+    pac::I2C1,
+    prelude::*,
+    rcc::{Clocks, APB1},
+};
+
+type SCL = PB8<Alternate<OpenDrain>>;
+type SDA = PB9<Alternate<OpenDrain>>;
+type I2C = BlockingI2c<I2C1, (SCL, SDA)>;
+
+// Half a period of 100kHz at 72MHz
+const HALF_PERIOD_CYCLES: u32 = 360;
+
+fn mode() -> Mode {
+    Mode::Fast {
+        frequency: 400_000.hz(),
+        duty_cycle: DutyCycle::Ratio2to1,
+    }
+}
+
+pub struct SensorBus {
+    // Only `None` while the bus is recovered
+    i2c: Option<I2C>,
+    mapr: MAPR,
+    crh: CRH,
+    apb: APB1,
+    clocks: Clocks,
+    faulted: bool,
+}
+
+impl SensorBus {
+    pub fn new(
+        i2c1: I2C1,
+        pins: (SCL, SDA),
+        mut mapr: MAPR,
+        crh: CRH,
+        mut apb: APB1,
+        clocks: Clocks,
+    ) -> Self {
+        let i2c = Self::setup(i2c1, pins, &mut mapr, &mut apb, clocks);
+        Self {
+            i2c: Some(i2c),
+            mapr,
+            crh,
+            apb,
+            clocks,
+            faulted: false,
+        }
+    }
+
+    fn setup(i2c1: I2C1, pins: (SCL, SDA), mapr: &mut MAPR, apb: &mut APB1, clocks: Clocks) -> I2C {
+        BlockingI2c::i2c1(i2c1, pins, mapr, mode(), clocks, apb, 5000, 3, 5000, 5000)
+    }
+
+    /// Frees a slave stuck in a transfer and sets the peripheral up again
+    fn recover(&mut self) {
+        let (i2c1, (scl, sda)) = match self.i2c.take() {
+            Some(i2c) => i2c.free(),
+            None => return,
+        };
+        let half_period = || cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+
+        // Clock out the rest of the byte the slave is sending, SDA has a pull-up
+        let mut scl = scl.into_open_drain_output(&mut self.crh);
+        let sda = sda.into_floating_input(&mut self.crh);
+        for _ in 0..9 {
+            if sda.is_high().unwrap() {
+                break;
+            }
+            let _ = scl.set_low();
+            half_period();
+            let _ = scl.set_high();
+            half_period();
+        }
+
+        // STOP, SDA rising while SCL is high
+        let mut sda = sda.into_open_drain_output(&mut self.crh);
+        let _ = scl.set_low();
+        half_period();
+        let _ = sda.set_low();
+        half_period();
+        let _ = scl.set_high();
+        half_period();
+        let _ = sda.set_high();
+        half_period();
+
+        let pins = (
+            scl.into_alternate_open_drain(&mut self.crh),
+            sda.into_alternate_open_drain(&mut self.crh),
+        );
+        self.i2c = Some(Self::setup(
+            i2c1,
+            pins,
+            &mut self.mapr,
+            &mut self.apb,
+            self.clocks,
+        ));
+        self.faulted = false;
+    }
+
+    fn transfer<T>(&mut self, f: impl FnOnce(&mut I2C) -> Result<T, Error>) -> Result<T, Error> {
+        if self.faulted {
+            self.recover();
+        }
+        let result = f(self.i2c.as_mut().unwrap());
+        self.faulted = result.is_err();
+        result
+    }
+}
+
+impl Write for SensorBus {
+    type Error = Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.transfer(|i2c| i2c.write(address, bytes))
+    }
+}
+
+impl Read for SensorBus {
+    type Error = Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+        self.transfer(|i2c| i2c.read(address, buffer))
+    }
+}
+
+impl WriteRead for SensorBus {
+    type Error = Error;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+        self.transfer(|i2c| i2c.write_read(address, bytes, buffer))
+    }
+}
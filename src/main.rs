@@ -1,81 +1,128 @@
 #![no_std]
 #![no_main]
 
+mod bus;
 mod clock;
-mod display;
-mod tone;
+mod panel;
+mod power;
+mod storage;
 
 use panic_halt as _;
 
+use clock::RtcClock;
+use pomia_core::{
+    alarm::Alarms,
+    clock::Clock,
+    note::{Note, Pitch},
+};
 use stm32f1xx_hal::stm32;
 
-const CAT_SONG: [(char, u32); 24] = [
-    ('g', 2),
-    ('e', 2),
-    ('e', 2),
-    ('f', 2),
-    ('d', 2),
-    ('d', 2),
-    ('c', 1),
-    ('e', 1),
-    ('g', 4),
-    ('c', 1),
-    ('e', 1),
-    ('g', 4),
-    ('g', 2),
-    ('e', 2),
-    ('e', 2),
-    ('f', 2),
-    ('d', 2),
-    ('d', 2),
-    ('c', 1),
-    ('e', 1),
-    ('g', 4),
-    ('c', 1),
-    ('e', 1),
-    ('c', 4),
+// Rate of the TIM3 system tick driving the LED, music and button timing
+const TICK_HZ: u32 = 100;
+
+const ALARM_SONG: &str = "Nokia:d=4,o=5,b=225:8e6,8d6,f#,g#,8c#6,8b,d,e,8b,8a,c#,e,2a";
+
+const C4: Note = Note::new(Pitch::C, 4);
+const D4: Note = Note::new(Pitch::D, 4);
+const E4: Note = Note::new(Pitch::E, 4);
+const F4: Note = Note::new(Pitch::F, 4);
+const G4: Note = Note::new(Pitch::G, 4);
+
+const CAT_SONG: [(Note, u32); 24] = [
+    (G4, 2),
+    (E4, 2),
+    (E4, 2),
+    (F4, 2),
+    (D4, 2),
+    (D4, 2),
+    (C4, 1),
+    (E4, 1),
+    (G4, 4),
+    (C4, 1),
+    (E4, 1),
+    (G4, 4),
+    (G4, 2),
+    (E4, 2),
+    (E4, 2),
+    (F4, 2),
+    (D4, 2),
+    (D4, 2),
+    (C4, 1),
+    (E4, 1),
+    (G4, 4),
+    (C4, 1),
+    (E4, 1),
+    (C4, 4),
 ];
 
+/// Milliseconds since boot for the given number of system ticks
+fn millis(ticks: u32) -> u32 {
+    ticks.wrapping_mul(1000 / TICK_HZ)
+}
+
+fn reschedule_alarm(clock: &mut RtcClock, alarms: &Alarms) {
+    clock.set_alarm(alarms.next_fire(clock.timestamp()));
+}
+
 #[rtic::app(device = crate::stm32)]
 mod app {
 
+    use crate::bus::SensorBus;
     use crate::clock::RtcClock;
-    use crate::display::{Display, Gui};
-    use crate::tone::Tone;
-    use bme280::BME280;
+    use crate::panel::Panel;
+    use crate::power::{self, Activity, Stop};
+    use crate::storage::FlashPage;
     use embedded_graphics::{pixelcolor::Rgb565, prelude::*};
     use embedded_hal::digital::v2::InputPin;
+    use pomia_core::{
+        alarm::Alarms,
+        backlight::Backlight,
+        clock::{Clock, DateTime},
+        display::{Display, Gui},
+        health::{Action, Health},
+        history::{History, Sample},
+        input::{Button, Gesture, Input as ButtonInput},
+        menu::{Context, Menu},
+        rotation::Rotation,
+        rtttl::Rtttl,
+        screens::{
+            AlarmsScreen, AnalogScreen, ClockScreen, ComfortScreen, DisplayScreen, GraphScreen,
+            HistoryScreen, MeasureScreen,
+        },
+        settings::{Error as SettingsError, Settings, SettingsStore},
+        shell::{self, Device, LineBuffer, Reply, SerialWriter},
+        telemetry::{Format, Telemetry},
+        tone::{Melody, Song, Tone},
+    };
+    use pomia_sensors::{bme280::Bme280, Sensor};
+    use pomia_telemetry::Record;
     use rtic_core::prelude::*;
-    use st7735_lcd::{Orientation, ST7735};
     use stm32f1xx_hal::{
+        backup_domain::BackupDomain,
         delay::Delay,
         gpio::{
             gpioa::{PA0, PA11, PA12, PA15},
-            gpiob::{PB8, PB9},
+            gpiob::PB6,
             gpioc::PC13,
-            Alternate, Edge, ExtiPin, Input, OpenDrain, Output, PullUp, PushPull,
+            Alternate, Edge, ExtiPin, Input, Output, PullUp, PushPull,
         },
-        i2c::{BlockingI2c, DutyCycle, Mode as I2cMode},
-        pac::{I2C1, TIM2, TIM3},
+        pac::{TIM2, TIM3, TIM4, USART1},
         prelude::*,
         pwm::{Channel, Pwm, C1},
         rtc::Rtc,
+        serial::{Config as SerialConfig, Event as SerialEvent, Rx, Serial, Tx},
         spi::{Mode as SpiMode, Phase, Polarity, Spi},
-        timer::{CountDownTimer, Event, Tim2NoRemap, Timer},
+        timer::{CountDownTimer, Event, Tim2NoRemap, Tim4NoRemap, Timer},
     };
-    use ufmt::derive::uDebug;
-
-    type SCL = PB8<Alternate<OpenDrain>>;
-    type SDA = PB9<Alternate<OpenDrain>>;
-
-    #[derive(uDebug, Copy, Clone)]
-    pub enum PressedButton {
-        Left,
-        Right,
-        ShortPress,
-        LongPress,
-        None,
-    }
+    use ufmt::uWrite;
+
+    type DISP = Panel;
+
+    // Boards with another sensor put `Sht3x` or `Bmp280` here
+    type SENSOR = Bme280<SensorBus>;
+
+    type TONE = Tone<Pwm<TIM2, Tim2NoRemap, C1, PA0<Alternate<PushPull>>>>;
+    type BACKLIGHT = Backlight<Pwm<TIM4, Tim4NoRemap, C1, PB6<Alternate<PushPull>>>>;
 
     pub struct Buttons {
         enter: PA15<Input<PullUp>>,
@@ -83,20 +130,92 @@ mod app {
         right: PA12<Input<PullUp>>,
     }
 
+    /// What the shell commands act on while idle holds the resources
+    struct Board<'a> {
+        clock: &'a mut RtcClock,
+        alarms: &'a Alarms,
+        tone: &'a mut TONE,
+        settings: &'a mut Settings,
+        sample: Option<Sample>,
+        health: Health,
+        save_error: Option<SettingsError>,
+    }
+
+    /// The transmitter shared with the USART1 interrupt, locked for one byte
+    /// at a time so a long write does not hold off the received bytes
+    struct SharedTx<'a, M>(&'a mut M);
+
+    impl<M> embedded_hal::serial::Write<u8> for SharedTx<'_, M>
+    where
+        M: Mutex<T = Tx<USART1>>,
+    {
+        type Error = <Tx<USART1> as embedded_hal::serial::Write<u8>>::Error;
+
+        fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+            self.0.lock(|tx| tx.write(byte))
+        }
+
+        fn flush(&mut self) -> nb::Result<(), Self::Error> {
+            self.0.lock(|tx| tx.flush())
+        }
+    }
+
+    impl Device for Board<'_> {
+        fn datetime(&self) -> DateTime {
+            self.clock.get_datetime()
+        }
+
+        fn set_datetime(&mut self, datetime: &DateTime) {
+            self.clock.set_datetime(datetime)
+        }
+
+        fn sample(&self) -> Option<Sample> {
+            self.sample
+        }
+
+        fn health(&self) -> Health {
+            self.health
+        }
+
+        fn alarms(&self) -> &Alarms {
+            self.alarms
+        }
+
+        fn settings(&mut self) -> &mut Settings {
+            self.settings
+        }
+
+        fn save_error(&self) -> Option<SettingsError> {
+            self.save_error
+        }
+
+        fn play(&mut self, song: &Rtttl) {
+            self.tone.play(Song::Melody(Melody::from_rtttl(song)));
+        }
+    }
+
     #[resources]
     struct Resource {
         led: PC13<Output<PushPull>>,
         tim: CountDownTimer<TIM3>,
-        tone: Tone<Pwm<TIM2, Tim2NoRemap, C1, PA0<Alternate<PushPull>>>>,
+        tone: TONE,
+        backlight: BACKLIGHT,
         delay: Delay,
-        bme: BME280<BlockingI2c<I2C1, (SCL, SDA)>>,
+        sensor: SENSOR,
         buttons: Buttons,
-        gui: Gui,
+        gui: Gui<DISP>,
         clock: RtcClock,
-        #[init(PressedButton::None)]
-        pressed_btn: PressedButton,
+        alarms: Alarms,
+        history: History,
+        input: ButtonInput,
+        settings: Settings,
+        store: SettingsStore<FlashPage, BackupDomain>,
+        tx: Tx<USART1>,
+        rx: Rx<USART1>,
+        shell: LineBuffer,
+        stop: Stop,
         #[init(0)]
-        press_counter: u8,
+        ticks: u32,
     }
 
     #[init]
@@ -106,13 +225,13 @@ mod app {
         // Get access to the device specific peripherals from the peripheral access crate
         let dp = cx.device;
 
-        // Take ownership over the raw flash and rcc devices and convert them into the corresponding
-        // HAL structs
+        // Take ownership over the raw flash and rcc devices and convert them into the
+        // corresponding HAL structs
         let mut flash = dp.FLASH.constrain();
         let mut rcc = dp.RCC.constrain();
         let mut afio = dp.AFIO.constrain(&mut rcc.apb2);
-        // Freeze the configuration of all the clocks in the system and store the frozen frequencies in
-        // `clocks`
+        // Freeze the configuration of all the clocks in the system and store the frozen
+        // frequencies in `clocks`
         let clocks = rcc
             .cfgr
             .use_hse(8.mhz())
@@ -125,10 +244,11 @@ mod app {
         let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
         let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
 
-        // Configure gpio C pin 13 as a push-pull output. The `crh` register is passed to the function
-        // in order to configure the port. For pins 0-7, crl should be passed instead.
+        // Configure gpio C pin 13 as a push-pull output. The `crh` register is passed to the
+        // function in order to configure the port. For pins 0-7, crl should be passed instead.
         let led = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
-        let mut timer3 = Timer::tim3(dp.TIM3, &clocks, &mut rcc.apb1).start_count_down(2.hz());
+        let mut timer3 =
+            Timer::tim3(dp.TIM3, &clocks, &mut rcc.apb1).start_count_down(crate::TICK_HZ.hz());
         timer3.listen(Event::Update);
 
         // PWM config
@@ -139,7 +259,17 @@ mod app {
             &mut afio.mapr,
             1.khz(),
         );
-        let tone = Tone::new(pwm, Channel::C1);
+        let mut tone = Tone::new(pwm, Channel::C1, |freq| freq.hz());
+        tone.play(Song::Beats(&crate::CAT_SONG));
+
+        // Backlight PWM, idle fades it in to the brightness setting
+        let backlight_pin = gpiob.pb6.into_alternate_push_pull(&mut gpiob.crl);
+        let pwm = Timer::tim4(dp.TIM4, &clocks, &mut rcc.apb1).pwm::<Tim4NoRemap, _, _, _>(
+            backlight_pin,
+            &mut afio.mapr,
+            1.khz(),
+        );
+        let backlight = Backlight::new(pwm, Channel::C1);
 
         //SPI
         let sck = gpioa.pa5.into_alternate_push_pull(&mut gpioa.crl);
@@ -163,37 +293,30 @@ mod app {
         );
 
         // Instanciate Display driver
-        let mut disp = ST7735::new(spi, dc, rst, true, false, 128, 160);
-
-        disp.init(&mut delay).unwrap();
-        disp.set_orientation(&Orientation::Portrait).unwrap();
-        let _ = disp.clear(Rgb565::BLACK);
-        let display = Display::new(disp);
-
-        let gui = Gui::new(display);
-
-        // I2C config
-        let scl = gpiob.pb8.into_alternate_open_drain(&mut gpiob.crh);
-        let sda = gpiob.pb9.into_alternate_open_drain(&mut gpiob.crh);
-        let i2c = BlockingI2c::i2c1(
-            dp.I2C1,
-            (scl, sda),
-            &mut afio.mapr,
-            I2cMode::Fast {
-                frequency: 400000.hz(),
-                duty_cycle: DutyCycle::Ratio2to1,
-            },
-            clocks,
-            &mut rcc.apb1,
-            5000,
-            3,
-            5000,
-            5000,
+        let mut panel = Panel::new(spi, dc, rst, 128, 160);
+
+        panel.init(&mut delay).unwrap();
+        let _ = panel.clear(Rgb565::BLACK);
+        let display = Display::new(panel);
+
+        let mut gui = Gui::new(display);
+        gui.register(cortex_m::singleton!(: MeasureScreen = MeasureScreen).unwrap());
+        gui.register(cortex_m::singleton!(: ClockScreen = ClockScreen::new()).unwrap());
+        gui.register(cortex_m::singleton!(: AnalogScreen = AnalogScreen::new()).unwrap());
+        gui.register(cortex_m::singleton!(: AlarmsScreen = AlarmsScreen::new()).unwrap());
+        let history_menu = gui.register(
+            cortex_m::singleton!(: Menu = Menu::new("History", &["Statistics", "Graph"])).unwrap(),
         );
-
-        //Initialize the sensor
-        let mut bme = BME280::new_primary(i2c);
-        let _ = bme.init(&mut delay);
+        gui.register_child(
+            history_menu,
+            cortex_m::singleton!(: HistoryScreen = HistoryScreen::new()).unwrap(),
+        );
+        gui.register_child(
+            history_menu,
+            cortex_m::singleton!(: GraphScreen = GraphScreen::new()).unwrap(),
+        );
+        gui.register(cortex_m::singleton!(: ComfortScreen = ComfortScreen).unwrap());
+        gui.register(cortex_m::singleton!(: DisplayScreen = DisplayScreen::new()).unwrap());
 
         let (pa15, _, _) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
         let mut enter = pa15.into_pull_up_input(&mut gpioa.crh);
@@ -202,110 +325,356 @@ mod app {
         enter.enable_interrupt(&dp.EXTI);
         let mut left = gpioa.pa11.into_pull_up_input(&mut gpioa.crh); // PA11
         left.make_interrupt_source(&mut afio);
-        left.trigger_on_edge(&dp.EXTI, Edge::FALLING);
+        left.trigger_on_edge(&dp.EXTI, Edge::RISING_FALLING);
         left.enable_interrupt(&dp.EXTI);
         let mut right = gpioa.pa12.into_pull_up_input(&mut gpioa.crh); //PA12
         right.make_interrupt_source(&mut afio);
-        right.trigger_on_edge(&dp.EXTI, Edge::FALLING);
+        right.trigger_on_edge(&dp.EXTI, Edge::RISING_FALLING);
         right.enable_interrupt(&dp.EXTI);
 
         let buttons = Buttons { enter, left, right };
 
+        // Command shell on USART1
+        let tx_pin = gpioa.pa9.into_alternate_push_pull(&mut gpioa.crh);
+        let rx_pin = gpioa.pa10;
+        let mut serial = Serial::usart1(
+            dp.USART1,
+            (tx_pin, rx_pin),
+            &mut afio.mapr,
+            SerialConfig::default().baudrate(115_200.bps()),
+            clocks,
+            &mut rcc.apb2,
+        );
+        serial.listen(SerialEvent::Rxne);
+        let (mut tx, rx) = serial.split();
+        let _ = SerialWriter(&mut tx).write_str(shell::PROMPT);
+
         // Initialize RTC
         let mut pwr = dp.PWR;
         let mut backup_domain = rcc.bkp.constrain(dp.BKP, &mut rcc.apb1, &mut pwr);
         let rtc = Rtc::rtc(dp.RTC, &mut backup_domain);
-        let clock = RtcClock::new(rtc);
+        let mut clock = RtcClock::new(rtc);
+        let stop = Stop::new(cp.SCB, pwr, &dp.EXTI);
+
+        // Settings, the backup registers are available once the RTC enabled the backup domain
+        let mut store = SettingsStore::new(FlashPage::new(flash), backup_domain);
+        let settings = store.load();
+        let mut alarms = Alarms::new();
+        for (idx, alarm) in settings.alarms.iter().enumerate() {
+            alarms.set(idx, *alarm);
+        }
+        crate::reschedule_alarm(&mut clock, &alarms);
+
+        // I2C config, last as the bus keeps what it needs to set itself up again
+        let scl = gpiob.pb8.into_alternate_open_drain(&mut gpiob.crh);
+        let sda = gpiob.pb9.into_alternate_open_drain(&mut gpiob.crh);
+        let bus = SensorBus::new(dp.I2C1, (scl, sda), afio.mapr, gpiob.crh, rcc.apb1, clocks);
+        // Initialised by idle, which keeps track of its health
+        let sensor = Bme280::new_primary(bus);
 
         init::LateResources {
             led,
             tim: timer3,
             tone,
+            backlight,
             delay,
-            bme,
+            sensor,
             gui,
             buttons,
             clock,
+            alarms,
+            history: History::new(),
+            input: ButtonInput::new(),
+            settings,
+            store,
+            tx,
+            rx,
+            shell: LineBuffer::new(),
+            stop,
         }
     }
 
-    #[idle(resources = [tone, delay, bme, gui, clock, pressed_btn])]
+    #[idle(resources = [
+        tone, backlight, delay, sensor, gui, clock, alarms, history, input, settings, store, tx,
+        shell, stop, ticks
+    ])]
     fn idle(cx: idle::Context) -> ! {
-        let tone = cx.resources.tone;
+        let mut tone = cx.resources.tone;
+        let mut backlight = cx.resources.backlight;
         let delay = cx.resources.delay;
-        let bme = cx.resources.bme;
+        let sensor = cx.resources.sensor;
         let mut gui = cx.resources.gui;
-        let clock = cx.resources.clock;
-        let mut pressed_btn = cx.resources.pressed_btn;
-        (tone, delay, bme, clock).lock(|tone, delay, bme, mut clock| {
-            // draw stuff here
-            tone.play_song(&crate::CAT_SONG, delay);
+        let mut clock = cx.resources.clock;
+        let mut alarms = cx.resources.alarms;
+        let mut history = cx.resources.history;
+        let mut input = cx.resources.input;
+        let settings = cx.resources.settings;
+        let store = cx.resources.store;
+        let mut tx = cx.resources.tx;
+        let mut shell = cx.resources.shell;
+        let mut stop = cx.resources.stop;
+        let mut ticks = cx.resources.ticks;
+        let alarm_song = Rtttl::parse(crate::ALARM_SONG).unwrap();
+        let mut sample: Option<Sample> = None;
+        let mut telemetry = Telemetry::new();
+        let mut health = Health::new();
+        let mut save_error = None;
+        let mut activity = Activity::new(0);
+        // Set by the press that woke the display until all buttons are released
+        let mut waking = false;
+        // The panel sleeps once the backlight faded out
+        let mut panel_on = true;
+        (delay, sensor, settings, store).lock(|delay, sensor, settings, store| {
+            let capabilities = sensor.capabilities();
+            if sensor.init(delay).is_err() {
+                health.failure(crate::millis(ticks.lock(|t| *t)));
+            }
             loop {
-                // Update buttons
-                let pb = pressed_btn.lock(|pb| *pb);
-                match pb {
-                    PressedButton::Left => {
-                        gui.lock(|g| g.backward());
-                        pressed_btn.lock(|pb| *pb = PressedButton::None);
+                // Update buttons, the press waking the display does nothing else
+                while let Some(event) = input.lock(|i| i.next_event()) {
+                    let ringing = alarms.lock(|a| a.is_ringing());
+                    if activity.touch(crate::millis(ticks.lock(|t| *t))) {
+                        waking = !ringing;
                     }
-                    PressedButton::Right => {
-                        gui.lock(|g| g.forward());
-                        pressed_btn.lock(|pb| *pb = PressedButton::None);
+                    if waking {
+                        continue;
                     }
-                    PressedButton::LongPress => {
-                        gui.lock(|g| g.edit_clock(&mut clock));
-                        pressed_btn.lock(|pb| *pb = PressedButton::None);
+                    match (event.button, event.gesture) {
+                        (Button::Left, Gesture::Press) | (Button::Right, Gesture::Press)
+                            if ringing =>
+                        {
+                            (&mut clock, &mut alarms).lock(|clock, alarms| {
+                                alarms.snooze(clock.timestamp());
+                                crate::reschedule_alarm(clock, alarms);
+                            });
+                            tone.lock(|t| t.stop());
+                        }
+                        (Button::Enter, Gesture::Click) | (Button::Enter, Gesture::LongPress)
+                            if ringing =>
+                        {
+                            (&mut clock, &mut alarms).lock(|clock, alarms| {
+                                alarms.dismiss();
+                                crate::reschedule_alarm(clock, alarms);
+                            });
+                            tone.lock(|t| t.stop());
+                        }
+                        _ => {
+                            (&mut gui, &mut clock, &mut alarms, &mut history).lock(
+                                |g, clock, alarms, history| {
+                                    g.handle(
+                                        event,
+                                        &mut Context {
+                                            clock,
+                                            alarms,
+                                            history,
+                                            settings,
+                                            sample,
+                                            capabilities,
+                                            health,
+                                        },
+                                    );
+                                    crate::reschedule_alarm(clock, alarms);
+                                },
+                            );
+                        }
                     }
-                    PressedButton::ShortPress => {
-                        gui.lock(|g| g.select());
-                        pressed_btn.lock(|pb| *pb = PressedButton::None);
+                }
+
+                if waking && input.lock(|i| i.is_idle()) {
+                    waking = false;
+                }
+
+                // The backlight follows the night schedule and fades out before the
+                // panel sleeps, it fades in again once the panel woke up
+                if activity.display_on() && !panel_on {
+                    gui.lock(|g| g.wake(delay));
+                    panel_on = true;
+                }
+                // The theme follows the same schedule, switching it or the rotation
+                // redraws the screen
+                let hour = clock.lock(|c| c.get_time().hours);
+                gui.lock(|g| g.set_theme(settings.theme_at(hour)));
+                let rotation =
+                    Rotation::from_index(settings.rotation).unwrap_or(Rotation::Portrait);
+                gui.lock(|g| g.set_rotation(rotation));
+                let level = if activity.display_on() {
+                    settings.brightness_at(hour)
+                } else {
+                    0
+                };
+                backlight.lock(|b| b.set_level(level));
+                if !activity.display_on() && panel_on && !backlight.lock(|b| b.is_fading()) {
+                    gui.lock(|g| g.sleep(delay));
+                    panel_on = false;
+                }
+
+                // Run a command received on the shell
+                if let Some(line) = shell.lock(|s| s.take()) {
+                    // Collected first and sent without the locks held
+                    let mut reply = Reply::new();
+                    (&mut clock, &mut alarms, &mut tone).lock(|clock, alarms, tone| {
+                        let mut board = Board {
+                            clock,
+                            alarms,
+                            tone,
+                            settings,
+                            sample,
+                            health,
+                            save_error,
+                        };
+                        let _ = shell::execute(&line, &mut board, &mut reply);
+                        crate::reschedule_alarm(board.clock, board.alarms);
+                    });
+                    let _ = SerialWriter(&mut SharedTx(&mut tx)).write_str(&reply);
+                }
+
+                // Keep the stored alarms in sync, also with one-off alarms disabling themselves
+                alarms.lock(|alarms| {
+                    for (saved, alarm) in settings.alarms.iter_mut().zip(alarms.iter()) {
+                        *saved = *alarm;
                     }
-                    _ => {}
+                });
+                save_error = store.save(settings).err();
+
+                // Measure, or bring a failing sensor back once its retry is due
+                let now = crate::millis(ticks.lock(|t| *t));
+                let measurement = match health.action(now) {
+                    Action::Measure => Some(sensor.measure(delay)),
+                    Action::Reinit => Some(sensor.init(delay).and_then(|_| sensor.measure(delay))),
+                    Action::Wait => None,
                 };
+                let mut measured = false;
+                match measurement {
+                    Some(Ok(measurement)) => {
+                        health.success();
+                        sample = Some(Sample::from(measurement));
+                        measured = true;
+                    }
+                    Some(Err(_)) => {
+                        health.failure(now);
+                        if health.is_offline() {
+                            sample = None;
+                        }
+                    }
+                    None => {}
+                }
 
                 // Update screen
-                gui.lock(|g| {
-                    g.print_header();
-
-                    match bme.measure(delay) {
-                        Ok(measurement) => {
-                            g.print_measurements((
-                                measurement.temperature as u8,
-                                measurement.humidity as u8,
-                                measurement.pressure as u32,
-                            ));
+                (&mut gui, &mut clock, &mut alarms, &mut history).lock(
+                    |g, clock, alarms, history| {
+                        if let (true, Some(sample)) = (measured, &sample) {
+                            history.record(clock.timestamp(), sample);
+                        }
+
+                        if !panel_on {
+                            return;
                         }
-                        Err(e) => g.print_error(&e),
+                        g.begin_frame();
+                        g.render(&mut Context {
+                            clock,
+                            alarms,
+                            history,
+                            settings,
+                            sample,
+                            capabilities,
+                            health,
+                        });
+                        g.end_frame();
+                    },
+                );
+
+                // A record in the middle of a typed command would garble the
+                // line, it is held back until the line is finished and goes out
+                // with the next measurement
+                let typing = !shell.lock(|s| s.is_empty());
+                if let (true, false, Some(sample)) = (measured, typing, &sample) {
+                    let record = Record {
+                        timestamp: clock.lock(|c| c.timestamp()),
+                        temperature: sample.temperature,
+                        humidity: sample.humidity,
+                        pressure: sample.pressure,
                     };
+                    let _ = telemetry.update(
+                        settings.telemetry,
+                        settings.telemetry_interval as u32,
+                        &record,
+                        &mut SerialWriter(&mut SharedTx(&mut tx)),
+                    );
+                }
 
-                    g.print_clock(&clock);
-                });
-                delay.delay_ms(200u32);
+                // Keep repeating the ringtone until the alarm is snoozed or dismissed,
+                // the display stays on while it rings
+                let ringing = alarms.lock(|a| a.is_ringing());
+                let now = crate::millis(ticks.lock(|t| *t));
+                if ringing {
+                    activity.touch(now);
+                    tone.lock(|t| {
+                        if !t.is_playing() {
+                            t.play(Song::Rtttl(alarm_song));
+                        }
+                    });
+                }
+                activity.check_timeout(now, settings.display_timeout);
+
+                // With the display off and nothing needing the tick the chip stops
+                // until the next sensor update, otherwise the core sleeps until then
+                let quiet = !panel_on
+                    && settings.telemetry == Format::Off
+                    && !tone.lock(|t| t.is_playing())
+                    && input.lock(|i| i.is_idle())
+                    && shell.lock(|s| s.is_empty());
+                if quiet {
+                    let started = (&mut clock, &mut alarms).lock(|clock, alarms| {
+                        let started = clock.timestamp();
+                        let update = started + power::STOP_SECONDS;
+                        let wake = alarms
+                            .next_fire(started)
+                            .map_or(update, |at| at.min(update));
+                        clock.set_alarm(Some(wake));
+                        started
+                    });
+                    stop.lock(|stop| {
+                        stop.stop(|| {
+                            // The tick stood still, catch up on the time slept
+                            let slept = clock.lock(|c| c.timestamp()).wrapping_sub(started);
+                            ticks.lock(|t| *t = t.wrapping_add(slept * crate::TICK_HZ));
+                        })
+                    });
+                    (&mut clock, &mut alarms).lock(|clock, alarms| {
+                        crate::reschedule_alarm(clock, alarms);
+                    });
+                } else {
+                    let until = now.wrapping_add(activity.update_ms());
+                    while (crate::millis(ticks.lock(|t| *t)).wrapping_sub(until) as i32) < 0
+                        && !input.lock(|i| i.has_event())
+                        && !shell.lock(|s| s.is_ready())
+                    {
+                        cortex_m::asm::wfi();
+                    }
+                }
             }
         });
         loop {}
     }
 
-    #[task(binds = EXTI15_10, resources = [buttons, press_counter, pressed_btn])]
+    #[task(binds = EXTI15_10, resources = [buttons, input, ticks])]
     fn exti15_10(cx: exti15_10::Context) {
         let buttons = cx.resources.buttons;
-        let press_counter = cx.resources.press_counter;
-        let pressed_btn = cx.resources.pressed_btn;
+        let input = cx.resources.input;
+        let ticks = cx.resources.ticks;
 
-        (buttons, press_counter, pressed_btn).lock(|buttons, pc, pb| {
+        (buttons, input, ticks).lock(|buttons, input, ticks| {
+            let now = crate::millis(*ticks);
             let Buttons { enter, left, right } = buttons;
+            // Buttons pull the pin low when pressed
             if enter.check_interrupt() {
-                if enter.is_low().unwrap() {
-                    *pc = 0;
-                } else if enter.is_high().unwrap() && *pc > 3 {
-                    *pb = PressedButton::LongPress;
-                } else if enter.is_high().unwrap() && *pc <= 3 {
-                    *pb = PressedButton::ShortPress;
-                }
-            } else if left.check_interrupt() && left.is_low().unwrap() {
-                *pb = PressedButton::Left;
-            } else if right.check_interrupt() && right.is_low().unwrap() {
-                *pb = PressedButton::Right;
+                input.on_edge(Button::Enter, enter.is_low().unwrap(), now);
+            }
+            if left.check_interrupt() {
+                input.on_edge(Button::Left, left.is_low().unwrap(), now);
+            }
+            if right.check_interrupt() {
+                input.on_edge(Button::Right, right.is_low().unwrap(), now);
             }
 
             enter.clear_interrupt_pending_bit();
@@ -314,10 +683,47 @@ mod app {
         })
     }
 
-    #[task(binds = TIM3, resources = [led, tim, press_counter])]
+    #[task(binds = USART1, resources = [rx, tx, shell])]
+    fn usart1(cx: usart1::Context) {
+        let rx = cx.resources.rx;
+        let tx = cx.resources.tx;
+        let shell = cx.resources.shell;
+
+        (rx, tx, shell).lock(|rx, tx, shell| {
+            // Reading also clears overrun errors, so the loop always ends
+            while let Ok(byte) = rx.read() {
+                let _ = shell.feed(byte, &mut SerialWriter(tx));
+            }
+        })
+    }
+
+    #[task(binds = RTCALARM, resources = [clock, alarms])]
+    fn rtc_alarm(cx: rtc_alarm::Context) {
+        let clock = cx.resources.clock;
+        let alarms = cx.resources.alarms;
+
+        (clock, alarms).lock(|clock, alarms| {
+            clock.clear_alarm();
+            power::clear_rtc_wakeup();
+            alarms.trigger(clock.timestamp());
+            crate::reschedule_alarm(clock, alarms);
+        })
+    }
+
+    #[task(binds = TIM3, resources = [led, tim, tone, backlight, input, ticks])]
     fn tim3(mut cx: tim3::Context) {
-        let _ = cx.resources.led.lock(|led| led.toggle());
+        let ticks = cx.resources.ticks.lock(|t| {
+            *t = t.wrapping_add(1);
+            *t
+        });
+        if ticks % (crate::TICK_HZ / 2) == 0 {
+            let _ = cx.resources.led.lock(|led| led.toggle());
+        }
         let _ = cx.resources.tim.lock(|tim| tim.wait());
-        cx.resources.press_counter.lock(|pc| *pc += 1);
+        cx.resources.tone.lock(|t| t.tick(1000 / crate::TICK_HZ));
+        cx.resources
+            .backlight
+            .lock(|b| b.tick(1000 / crate::TICK_HZ));
+        cx.resources.input.lock(|i| i.poll(crate::millis(ticks)));
     }
 }
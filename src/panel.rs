@@ -0,0 +1,235 @@
+// The ST7735 LCD on SPI1. st7735-lcd keeps its command writer to itself and
+// only knows a reset to get the panel dark, which loses the setup and takes a
+// full `init` to come back from. The SPI bus and the D/C pin are shared
+// between the driver and the panel instead, so the panel can send the sleep
+// commands itself. Only idle draws, the critical sections around the shared
+// parts last for one short SPI write.
+//
+// The screen is turned by the scan direction of the panel. The driver keeps
+// clipping to the portrait size it was created with, so the panel clips to the
+// turned size and writes through the driver's unclipped pixel calls.
+
+use core::cell::RefCell;
+use cortex_m::interrupt::{self, Mutex};
+use embedded_graphics::{
+    pixelcolor::{
+        raw::{RawData, RawU16},
+        Rgb565,
+    },
+    prelude::*,
+    primitives::Rectangle,
+};
+use embedded_hal::{
+    blocking::{delay::DelayMs, spi::Write},
+    digital::v2::OutputPin,
+};
+use pomia_core::{
+    display::{Rotate, Sleep},
+    rotation::Rotation,
+};
+use st7735_lcd::{Orientation, ST7735};
+use stm32f1xx_hal::{
+    gpio::{
+        gpioa::{PA5, PA6, PA7},
+        gpiob::{PB0, PB1},
+        Alternate, Floating, Input, Output, PushPull,
+    },
+    pac::SPI1,
+    spi::{Spi, Spi1NoRemap},
+};
+
+type SCK = PA5<Alternate<PushPull>>;
+type MISO = PA6<Input<Floating>>;
+type MOSI = PA7<Alternate<PushPull>>;
+pub type SPI = Spi<SPI1, Spi1NoRemap, (SCK, MISO, MOSI), u8>;
+pub type DC = PB1<Output<PushPull>>;
+pub type RESET = PB0<Output<PushPull>>;
+
+type Wires = Mutex<RefCell<(SPI, DC)>>;
+
+// Commands of the ST7735S
+const SLPIN: u8 = 0x10;
+const SLPOUT: u8 = 0x11;
+const DISPOFF: u8 = 0x28;
+const DISPON: u8 = 0x29;
+
+// No command is taken for 5 ms after SLPIN, SLPOUT needs 120 ms for the
+// supply booster and the oscillator to settle
+const SLPIN_DELAY_MS: u8 = 5;
+const SLPOUT_DELAY_MS: u8 = 120;
+
+/// The driver's side of the shared SPI bus
+pub struct SharedSpi(&'static Wires);
+
+impl Write<u8> for SharedSpi {
+    type Error = <SPI as Write<u8>>::Error;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        interrupt::free(|cs| self.0.borrow(cs).borrow_mut().0.write(words))
+    }
+}
+
+/// The driver's side of the shared D/C pin
+pub struct SharedDc(&'static Wires);
+
+impl OutputPin for SharedDc {
+    type Error = <DC as OutputPin>::Error;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        interrupt::free(|cs| self.0.borrow(cs).borrow_mut().1.set_low())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        interrupt::free(|cs| self.0.borrow(cs).borrow_mut().1.set_high())
+    }
+}
+
+pub struct Panel {
+    lcd: ST7735<SharedSpi, SharedDc, RESET>,
+    wires: &'static Wires,
+    /// Size in portrait orientation
+    native: Size,
+    rotation: Rotation,
+}
+
+impl Panel {
+    /// There is only one panel, panics when called a second time
+    pub fn new(spi: SPI, dc: DC, rst: RESET, width: u32, height: u32) -> Self {
+        let wires = cortex_m::singleton!(: Wires = Mutex::new(RefCell::new((spi, dc)))).unwrap();
+        Self {
+            lcd: ST7735::new(
+                SharedSpi(wires),
+                SharedDc(wires),
+                rst,
+                true,
+                false,
+                width,
+                height,
+            ),
+            wires,
+            native: Size::new(width, height),
+            rotation: Rotation::Portrait,
+        }
+    }
+
+    /// Resets the panel and sets it up in the current orientation
+    pub fn init<T: DelayMs<u8>>(&mut self, delay: &mut T) -> Result<(), ()> {
+        self.lcd.init(delay)?;
+        self.lcd.set_orientation(&orientation(self.rotation))
+    }
+
+    /// Sends a command without parameters
+    fn command(&mut self, command: u8) {
+        interrupt::free(|cs| {
+            let (spi, dc) = &mut *self.wires.borrow(cs).borrow_mut();
+            let _ = dc.set_low();
+            let _ = spi.write(&[command]);
+        });
+    }
+}
+
+fn orientation(rotation: Rotation) -> Orientation {
+    match rotation {
+        Rotation::Portrait => Orientation::Portrait,
+        Rotation::Landscape => Orientation::Landscape,
+        Rotation::PortraitFlipped => Orientation::PortraitSwapped,
+        Rotation::LandscapeFlipped => Orientation::LandscapeSwapped,
+    }
+}
+
+fn raw(color: Rgb565) -> u16 {
+    RawU16::from(color).into_inner()
+}
+
+fn contains(size: Size, point: Point) -> bool {
+    point.x >= 0 && point.y >= 0 && (point.x as u32) < size.width && (point.y as u32) < size.height
+}
+
+impl Rotate for Panel {
+    fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    fn set_rotation(&mut self, rotation: Rotation) {
+        if self.lcd.set_orientation(&orientation(rotation)).is_ok() {
+            self.rotation = rotation;
+        }
+    }
+}
+
+// The panel keeps its setup and the frame memory in sleep in mode, waking
+// only takes it out again
+impl Sleep for Panel {
+    fn sleep<T: DelayMs<u8>>(&mut self, delay: &mut T) {
+        self.command(DISPOFF);
+        self.command(SLPIN);
+        delay.delay_ms(SLPIN_DELAY_MS);
+    }
+
+    fn wake<T: DelayMs<u8>>(&mut self, delay: &mut T) {
+        self.command(SLPOUT);
+        delay.delay_ms(SLPOUT_DELAY_MS);
+        self.command(DISPON);
+    }
+}
+
+impl OriginDimensions for Panel {
+    fn size(&self) -> Size {
+        self.rotation.size(self.native)
+    }
+}
+
+impl DrawTarget for Panel {
+    type Color = Rgb565;
+    type Error = ();
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let size = self.size();
+        for Pixel(point, color) in pixels {
+            if contains(size, point) {
+                self.lcd
+                    .set_pixel(point.x as u16, point.y as u16, raw(color))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if area.size.width == 0 || area.size.height == 0 {
+            return Ok(());
+        }
+        let (start, end) = (area.top_left, area.top_left + area.size - Size::new(1, 1));
+        let size = self.size();
+        if contains(size, start) && contains(size, end) {
+            // One windowed write in the scan order of the turned panel
+            self.lcd.set_pixels(
+                start.x as u16,
+                start.y as u16,
+                end.x as u16,
+                end.y as u16,
+                colors.into_iter().map(raw),
+            )
+        } else {
+            self.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(point, color)| Pixel(point, color)),
+            )
+        }
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let count = (area.size.width * area.size.height) as usize;
+        self.fill_contiguous(area, core::iter::repeat(color).take(count))
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_solid(&self.bounding_box(), color)
+    }
+}
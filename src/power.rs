@@ -0,0 +1,116 @@
+// Power saving. Whenever idle has nothing to do the core sleeps until the next
+// interrupt, the TIM3 tick wakes it at the latest. After a while without a
+// button press the display goes to sleep, and as long as nothing needs the
+// tick the whole chip is stopped between sensor updates. The RTC alarm, routed
+// to EXTI line 17, or a button brings it back.
+
+use cortex_m::peripheral::SCB;
+use stm32f1xx_hal::pac::{EXTI, PWR, RCC};
+
+/// Time between updates with the display on
+pub const AWAKE_UPDATE_MS: u32 = 200;
+/// Time between updates with the display asleep while the tick keeps running
+pub const ASLEEP_UPDATE_MS: u32 = 1000;
+/// Seconds the chip stops for between sensor updates with the display asleep
+pub const STOP_SECONDS: u32 = 30;
+
+const RTC_ALARM_LINE: u32 = 1 << 17;
+
+/// Switches the display off after a while without a button press
+pub struct Activity {
+    last: u32,
+    display_on: bool,
+}
+
+impl Activity {
+    pub fn new(now_ms: u32) -> Self {
+        Self {
+            last: now_ms,
+            display_on: true,
+        }
+    }
+
+    /// A button was used, returns true if that woke the display
+    pub fn touch(&mut self, now_ms: u32) -> bool {
+        self.last = now_ms;
+        let woke = !self.display_on;
+        self.display_on = true;
+        woke
+    }
+
+    /// Switches the display off once `timeout_s` passed without a button
+    /// press, a timeout of 0 keeps it on
+    pub fn check_timeout(&mut self, now_ms: u32, timeout_s: u16) {
+        let timeout_ms = timeout_s as u32 * 1000;
+        if timeout_ms != 0 && now_ms.wrapping_sub(self.last) >= timeout_ms {
+            self.display_on = false;
+        }
+    }
+
+    pub fn display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Milliseconds until the next update while the tick keeps running
+    pub fn update_ms(&self) -> u32 {
+        if self.display_on {
+            AWAKE_UPDATE_MS
+        } else {
+            ASLEEP_UPDATE_MS
+        }
+    }
+}
+
+/// STOP mode, all clocks but the RTC halt until an EXTI line fires
+pub struct Stop {
+    scb: SCB,
+    pwr: PWR,
+}
+
+impl Stop {
+    /// Also routes the RTC alarm to EXTI line 17, the only way it reaches
+    /// the core in STOP mode
+    pub fn new(scb: SCB, pwr: PWR, exti: &EXTI) -> Self {
+        exti.rtsr
+            .modify(|r, w| unsafe { w.bits(r.bits() | RTC_ALARM_LINE) });
+        exti.imr
+            .modify(|r, w| unsafe { w.bits(r.bits() | RTC_ALARM_LINE) });
+        Self { scb, pwr }
+    }
+
+    /// Stops the chip until the RTC alarm or a button fires. The clocks are
+    /// set up again and `woken` runs before any interrupt is served.
+    pub fn stop<F: FnOnce()>(&mut self, woken: F) {
+        cortex_m::interrupt::free(|_| {
+            // Voltage regulator in low power mode, STOP rather than standby
+            self.pwr
+                .cr
+                .modify(|_, w| w.pdds().clear_bit().lpds().set_bit());
+            self.scb.set_sleepdeep();
+            // A pending interrupt ends WFI even with interrupts disabled
+            cortex_m::asm::wfi();
+            self.scb.clear_sleepdeep();
+            restore_clocks();
+            woken();
+        });
+    }
+}
+
+/// Clears the RTC alarm on EXTI line 17 once it was handled
+pub fn clear_rtc_wakeup() {
+    // Write only register, setting a bit clears that line alone
+    let exti = unsafe { &*EXTI::ptr() };
+    exti.pr.write(|w| unsafe { w.bits(RTC_ALARM_LINE) });
+}
+
+// The chip wakes running on HSI. The PLL keeps its configuration, it only has
+// to be started on the HSE again.
+fn restore_clocks() {
+    let rcc = unsafe { &*RCC::ptr() };
+    rcc.cr.modify(|_, w| w.hseon().set_bit());
+    while rcc.cr.read().hserdy().bit_is_clear() {}
+    rcc.cr.modify(|_, w| w.pllon().set_bit());
+    while rcc.cr.read().pllrdy().bit_is_clear() {}
+    rcc.cfgr.modify(|_, w| w.sw().pll());
+    while !rcc.cfgr.read().sws().is_pll() {}
+}
@@ -0,0 +1,66 @@
+// Settings storage on the STM32F103C8
+
+use pomia_core::settings::{Error, Flash, Registers};
+use stm32f1xx_hal::{
+    backup_domain::BackupDomain,
+    flash::{self, FlashSize, SectorSize},
+};
+
+/// The last 1K page of the 64K flash, left out of the linker script
+const PAGE_OFFSET: u32 = 63 * 1024;
+const PAGE_SIZE: usize = 1024;
+// Data registers DR1 - DR10 of the medium density devices
+const REGISTER_COUNT: usize = 10;
+
+pub struct FlashPage {
+    flash: flash::Parts,
+}
+
+impl FlashPage {
+    pub fn new(flash: flash::Parts) -> Self {
+        Self { flash }
+    }
+}
+
+impl Flash for FlashPage {
+    fn size(&self) -> usize {
+        PAGE_SIZE
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) {
+        let writer = self.flash.writer(SectorSize::Sz1K, FlashSize::Sz64K);
+        match writer.read(PAGE_OFFSET + offset as u32, buf.len()) {
+            Ok(data) => buf.copy_from_slice(data),
+            // Reads as garbage, the page gets erased on the next save
+            Err(_) => buf.iter_mut().for_each(|byte| *byte = 0),
+        }
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        let mut writer = self.flash.writer(SectorSize::Sz1K, FlashSize::Sz64K);
+        writer
+            .write(PAGE_OFFSET + offset as u32, data)
+            .map_err(|_| Error::Flash)
+    }
+
+    fn erase(&mut self) -> Result<(), Error> {
+        let mut writer = self.flash.writer(SectorSize::Sz1K, FlashSize::Sz64K);
+        writer
+            .erase(PAGE_OFFSET, PAGE_SIZE)
+            .map_err(|_| Error::Flash)
+    }
+}
+
+impl Registers for BackupDomain {
+    fn count(&self) -> usize {
+        REGISTER_COUNT
+    }
+
+    fn read(&self, idx: usize) -> u16 {
+        self.read_data_register_low(idx)
+    }
+
+    fn write(&mut self, idx: usize, value: u16) {
+        self.write_data_register_low(idx, value)
+    }
+}
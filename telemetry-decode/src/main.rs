@@ -0,0 +1,43 @@
+//! Turns a capture of the binary telemetry stream back into CSV.
+//!
+//! Reads the capture from the file given as argument or from stdin and writes
+//! the CSV to stdout. Frames that do not decode are counted on stderr.
+
+use pomia_telemetry::{Decoder, CSV_HEADER};
+use std::io::{self, BufWriter, Read, Write};
+use std::{env, fs, process};
+
+fn main() {
+    let mut capture = Vec::new();
+    let read = match env::args().nth(1) {
+        Some(path) => fs::File::open(&path).and_then(|mut file| file.read_to_end(&mut capture)),
+        None => io::stdin().read_to_end(&mut capture),
+    };
+    if let Err(e) = read {
+        eprintln!("Can't read the capture: {}", e);
+        process::exit(1);
+    }
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut decoder = Decoder::new();
+    let mut line = String::new();
+    let mut errors = 0;
+    writeln!(out, "{}", CSV_HEADER).unwrap();
+    for byte in capture {
+        match decoder.feed(byte) {
+            Some(Ok(record)) => {
+                line.clear();
+                record.write_csv(&mut line).unwrap();
+                writeln!(out, "{}", line).unwrap();
+            }
+            Some(Err(_)) => errors += 1,
+            None => {}
+        }
+    }
+    out.flush().unwrap();
+
+    if errors > 0 {
+        eprintln!("Skipped {} corrupt frames", errors);
+    }
+}
@@ -0,0 +1,366 @@
+//! Sensor telemetry records shared by the firmware and the host tools.
+//!
+//! A binary record is the layout version, the RTC timestamp and the three
+//! measurements as little endian `f32`, NaN for what the sensor does not
+//! measure, followed by a CRC-16/CCITT-FALSE of those bytes. The whole is
+//! COBS encoded and terminated by a zero byte, so a reader can pick up the
+//! stream at any point and resynchronise on the next zero after a corrupt
+//! frame.
+
+#![no_std]
+
+use pomia_crc::crc16;
+use ufmt::{uDisplay, uWrite, uwrite, Formatter};
+
+/// Layout version written in front of every binary record
+pub const VERSION: u8 = 2;
+/// Bytes of a record before framing
+pub const RECORD_LEN: usize = 17;
+const CRC_LEN: usize = 2;
+/// Longest frame including the zero delimiter
+pub const MAX_FRAME: usize = cobs_max_len(RECORD_LEN + CRC_LEN) + 1;
+
+pub const CSV_HEADER: &str = "timestamp,temperature,humidity,pressure";
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Error {
+    /// Zero length or malformed COBS data
+    Framing,
+    /// The frame is longer than any record
+    Overflow,
+    /// The decoded data is not the size of a record and its CRC
+    Length,
+    /// The CRC does not match the record
+    Crc,
+    /// Written by a firmware with a newer layout
+    Version(u8),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Record {
+    /// RTC counter, seconds since 1970-01-01
+    pub timestamp: u32,
+    /// Degree Celsius
+    pub temperature: Option<f32>,
+    /// Relative humidity in percent
+    pub humidity: Option<f32>,
+    /// Pascal
+    pub pressure: Option<f32>,
+}
+
+impl Record {
+    pub fn to_bytes(&self) -> [u8; RECORD_LEN] {
+        let mut bytes = [0u8; RECORD_LEN];
+        bytes[0] = VERSION;
+        bytes[1..5].copy_from_slice(&self.timestamp.to_le_bytes());
+        let value = |value: Option<f32>| value.unwrap_or(f32::NAN).to_le_bytes();
+        bytes[5..9].copy_from_slice(&value(self.temperature));
+        bytes[9..13].copy_from_slice(&value(self.humidity));
+        bytes[13..17].copy_from_slice(&value(self.pressure));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != RECORD_LEN {
+            return Err(Error::Length);
+        }
+        if bytes[0] != VERSION {
+            return Err(Error::Version(bytes[0]));
+        }
+        let word = |at: usize| [bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]];
+        let value = |at: usize| Some(f32::from_le_bytes(word(at))).filter(|v| !v.is_nan());
+        Ok(Self {
+            timestamp: u32::from_le_bytes(word(1)),
+            temperature: value(5),
+            humidity: value(9),
+            pressure: value(13),
+        })
+    }
+
+    /// Writes the framed record including the delimiter, returns its length
+    pub fn encode(&self, frame: &mut [u8; MAX_FRAME]) -> usize {
+        let mut data = [0u8; RECORD_LEN + CRC_LEN];
+        data[..RECORD_LEN].copy_from_slice(&self.to_bytes());
+        let crc = crc16(&data[..RECORD_LEN]);
+        data[RECORD_LEN..].copy_from_slice(&crc.to_le_bytes());
+
+        let len = cobs_encode(&data, &mut frame[..]);
+        frame[len] = 0;
+        len + 1
+    }
+
+    /// Decodes a frame without its delimiter
+    pub fn decode(frame: &[u8]) -> Result<Self, Error> {
+        let mut data = [0u8; MAX_FRAME];
+        let len = cobs_decode(frame, &mut data).ok_or(Error::Framing)?;
+        if len < CRC_LEN {
+            return Err(Error::Length);
+        }
+        let (record, crc) = data[..len].split_at(len - CRC_LEN);
+        if crc16(record) != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(Error::Crc);
+        }
+        Self::from_bytes(record)
+    }
+
+    /// One CSV line in the order of `CSV_HEADER`, without the line ending.
+    /// What the sensor does not measure is left empty.
+    pub fn write_csv<W: uWrite + ?Sized>(&self, out: &mut W) -> Result<(), W::Error> {
+        uwrite!(
+            out,
+            "{},{},{},{}",
+            self.timestamp,
+            Fixed(self.temperature, 2),
+            Fixed(self.humidity, 2),
+            Fixed(self.pressure, 0)
+        )
+    }
+}
+
+/// Float with a fixed number of decimal places, ufmt has no float support.
+/// Nothing is written for `None`.
+struct Fixed(Option<f32>, u8);
+
+impl uDisplay for Fixed {
+    fn fmt<W: uWrite + ?Sized>(&self, f: &mut Formatter<'_, W>) -> Result<(), W::Error> {
+        let value = match self.0 {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        let scale = 10i32.pow(self.1 as u32);
+        let half = if value < 0.0 { -0.5 } else { 0.5 };
+        let scaled = (value * scale as f32 + half) as i32;
+        if scaled < 0 {
+            f.write_str("-")?;
+        }
+        let scaled = scaled.abs();
+        uwrite!(f, "{}", scaled / scale)?;
+        if self.1 > 0 {
+            f.write_str(".")?;
+            let fraction = scaled % scale;
+            let mut digit = scale / 10;
+            while digit > 1 && fraction < digit {
+                f.write_str("0")?;
+                digit /= 10;
+            }
+            uwrite!(f, "{}", fraction)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects a byte stream into frames and decodes them
+pub struct Decoder {
+    buf: [u8; MAX_FRAME],
+    len: usize,
+    overflow: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_FRAME],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// Returns the outcome of a frame once its delimiter arrives
+    pub fn feed(&mut self, byte: u8) -> Option<Result<Record, Error>> {
+        if byte != 0 {
+            match self.buf.get_mut(self.len) {
+                Some(slot) => {
+                    *slot = byte;
+                    self.len += 1;
+                }
+                None => self.overflow = true,
+            }
+            return None;
+        }
+
+        let (len, overflow) = (self.len, self.overflow);
+        self.len = 0;
+        self.overflow = false;
+        match (len, overflow) {
+            // Back to back delimiters carry no frame
+            (0, false) => None,
+            (_, true) => Some(Err(Error::Overflow)),
+            _ => Some(Record::decode(&self.buf[..len])),
+        }
+    }
+}
+
+/// Encoded length of the worst case of `len` bytes, without the delimiter
+pub const fn cobs_max_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// COBS encodes `data` into `out`, which must hold `cobs_max_len` bytes.
+/// Returns the encoded length, the delimiter is not written.
+pub fn cobs_encode(data: &[u8], out: &mut [u8]) -> usize {
+    let mut code_at = 0;
+    let mut code = 1u8;
+    let mut len = 1;
+    for &byte in data {
+        if byte != 0 {
+            out[len] = byte;
+            len += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xff {
+            out[code_at] = code;
+            code_at = len;
+            len += 1;
+            code = 1;
+        }
+    }
+    out[code_at] = code;
+    len
+}
+
+/// Decodes COBS data without the delimiter, `None` if it is malformed or
+/// does not fit `out`
+pub fn cobs_decode(data: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    let mut written = 0;
+    while read < data.len() {
+        let code = data[read];
+        if code == 0 {
+            return None;
+        }
+        read += 1;
+        for _ in 1..code {
+            let byte = *data.get(read)?;
+            if byte == 0 {
+                return None;
+            }
+            *out.get_mut(written)? = byte;
+            written += 1;
+            read += 1;
+        }
+        // Every block but the last and the full ones stands for a zero
+        if code != 0xff && read < data.len() {
+            *out.get_mut(written)? = 0;
+            written += 1;
+        }
+    }
+    if read == 0 {
+        return None;
+    }
+    Some(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: Record = Record {
+        timestamp: 1_600_000_000,
+        temperature: Some(-3.25),
+        humidity: Some(0.0),
+        pressure: Some(101_325.0),
+    };
+
+    // A sensor without pressure
+    const NO_PRESSURE: Record = Record {
+        pressure: None,
+        ..RECORD
+    };
+
+    fn frame(record: &Record) -> ([u8; MAX_FRAME], usize) {
+        let mut frame = [0u8; MAX_FRAME];
+        let len = record.encode(&mut frame);
+        (frame, len)
+    }
+
+    #[test]
+    fn frame_round_trips() {
+        let (frame, len) = frame(&RECORD);
+        assert_eq!(frame[len - 1], 0);
+        assert!(!frame[..len - 1].contains(&0));
+        assert_eq!(Record::decode(&frame[..len - 1]), Ok(RECORD));
+    }
+
+    #[test]
+    fn unmeasured_values_round_trip() {
+        let (frame, len) = frame(&NO_PRESSURE);
+        assert_eq!(Record::decode(&frame[..len - 1]), Ok(NO_PRESSURE));
+    }
+
+    #[test]
+    fn cobs_round_trips() {
+        let inputs: [&[u8]; 5] = [&[], &[0], &[0, 0], &[1, 0, 2, 0], &[0x11; 300]];
+        for data in inputs.iter() {
+            let mut encoded = [0u8; 400];
+            let len = cobs_encode(data, &mut encoded);
+            assert!(len <= cobs_max_len(data.len()));
+            assert!(!encoded[..len].contains(&0));
+            let mut decoded = [0u8; 400];
+            let decoded_len = cobs_decode(&encoded[..len], &mut decoded).unwrap();
+            assert_eq!(&decoded[..decoded_len], *data);
+        }
+    }
+
+    #[test]
+    fn decoder_resynchronises_after_garbage() {
+        let (frame, len) = frame(&RECORD);
+        let mut corrupt = frame;
+        corrupt[3] ^= 0x40;
+
+        let mut decoder = Decoder::new();
+        let mut results = [None; 4];
+        let mut count = 0;
+        let stream = [
+            &[0x55, 0x12, 0][..],
+            &frame[..len],
+            &corrupt[..len],
+            &frame[..len],
+        ];
+        for byte in stream.iter().flat_map(|part| part.iter()) {
+            if let Some(result) = decoder.feed(*byte) {
+                results[count] = Some(result);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 4);
+        assert!(matches!(results[0], Some(Err(_))));
+        assert_eq!(results[1], Some(Ok(RECORD)));
+        assert_eq!(results[2], Some(Err(Error::Crc)));
+        assert_eq!(results[3], Some(Ok(RECORD)));
+    }
+
+    #[test]
+    fn decoder_rejects_long_frames() {
+        let mut decoder = Decoder::new();
+        for _ in 0..MAX_FRAME + 5 {
+            assert_eq!(decoder.feed(0x01), None);
+        }
+        assert_eq!(decoder.feed(0), Some(Err(Error::Overflow)));
+    }
+
+    #[test]
+    fn csv_line() {
+        struct Line([u8; 64], usize);
+        impl uWrite for Line {
+            type Error = ();
+            fn write_str(&mut self, s: &str) -> Result<(), ()> {
+                self.0[self.1..self.1 + s.len()].copy_from_slice(s.as_bytes());
+                self.1 += s.len();
+                Ok(())
+            }
+        }
+        let mut line = Line([0; 64], 0);
+        RECORD.write_csv(&mut line).unwrap();
+        assert_eq!(&line.0[..line.1], b"1600000000,-3.25,0.00,101325");
+
+        let mut line = Line([0; 64], 0);
+        NO_PRESSURE.write_csv(&mut line).unwrap();
+        assert_eq!(&line.0[..line.1], b"1600000000,-3.25,0.00,");
+    }
+}